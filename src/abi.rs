@@ -0,0 +1,590 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use serde::Serialize;
+
+use crate::{
+    analysis::Mutability,
+    cfg::{AbstractValue, Cfg},
+    loader::Program,
+    parser::Instruction,
+};
+
+// Revert selectors emitted by the compiler itself rather than by `error` declarations.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71]; // Panic(uint256)
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0]; // Error(string)
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbiType {
+    Uint(usize),
+    Int(usize),
+    Address,
+    Bool,
+    FixedBytes(usize),
+    Bytes,
+    Array(Box<AbiType>),
+}
+
+impl fmt::Display for AbiType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbiType::Uint(bits) => write!(f, "uint{}", bits),
+            AbiType::Int(bits) => write!(f, "int{}", bits),
+            AbiType::Address => write!(f, "address"),
+            AbiType::Bool => write!(f, "bool"),
+            AbiType::FixedBytes(size) => write!(f, "bytes{}", size),
+            AbiType::Bytes => write!(f, "bytes"),
+            AbiType::Array(inner) => write!(f, "{}[]", inner),
+        }
+    }
+}
+
+// Signature used for functions whose selector is not in the database, e.g. `func_3fb5c1cb(uint256)`.
+pub fn guess_signature(selector: [u8; 4], parameters: &[AbiType]) -> String {
+    let parameters: Vec<String> = parameters.iter().map(|p| p.to_string()).collect();
+    format!("func_{}({})", hex::encode(selector), parameters.join(","))
+}
+
+// What the code does with a calldata word, collected while walking a function.
+#[derive(Debug)]
+enum Hint {
+    Loaded,
+    Type(AbiType),
+    Dynamic,
+    Copied,
+}
+
+// Abstract value tracking where a stack item comes from.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Value {
+    Const([u8; 32]),
+    Arg(usize),    // head word of the n-th argument, possibly cleaned up
+    IsZero(usize), // ISZERO of the n-th argument
+    Offset(usize), // calldata pointer derived from the n-th (dynamic) argument
+    Unknown,
+}
+
+impl AbstractValue for Value {
+    fn unknown() -> Self {
+        Value::Unknown
+    }
+
+    fn jump_target(&self) -> Option<usize> {
+        match self {
+            Value::Const(word) => small(word),
+            _ => None,
+        }
+    }
+
+    fn apply(instruction: &Instruction, inputs: &[Self]) -> Self {
+        match (instruction.opcode, inputs) {
+            // PUSH0..PUSH32
+            (0x5F..=0x7F, _) => {
+                let mut word = [0u8; 32];
+                word[32 - instruction.data.len()..].copy_from_slice(&instruction.data);
+                Value::Const(word)
+            }
+            // ADD
+            (0x01, [Value::Const(a), Value::Const(b)]) => fold(a, b, u64::checked_add),
+            (0x01, [Value::Arg(n), Value::Const(_)] | [Value::Const(_), Value::Arg(n)]) => {
+                Value::Offset(*n)
+            }
+            (0x01, [Value::Offset(n), _] | [_, Value::Offset(n)]) => Value::Offset(*n),
+            // SUB
+            (0x03, [Value::Const(a), Value::Const(b)]) => fold(a, b, u64::checked_sub),
+            // AND
+            (0x16, [Value::Const(a), Value::Const(b)]) => {
+                let mut word = [0u8; 32];
+                for i in 0..32 {
+                    word[i] = a[i] & b[i];
+                }
+                Value::Const(word)
+            }
+            (0x16, [Value::Arg(n), Value::Const(_)] | [Value::Const(_), Value::Arg(n)]) => {
+                Value::Arg(*n)
+            }
+            // SHL by whole bytes, used to left-align error selectors
+            (0x1B, [Value::Const(shift), Value::Const(value)]) => match small(shift) {
+                Some(shift) if shift % 8 == 0 && shift < 256 => {
+                    let shift = shift / 8;
+                    let mut word = [0u8; 32];
+                    word[..32 - shift].copy_from_slice(&value[shift..]);
+                    Value::Const(word)
+                }
+                _ => Value::Unknown,
+            },
+            // SIGNEXTEND
+            (0x0B, [Value::Const(_), Value::Arg(n)]) => Value::Arg(*n),
+            // ISZERO
+            (0x15, [Value::Arg(n)]) => Value::IsZero(*n),
+            (0x15, [Value::IsZero(n)]) => Value::Arg(*n),
+            // CALLDATALOAD
+            (0x35, [Value::Const(word)]) => match small(word) {
+                Some(offset) if offset >= 4 && (offset - 4) % 32 == 0 => {
+                    Value::Arg((offset - 4) / 32)
+                }
+                _ => Value::Unknown,
+            },
+            _ => Value::Unknown,
+        }
+    }
+}
+
+// Guess parameter types of the function starting at `entry` from how it reads calldata.
+// Head words are classified by the cleanup applied to them: `AND` masks (address, uintN,
+// bytesN), `SIGNEXTEND` (intN) and `ISZERO ISZERO` (bool). A word used as a calldata
+// offset is a dynamic argument, `bytes` when copied with `CALLDATACOPY`, an array otherwise.
+pub fn infer_parameters(cfg: &Cfg, entry: usize) -> Vec<AbiType> {
+    let mut hints: BTreeMap<usize, Vec<Hint>> = BTreeMap::new();
+
+    cfg.walk_with::<Value>(entry, |instruction, inputs| {
+        let hint = match (instruction.opcode, inputs) {
+            // AND
+            (0x16, [Value::Arg(n), Value::Const(mask)] | [Value::Const(mask), Value::Arg(n)]) => {
+                mask_type(mask).map(|t| (*n, Hint::Type(t)))
+            }
+            // SIGNEXTEND
+            (0x0B, [Value::Const(size), Value::Arg(n)]) => small(size)
+                .filter(|&size| size < 31)
+                .map(|size| (*n, Hint::Type(AbiType::Int((size + 1) * 8)))),
+            // ISZERO
+            (0x15, [Value::IsZero(n)]) => Some((*n, Hint::Type(AbiType::Bool))),
+            // CALLDATALOAD
+            (0x35, [Value::Offset(n)]) => Some((*n, Hint::Dynamic)),
+            (0x35, [Value::Const(_)]) => match Value::apply(instruction, inputs) {
+                Value::Arg(n) => Some((n, Hint::Loaded)),
+                _ => None,
+            },
+            // CALLDATACOPY
+            (0x37, [_, Value::Offset(n), _]) => Some((*n, Hint::Copied)),
+            _ => None,
+        };
+
+        if let Some((n, hint)) = hint {
+            hints.entry(n).or_default().push(hint);
+        }
+    });
+
+    let count = hints.keys().next_back().map(|n| n + 1).unwrap_or(0);
+    (0..count)
+        .map(|n| resolve(hints.get(&n).map(Vec::as_slice).unwrap_or(&[])))
+        .collect()
+}
+
+fn resolve(hints: &[Hint]) -> AbiType {
+    if hints.iter().any(|h| matches!(h, Hint::Copied)) {
+        return AbiType::Bytes;
+    }
+    if hints.iter().any(|h| matches!(h, Hint::Dynamic)) {
+        return AbiType::Array(Box::new(AbiType::Uint(256)));
+    }
+
+    let types: Vec<&AbiType> = hints
+        .iter()
+        .filter_map(|h| match h {
+            Hint::Type(t) => Some(t),
+            _ => None,
+        })
+        .collect();
+
+    // the most specific cleanup wins, narrower masks come from the parameter itself
+    // while wider ones are usually arithmetic on it
+    let rank = |t: &AbiType| match t {
+        AbiType::Bool => (0, 0),
+        AbiType::Address => (1, 0),
+        AbiType::FixedBytes(size) => (2, *size),
+        AbiType::Int(bits) => (3, *bits),
+        AbiType::Uint(bits) => (4, *bits),
+        _ => (5, 0),
+    };
+
+    types
+        .into_iter()
+        .min_by_key(|t| rank(t))
+        .cloned()
+        .unwrap_or(AbiType::Uint(256))
+}
+
+pub struct Event {
+    pub topic: [u8; 32],
+    pub indexed: usize, // number of topics after the signature hash
+    pub offset: usize,  // LOGn emitting the event
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Event {{ topic: 0x{}, indexed: {}, offset: 0x{:04x} }}",
+            hex::encode(self.topic),
+            self.indexed,
+            self.offset,
+        )
+    }
+}
+
+// Find events by their signature hash, a constant first topic reaching LOG1..LOG4.
+pub fn analyze_events(cfg: &Cfg) -> Vec<Event> {
+    let mut events: Vec<Event> = Vec::new();
+
+    cfg.walk_with::<Value>(0, |instruction, inputs| {
+        // LOG1..LOG4, inputs are offset, size, topic0, ...
+        if !(0xA1..=0xA4).contains(&instruction.opcode) {
+            return;
+        }
+        let Some(Value::Const(topic)) = inputs.get(2) else {
+            return;
+        };
+        // small constants are not hashes
+        if small(topic).is_some() || events.iter().any(|e| e.topic == *topic) {
+            return;
+        }
+
+        events.push(Event {
+            topic: *topic,
+            indexed: (instruction.opcode - 0xA1) as usize,
+            offset: instruction.offset,
+        });
+    });
+
+    events.sort_by_key(|e| e.offset);
+    events
+}
+
+pub struct CustomError {
+    pub selector: [u8; 4],
+    pub offset: usize, // REVERT raising the error
+}
+
+impl fmt::Debug for CustomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CustomError {{ selector: 0x{}, offset: 0x{:04x} }}",
+            hex::encode(self.selector),
+            self.offset,
+        )
+    }
+}
+
+// Find custom errors by the selector stored to memory right before a REVERT, either
+// left-aligned (`shl(224, selector)`) or stored at 0 and reverted from offset 0x1c.
+// The store has to be in the REVERT's block and the REVERT has to start reading at the
+// selector, a selector stored for an external call is not an error.
+// Panic(uint256) and Error(string) come from the compiler and are skipped.
+pub fn analyze_errors(cfg: &Cfg) -> Vec<CustomError> {
+    let mut errors: Vec<CustomError> = Vec::new();
+    // memory offset of the stored selector, and the selector
+    let mut stored: Option<(Value, [u8; 4])> = None;
+
+    cfg.walk_with::<Value>(0, |instruction, inputs| {
+        // the walk is depth first, stores of another path must not be carried over
+        if cfg.blocks.contains_key(&instruction.offset) {
+            stored = None;
+        }
+
+        match (instruction.opcode, inputs) {
+            // MSTORE
+            (0x52, [offset, Value::Const(value)]) => {
+                if value[..4] != [0; 4] && value[4..] == [0; 28] {
+                    stored = Some((offset.clone(), [value[0], value[1], value[2], value[3]]));
+                } else if value[..28] == [0; 28]
+                    && value[28] != 0
+                    && let Value::Const(word) = offset
+                    && let Some(start) = small(word).and_then(|start| start.checked_add(28))
+                {
+                    let mut start_word = [0; 32];
+                    start_word[24..].copy_from_slice(&(start as u64).to_be_bytes());
+                    stored = Some((
+                        Value::Const(start_word),
+                        [value[28], value[29], value[30], value[31]],
+                    ));
+                }
+            }
+            // REVERT
+            (0xFD, [offset, Value::Const(size)]) if small(size).is_some_and(|size| size >= 4) => {
+                let selector = match stored.take() {
+                    Some((start, selector)) if start == *offset => selector,
+                    _ => return,
+                };
+
+                if selector != PANIC_SELECTOR
+                    && selector != ERROR_SELECTOR
+                    && !errors.iter().any(|e| e.selector == selector)
+                {
+                    errors.push(CustomError {
+                        selector,
+                        offset: instruction.offset,
+                    });
+                }
+            }
+            _ => {}
+        }
+    });
+
+    errors.sort_by_key(|e| e.offset);
+    errors
+}
+
+// Entry of a Solidity ABI JSON, extended with a confidence score between 0 and 1.
+#[derive(Serialize)]
+pub struct AbiItem {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub name: String,
+    pub inputs: Vec<AbiParam>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<Vec<AbiParam>>,
+    #[serde(rename = "stateMutability", skip_serializing_if = "Option::is_none")]
+    pub state_mutability: Option<Mutability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anonymous: Option<bool>,
+    pub confidence: f32,
+}
+
+#[derive(Serialize)]
+pub struct AbiParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indexed: Option<bool>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<AbiParam>,
+}
+
+impl AbiParam {
+    fn new(kind: String) -> Self {
+        AbiParam {
+            name: String::new(),
+            kind,
+            indexed: None,
+            components: Vec::new(),
+        }
+    }
+}
+
+// Reconstruct the ABI of a program from its entrypoints, events and errors.
+// Signatures come from the selectors database when possible, otherwise they are guessed.
+// Return values are never recovered, `outputs` is always empty.
+pub fn reconstruct_abi(program: &Program, selectors: &HashMap<u32, String>) -> Vec<AbiItem> {
+    let mut items = Vec::new();
+
+    for entry in &program.entrypoints {
+        let (signature, guessed) = entry.signature(selectors);
+        let Some((name, inputs)) = parse_signature(&signature) else {
+            continue;
+        };
+
+        items.push(AbiItem {
+            kind: "function",
+            name,
+            inputs,
+            outputs: Some(Vec::new()),
+            state_mutability: Some(entry.mutability),
+            anonymous: None,
+            confidence: if guessed { 0.5 } else { 0.9 },
+        });
+    }
+
+    for event in &program.events {
+        // topics are full keccak hashes, the 4 byte database can't name them
+        let inputs = (0..event.indexed)
+            .map(|_| AbiParam {
+                indexed: Some(true),
+                ..AbiParam::new("bytes32".to_string())
+            })
+            .collect();
+
+        items.push(AbiItem {
+            kind: "event",
+            name: format!("event_{}", hex::encode(&event.topic[..4])),
+            inputs,
+            outputs: None,
+            state_mutability: None,
+            anonymous: Some(false),
+            confidence: 0.3,
+        });
+    }
+
+    for error in &program.errors {
+        let known = selectors
+            .get(&u32::from_be_bytes(error.selector))
+            .and_then(|signature| parse_signature(signature));
+
+        let (name, inputs, confidence) = match known {
+            Some((name, inputs)) => (name, inputs, 0.8),
+            None => (
+                format!("error_{}", hex::encode(error.selector)),
+                Vec::new(),
+                0.3,
+            ),
+        };
+
+        items.push(AbiItem {
+            kind: "error",
+            name,
+            inputs,
+            outputs: None,
+            state_mutability: None,
+            anonymous: None,
+            confidence,
+        });
+    }
+
+    items
+}
+
+// Split `transfer(address,uint256)` into a name and ABI parameters, tuples become components.
+pub fn parse_signature(signature: &str) -> Option<(String, Vec<AbiParam>)> {
+    let open = signature.find('(')?;
+    let close = signature.rfind(')')?;
+    if close < open {
+        return None;
+    }
+
+    let name = signature[..open].to_string();
+    Some((name, parse_params(&signature[open + 1..close])))
+}
+
+fn parse_params(params: &str) -> Vec<AbiParam> {
+    let mut result = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in params.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(parse_param(&params[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if !params.trim().is_empty() {
+        result.push(parse_param(&params[start..]));
+    }
+
+    result
+}
+
+fn parse_param(param: &str) -> AbiParam {
+    let param = param.trim();
+    match (param.strip_prefix('('), param.rfind(')')) {
+        (Some(_), Some(close)) => AbiParam {
+            components: parse_params(&param[1..close]),
+            ..AbiParam::new(format!("tuple{}", &param[close + 1..]))
+        },
+        _ => AbiParam::new(param.to_string()),
+    }
+}
+
+// 0x00..00ff..ff masks clean up addresses and short integers, 0xff..ff00..00 fixed bytes.
+fn mask_type(mask: &[u8; 32]) -> Option<AbiType> {
+    let leading_zeros = mask.iter().take_while(|&&b| b == 0x00).count();
+    if leading_zeros > 0 && leading_zeros < 32 && mask[leading_zeros..].iter().all(|&b| b == 0xff) {
+        return Some(match 32 - leading_zeros {
+            20 => AbiType::Address,
+            size => AbiType::Uint(size * 8),
+        });
+    }
+
+    let leading_ones = mask.iter().take_while(|&&b| b == 0xff).count();
+    if leading_ones > 0 && leading_ones < 32 && mask[leading_ones..].iter().all(|&b| b == 0x00) {
+        return Some(AbiType::FixedBytes(leading_ones));
+    }
+
+    None
+}
+
+fn small(word: &[u8; 32]) -> Option<usize> {
+    if word[..24].iter().any(|&b| b != 0) {
+        return None;
+    }
+
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&word[24..]);
+    usize::try_from(u64::from_be_bytes(bytes)).ok()
+}
+
+fn fold(a: &[u8; 32], b: &[u8; 32], op: fn(u64, u64) -> Option<u64>) -> Value {
+    match (small(a), small(b)) {
+        (Some(a), Some(b)) => match op(a as u64, b as u64) {
+            Some(result) => {
+                let mut word = [0u8; 32];
+                word[24..].copy_from_slice(&result.to_be_bytes());
+                Value::Const(word)
+            }
+            None => Value::Unknown,
+        },
+        _ => Value::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_bytecode;
+
+    fn infer(code: &str) -> Vec<AbiType> {
+        let instructions = parse_bytecode(&hex::decode(code).unwrap());
+        infer_parameters(&Cfg::from_instructions(&instructions), 0)
+    }
+
+    #[test]
+    fn address_and_bool_cleanup() {
+        // PUSH1 0x04 CALLDATALOAD PUSH20 0xff..ff AND POP
+        // PUSH1 0x24 CALLDATALOAD ISZERO ISZERO POP STOP
+        let code = format!("60043573{}165060243515155000", "ff".repeat(20));
+        assert_eq!(infer(&code), vec![AbiType::Address, AbiType::Bool]);
+    }
+
+    #[test]
+    fn dynamic_bytes() {
+        // PUSH1 0x04 CALLDATALOAD PUSH1 0x04 ADD DUP1 CALLDATALOAD (length)
+        // SWAP1 PUSH1 0x20 ADD PUSH0 CALLDATACOPY STOP
+        assert_eq!(
+            infer("6004356004018035906020015f3700"),
+            vec![AbiType::Bytes]
+        );
+    }
+
+    #[test]
+    fn custom_error_revert() {
+        // PUSH4 0xdeadbeef PUSH1 0xe0 SHL PUSH0 MSTORE PUSH1 0x04 PUSH0 REVERT
+        let instructions = parse_bytecode(&hex::decode("63deadbeef60e01b5f5260045ffd").unwrap());
+        let errors = analyze_errors(&Cfg::from_instructions(&instructions));
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].selector, [0xde, 0xad, 0xbe, 0xef]);
+
+        // PUSH4 0x12345678 PUSH1 0xe0 SHL PUSH0 MSTORE, then CALL(gas, caller, 0, 0, 4, 0, 0)
+        // and on failure RETURNDATASIZE PUSH0 REVERT bubbling the callee's revert up
+        let instructions = parse_bytecode(
+            &hex::decode("631234567860e01b5f525f5f60045f5f335af16019573d5ffd5b00").unwrap(),
+        );
+        assert!(analyze_errors(&Cfg::from_instructions(&instructions)).is_empty());
+    }
+
+    #[test]
+    fn tuple_signature() {
+        let (name, params) = parse_signature("swap((address,uint256)[],bytes)").unwrap();
+
+        assert_eq!(name, "swap");
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0].kind, "tuple[]");
+        assert_eq!(params[0].components.len(), 2);
+        assert_eq!(params[1].kind, "bytes");
+    }
+
+    #[test]
+    fn guessed_signature() {
+        let signature = guess_signature([0x3f, 0xb5, 0xc1, 0xcb], &[AbiType::Uint(256)]);
+        assert_eq!(signature, "func_3fb5c1cb(uint256)");
+    }
+}
@@ -1,11 +1,25 @@
-use std::fmt;
+use std::{collections::HashMap, fmt};
 
-use crate::parser::Instruction;
+use serde::Serialize;
+
+use crate::{
+    abi::{self, AbiType, CustomError, Event},
+    cfg::Cfg,
+    parser::Instruction,
+};
 
 pub struct Analysis {
     pub function_selectors: Vec<FunctionSelector>,
     pub functions: Vec<Function>,
     pub function_entrypoints: Vec<FunctionEntrypoint>,
+    pub events: Vec<Event>,
+    pub errors: Vec<CustomError>,
+}
+
+impl Default for Analysis {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Analysis {
@@ -14,14 +28,20 @@ impl Analysis {
             function_selectors: Vec::new(),
             functions: Vec::new(),
             function_entrypoints: Vec::new(),
+            events: Vec::new(),
+            errors: Vec::new(),
         }
     }
 
     pub fn from_instructions(instructions: &[Instruction]) -> Self {
+        let cfg = Cfg::from_instructions(instructions);
+
         Self {
             function_selectors: analyze_function_selectors(instructions),
             functions: analyze_functions(instructions),
             function_entrypoints: analyze_function_entrypoints(instructions),
+            events: abi::analyze_events(&cfg),
+            errors: abi::analyze_errors(&cfg),
         }
     }
 }
@@ -30,6 +50,7 @@ impl Analysis {
 pub struct FunctionSelector {
     pub offset: usize,
     pub selector: [u8; 4],
+    pub name: Option<String>,
 }
 
 impl fmt::Debug for FunctionSelector {
@@ -38,7 +59,7 @@ impl fmt::Debug for FunctionSelector {
             f,
             "Selector {{ offset: {}, selector: 0x{} }}",
             self.offset,
-            hex::encode(&self.selector),
+            hex::encode(self.selector),
         )
     }
 }
@@ -61,6 +82,7 @@ pub fn analyze_function_selectors(instructions: &[Instruction]) -> Vec<FunctionS
                 Some(FunctionSelector {
                     offset: first.offset,
                     selector,
+                    name: Some(format!("func_{}", hex::encode(selector))),
                 })
             } else {
                 None
@@ -80,7 +102,7 @@ impl fmt::Debug for Function {
         write!(
             f,
             "Function {{ selector: 0x{}, start: 0x{:04x}, end: 0x{:04x} }}",
-            hex::encode(&self.selector),
+            hex::encode(self.selector),
             self.start,
             self.end,
         )
@@ -126,20 +148,69 @@ pub fn analyze_functions(instructions: &[Instruction]) -> Vec<Function> {
 pub struct FunctionEntrypoint {
     pub selector: [u8; 4],
     pub offset: usize,
+    pub mutability: Mutability,
+    pub parameters: Vec<AbiType>,
+}
+
+impl FunctionEntrypoint {
+    // Signature from the selectors database, or one guessed from the inferred parameters.
+    // The flag is true when the signature is a guess.
+    pub fn signature(&self, selectors: &HashMap<u32, String>) -> (String, bool) {
+        match selectors.get(&u32::from_be_bytes(self.selector)) {
+            Some(signature) => (signature.clone(), false),
+            None => (abi::guess_signature(self.selector, &self.parameters), true),
+        }
+    }
 }
 
 impl fmt::Debug for FunctionEntrypoint {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Function {{ selector: 0x{}, offset: 0x{:04x} }}",
-            hex::encode(&self.selector),
+            "Function {{ selector: 0x{}, offset: 0x{:04x}, mutability: {} }}",
+            hex::encode(self.selector),
             self.offset,
+            self.mutability,
         )
     }
 }
 
+// Solidity ABI state mutability, inferred from the bytecode reachable from a function entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mutability {
+    Pure,
+    View,
+    NonPayable,
+    Payable,
+}
+
+impl fmt::Display for Mutability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Mutability::Pure => "pure",
+            Mutability::View => "view",
+            Mutability::NonPayable => "nonpayable",
+            Mutability::Payable => "payable",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 pub fn analyze_function_entrypoints(instructions: &[Instruction]) -> Vec<FunctionEntrypoint> {
+    let cfg = Cfg::from_instructions(instructions);
+
+    // solc hoists the callvalue check in front of the dispatcher when no function is payable
+    let dispatcher_end = analyze_function_selectors(instructions)
+        .first()
+        .map(|s| s.offset)
+        .unwrap_or(0);
+    let dispatcher_guarded = instructions
+        .iter()
+        .take_while(|i| i.offset < dispatcher_end)
+        .enumerate()
+        .any(|(idx, _)| is_callvalue_guard(&instructions[idx..]));
+
     instructions
         .windows(4)
         .filter_map(|w| {
@@ -161,8 +232,15 @@ pub fn analyze_function_entrypoints(instructions: &[Instruction]) -> Vec<Functio
                 selector.copy_from_slice(&first.data);
 
                 let offset = bytes_to_usize(&third.data);
+                let mutability = infer_mutability(&cfg, offset, dispatcher_guarded);
+                let parameters = abi::infer_parameters(&cfg, offset);
 
-                return Some(FunctionEntrypoint { selector, offset });
+                return Some(FunctionEntrypoint {
+                    selector,
+                    offset,
+                    mutability,
+                    parameters,
+                });
             }
 
             None
@@ -170,6 +248,60 @@ pub fn analyze_function_entrypoints(instructions: &[Instruction]) -> Vec<Functio
         .collect()
 }
 
+// Infer mutability of the function starting at `entry`.
+// Functions without the `CALLVALUE DUP1 ISZERO ... REVERT` guard accept ether and are payable.
+// Guarded ones are view when nothing reachable writes state and pure when nothing reads it either.
+pub fn infer_mutability(cfg: &Cfg, entry: usize, dispatcher_guarded: bool) -> Mutability {
+    let guarded = dispatcher_guarded
+        || cfg.blocks.get(&entry).is_some_and(|block| {
+            (0..block.instructions.len().min(3))
+                .any(|idx| is_callvalue_guard(&block.instructions[idx..]))
+        });
+
+    if !guarded {
+        return Mutability::Payable;
+    }
+
+    let walk = cfg.walk(entry);
+    let opcodes: Vec<u8> = cfg.instructions(&walk.blocks).map(|i| i.opcode).collect();
+
+    if opcodes.iter().any(|&op| writes_state(op)) {
+        Mutability::NonPayable
+    } else if opcodes.iter().any(|&op| reads_state(op)) {
+        Mutability::View
+    } else {
+        Mutability::Pure
+    }
+}
+
+// CALLVALUE DUP1 ISZERO PUSH JUMPI (legacy) or CALLVALUE PUSH JUMPI (via-IR)
+fn is_callvalue_guard(instructions: &[Instruction]) -> bool {
+    let opcodes: Vec<u8> = instructions.iter().take(5).map(|i| i.opcode).collect();
+    let is_push = |op: &u8| (0x60..=0x62).contains(op);
+
+    match opcodes.as_slice() {
+        [0x34, 0x80, 0x15, push, 0x57, ..] => is_push(push),
+        [0x34, push, 0x57, ..] => is_push(push),
+        _ => false,
+    }
+}
+
+// SSTORE, TSTORE, LOGn, CREATE, CALL, CALLCODE, DELEGATECALL, CREATE2, SELFDESTRUCT
+fn writes_state(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        0x55 | 0x5D | 0xA0..=0xA4 | 0xF0 | 0xF1 | 0xF2 | 0xF4 | 0xF5 | 0xFF
+    )
+}
+
+// SLOAD, TLOAD, STATICCALL and reads of the account, transaction or block environment
+fn reads_state(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        0x30..=0x33 | 0x3A..=0x3C | 0x3F..=0x4A | 0x54 | 0x5A | 0x5C | 0xFA
+    )
+}
+
 fn find_function_end(instructions: &[Instruction], start_offset: usize) -> Option<usize> {
     let start_idx = instructions.iter().position(|i| i.offset == start_offset)?;
 
@@ -233,6 +365,7 @@ mod tests {
             FunctionSelector {
                 offset: 100,
                 selector: [0x11, 0x22, 0x33, 0x44],
+                name: Some("func_11223344".to_string()),
             }
         )
     }
@@ -0,0 +1,488 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    hash::Hash,
+};
+
+use crate::parser::{self, Instruction};
+
+// Upper bound on abstract states visited by a single walk, keeps recursive code from exploding.
+const MAX_STATES: usize = 16384;
+const MAX_STACK: usize = 1024;
+
+pub struct Cfg {
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    // JUMP/JUMPI offsets for which no target could be resolved
+    pub unresolved: BTreeSet<usize>,
+}
+
+#[derive(Clone)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize, // offset of the last instruction
+    pub instructions: Vec<Instruction>,
+    pub successors: BTreeSet<usize>,
+    pub predecessors: BTreeSet<usize>,
+}
+
+impl BasicBlock {
+    pub fn last(&self) -> &Instruction {
+        self.instructions
+            .last()
+            .expect("basic block is never empty")
+    }
+
+    pub fn is_jumpdest(&self) -> bool {
+        self.instructions[0].opcode == 0x5B
+    }
+
+    // Offset right after the block, where execution continues unless it jumps or halts.
+    pub fn fallthrough(&self) -> Option<usize> {
+        let last = self.last();
+        if last.opcode == 0x56 || parser::is_terminator(last.opcode) {
+            return None;
+        }
+
+        Some(last.offset + 1 + last.data.len())
+    }
+}
+
+// Result of walking the code from a single entry with abstract stack values.
+#[derive(Default)]
+pub struct Walk {
+    pub blocks: BTreeSet<usize>,
+    pub edges: BTreeSet<(usize, usize)>,
+    pub unresolved: BTreeSet<usize>,
+}
+
+// Dominator tree of the blocks reachable from an entry.
+pub struct Dominators {
+    pub entry: usize,
+    pub immediate: BTreeMap<usize, usize>, // block to its immediate dominator, entry excluded
+}
+
+impl Dominators {
+    // Every path from the entry to `block` goes through `dominator`.
+    pub fn dominates(&self, dominator: usize, block: usize) -> bool {
+        let mut current = block;
+        loop {
+            if current == dominator {
+                return true;
+            }
+            match self.immediate.get(&current) {
+                Some(&parent) => current = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+// Value tracked on the abstract stack while walking the code.
+pub trait AbstractValue: Clone + Eq + Hash {
+    fn unknown() -> Self;
+
+    // Code offset held by the value, used to resolve JUMP and JUMPI targets.
+    fn jump_target(&self) -> Option<usize>;
+
+    // Output of any instruction other than DUPn/SWAPn, `inputs[0]` is the top of the stack.
+    fn apply(instruction: &Instruction, inputs: &[Self]) -> Self;
+}
+
+// Default abstract value, only constants that could be code offsets are tracked.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Slot {
+    Const(usize),
+    Unknown,
+}
+
+impl AbstractValue for Slot {
+    fn unknown() -> Self {
+        Slot::Unknown
+    }
+
+    fn jump_target(&self) -> Option<usize> {
+        match self {
+            Slot::Const(target) => Some(*target),
+            Slot::Unknown => None,
+        }
+    }
+
+    fn apply(instruction: &Instruction, inputs: &[Self]) -> Self {
+        match (instruction.opcode, inputs) {
+            // PUSH0..PUSH32
+            (0x5F..=0x7F, _) => push_slot(&instruction.data),
+            // ADD, SUB, AND on constants show up around jump targets
+            (opcode @ (0x01 | 0x03 | 0x16), [Slot::Const(a), Slot::Const(b)]) => match opcode {
+                0x01 => a.checked_add(*b).map(Slot::Const).unwrap_or(Slot::Unknown),
+                0x03 => a.checked_sub(*b).map(Slot::Const).unwrap_or(Slot::Unknown),
+                _ => Slot::Const(a & b),
+            },
+            _ => Slot::Unknown,
+        }
+    }
+}
+
+impl Cfg {
+    pub fn from_instructions(instructions: &[Instruction]) -> Self {
+        let mut cfg = Cfg {
+            blocks: split_blocks(instructions),
+            unresolved: BTreeSet::new(),
+        };
+
+        let walk = cfg.walk(0);
+
+        let mut edges = walk.edges;
+        for block in cfg.blocks.values() {
+            if let Some(next) = block.fallthrough()
+                && cfg.blocks.contains_key(&next)
+            {
+                edges.insert((block.start, next));
+            }
+        }
+
+        for (from, to) in edges {
+            if let Some(block) = cfg.blocks.get_mut(&from) {
+                block.successors.insert(to);
+            }
+            if let Some(block) = cfg.blocks.get_mut(&to) {
+                block.predecessors.insert(from);
+            }
+        }
+
+        cfg.unresolved = walk.unresolved;
+        cfg
+    }
+
+    // Block containing the instruction at given offset.
+    pub fn block_at(&self, offset: usize) -> Option<&BasicBlock> {
+        self.blocks
+            .range(..=offset)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| offset <= block.end)
+    }
+
+    pub fn instructions<'a>(
+        &'a self,
+        blocks: &'a BTreeSet<usize>,
+    ) -> impl Iterator<Item = &'a Instruction> {
+        blocks
+            .iter()
+            .filter_map(|start| self.blocks.get(start))
+            .flat_map(|block| block.instructions.iter())
+    }
+
+    // Dominators over the resolved edges, with the iterative algorithm of Cooper, Harvey
+    // and Kennedy.
+    pub fn dominators(&self, entry: usize) -> Dominators {
+        // reverse postorder of the blocks reachable from the entry
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![(entry, false)];
+        while let Some((start, done)) = stack.pop() {
+            if done {
+                order.push(start);
+                continue;
+            }
+            let Some(block) = self.blocks.get(&start) else {
+                continue;
+            };
+            if !visited.insert(start) {
+                continue;
+            }
+            stack.push((start, true));
+            for &successor in block.successors.iter().rev() {
+                if !visited.contains(&successor) {
+                    stack.push((successor, false));
+                }
+            }
+        }
+        order.reverse();
+        let index: BTreeMap<usize, usize> =
+            order.iter().enumerate().map(|(i, &b)| (b, i)).collect();
+
+        let mut immediate: BTreeMap<usize, usize> = BTreeMap::from([(entry, entry)]);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let mut dominator = None;
+                for predecessor in &self.blocks[&block].predecessors {
+                    if !immediate.contains_key(predecessor) {
+                        continue;
+                    }
+                    dominator = Some(match dominator {
+                        None => *predecessor,
+                        Some(current) => {
+                            // walk both up the tree until they meet
+                            let (mut a, mut b) = (current, *predecessor);
+                            while a != b {
+                                while index[&a] > index[&b] {
+                                    a = immediate[&a];
+                                }
+                                while index[&b] > index[&a] {
+                                    b = immediate[&b];
+                                }
+                            }
+                            a
+                        }
+                    });
+                }
+                if let Some(dominator) = dominator
+                    && immediate.get(&block) != Some(&dominator)
+                {
+                    immediate.insert(block, dominator);
+                    changed = true;
+                }
+            }
+        }
+
+        immediate.remove(&entry);
+        Dominators { entry, immediate }
+    }
+
+    // Explore everything reachable from `start`, resolving jumps with constants
+    // tracked on an abstract stack. Stack items below the entry are unknown.
+    // Exploration is context sensitive, so an internal function returning to
+    // different callers is followed back to each caller separately.
+    pub fn walk(&self, start: usize) -> Walk {
+        self.walk_with::<Slot>(start, |_, _| {})
+    }
+
+    // Same as `walk` with a custom abstract value. `visit` is called with every
+    // executed instruction (except DUPn/SWAPn) and the stack items it consumes.
+    pub fn walk_with<V: AbstractValue>(
+        &self,
+        start: usize,
+        mut visit: impl FnMut(&Instruction, &[V]),
+    ) -> Walk {
+        self.walk_marked(
+            start,
+            |_| false,
+            |instruction, inputs, _| visit(instruction, inputs),
+        )
+    }
+
+    // Same as `walk_with`, also passing `visit` the offset of the first instruction
+    // `mark` returned true for on the path to this one. Code reached both before and
+    // after a marked instruction is walked for each.
+    pub fn walk_marked<V: AbstractValue>(
+        &self,
+        start: usize,
+        mark: impl Fn(&Instruction) -> bool,
+        mut visit: impl FnMut(&Instruction, &[V], Option<usize>),
+    ) -> Walk {
+        let mut walk = Walk::default();
+        let mut resolved = BTreeSet::new();
+
+        if !self.blocks.contains_key(&start) {
+            return walk;
+        }
+
+        let mut visited = HashSet::new();
+        let mut queue: Vec<(usize, Vec<V>, Option<usize>)> = vec![(start, Vec::new(), None)];
+
+        while let Some((offset, mut stack, mut marked)) = queue.pop() {
+            if visited.len() >= MAX_STATES || stack.len() > MAX_STACK {
+                continue;
+            }
+            if !visited.insert((offset, stack.clone(), marked)) {
+                continue;
+            }
+
+            let Some(block) = self.blocks.get(&offset) else {
+                continue;
+            };
+            walk.blocks.insert(offset);
+
+            let mut target = None;
+            for instruction in &block.instructions {
+                let mut visit =
+                    |instruction: &Instruction, inputs: &[V]| visit(instruction, inputs, marked);
+                if let Some(jump) = step(&mut stack, instruction, &mut visit) {
+                    target = Some(jump);
+                }
+                if marked.is_none() && mark(instruction) {
+                    marked = Some(instruction.offset);
+                }
+            }
+
+            let last = block.last();
+            match target.map(|t| t.jump_target()) {
+                Some(Some(target)) if self.blocks.get(&target).is_some_and(|b| b.is_jumpdest()) => {
+                    resolved.insert(last.offset);
+                    walk.edges.insert((offset, target));
+                    queue.push((target, stack.clone(), marked));
+                }
+                Some(None) => {
+                    walk.unresolved.insert(last.offset);
+                }
+                _ => {}
+            }
+
+            if let Some(next) = block.fallthrough()
+                && self.blocks.contains_key(&next)
+            {
+                walk.edges.insert((offset, next));
+                queue.push((next, stack, marked));
+            }
+        }
+
+        walk.unresolved.retain(|offset| !resolved.contains(offset));
+        walk
+    }
+}
+
+fn split_blocks(instructions: &[Instruction]) -> BTreeMap<usize, BasicBlock> {
+    let mut blocks = BTreeMap::new();
+    let mut current: Vec<Instruction> = Vec::new();
+
+    for instruction in instructions {
+        // JUMPDEST starts a new block
+        if instruction.opcode == 0x5B && !current.is_empty() {
+            push_block(&mut blocks, std::mem::take(&mut current));
+        }
+
+        current.push(instruction.clone());
+
+        // JUMP, JUMPI and halting instructions end a block
+        if matches!(instruction.opcode, 0x56 | 0x57) || parser::is_terminator(instruction.opcode) {
+            push_block(&mut blocks, std::mem::take(&mut current));
+        }
+    }
+
+    if !current.is_empty() {
+        push_block(&mut blocks, current);
+    }
+
+    blocks
+}
+
+fn push_block(blocks: &mut BTreeMap<usize, BasicBlock>, instructions: Vec<Instruction>) {
+    let start = instructions[0].offset;
+    let end = instructions[instructions.len() - 1].offset;
+    blocks.insert(
+        start,
+        BasicBlock {
+            start,
+            end,
+            instructions,
+            successors: BTreeSet::new(),
+            predecessors: BTreeSet::new(),
+        },
+    );
+}
+
+fn pop<V: AbstractValue>(stack: &mut Vec<V>) -> V {
+    stack.pop().unwrap_or_else(V::unknown)
+}
+
+// Make sure at least `depth` items are on the stack, padding the bottom with unknowns.
+fn ensure<V: AbstractValue>(stack: &mut Vec<V>, depth: usize) {
+    if stack.len() < depth {
+        let missing = depth - stack.len();
+        stack.splice(0..0, std::iter::repeat_n(V::unknown(), missing));
+    }
+}
+
+// Execute a single instruction on the abstract stack, returns the target of JUMP/JUMPI.
+fn step<V: AbstractValue>(
+    stack: &mut Vec<V>,
+    instruction: &Instruction,
+    visit: &mut impl FnMut(&Instruction, &[V]),
+) -> Option<V> {
+    let opcode = instruction.opcode;
+    match opcode {
+        // DUP1..DUP16
+        0x80..=0x8F => {
+            let n = (opcode - 0x7F) as usize;
+            ensure(stack, n);
+            let value = stack[stack.len() - n].clone();
+            stack.push(value);
+            None
+        }
+        // SWAP1..SWAP16
+        0x90..=0x9F => {
+            let n = (opcode - 0x8F) as usize;
+            ensure(stack, n + 1);
+            let len = stack.len();
+            stack.swap(len - 1, len - 1 - n);
+            None
+        }
+        _ => {
+            let (pops, pushes) = parser::stack_io(opcode);
+            let inputs: Vec<V> = (0..pops).map(|_| pop(stack)).collect();
+            visit(instruction, &inputs);
+
+            if pushes == 1 {
+                stack.push(V::apply(instruction, &inputs));
+            }
+
+            // JUMP, JUMPI
+            if matches!(opcode, 0x56 | 0x57) {
+                return inputs.into_iter().next();
+            }
+            None
+        }
+    }
+}
+
+fn push_slot(data: &[u8]) -> Slot {
+    let significant = data.iter().skip_while(|&&b| b == 0).count();
+    if significant > 8 {
+        return Slot::Unknown;
+    }
+
+    Slot::Const(
+        data.iter()
+            .fold(0usize, |acc, &byte| (acc << 8) | byte as usize),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_bytecode;
+
+    #[test]
+    fn resolves_internal_call_and_return() {
+        // 00: PUSH1 0x06 PUSH1 0x0a JUMP
+        // 05: INVALID
+        // 06: JUMPDEST STOP
+        // 08: INVALID INVALID
+        // 0a: JUMPDEST JUMP
+        let bytecode = hex::decode("6006600a56fe5b00fefe5b56").unwrap();
+        let cfg = Cfg::from_instructions(&parse_bytecode(&bytecode));
+
+        let walk = cfg.walk(0);
+        assert!(walk.edges.contains(&(0x00, 0x0a)));
+        assert!(walk.edges.contains(&(0x0a, 0x06)));
+        assert!(walk.unresolved.is_empty());
+        assert!(!walk.blocks.contains(&0x05));
+    }
+
+    #[test]
+    fn loop_header_dominates_body() {
+        // 00: PUSH1 0
+        // 02: JUMPDEST PUSH1 1 ADD DUP1 CALLDATASIZE GT PUSH1 2 JUMPI
+        // 0c: STOP
+        let bytecode = hex::decode("60005b60010180361160025700").unwrap();
+        let cfg = Cfg::from_instructions(&parse_bytecode(&bytecode));
+
+        let dominators = cfg.dominators(0);
+        assert_eq!(
+            dominators.immediate,
+            BTreeMap::from([(0x02, 0x00), (0x0c, 0x02)])
+        );
+        assert!(dominators.dominates(0x02, 0x0c));
+        assert!(!dominators.dominates(0x0c, 0x02));
+    }
+
+    #[test]
+    fn unknown_jump_target_is_unresolved() {
+        // CALLDATASIZE JUMP
+        let bytecode = hex::decode("3656").unwrap();
+        let cfg = Cfg::from_instructions(&parse_bytecode(&bytecode));
+
+        assert_eq!(cfg.unresolved, BTreeSet::from([0x01]));
+    }
+}
@@ -0,0 +1,492 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    cfg::{Cfg, Slot},
+    parser::{self, Instruction},
+};
+
+// keys a metadata CBOR must contain one of, anything else is code that happens to parse
+const METADATA_KEYS: &[&str] = &["solc", "vyper", "ipfs", "bzzr0", "bzzr1", "experimental"];
+
+// Panic(uint256) selector, solidity reverts with it since 0.8.0
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Family {
+    Solidity,
+    Vyper,
+    Huff,
+    Yul,
+}
+
+impl Family {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Family::Solidity => "solidity",
+            Family::Vyper => "vyper",
+            Family::Huff => "huff",
+            Family::Yul => "yul",
+        }
+    }
+}
+
+// Solidity code generator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pipeline {
+    Legacy,
+    ViaIr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Compiler {
+    pub family: Family,
+    pub version: Option<String>,           // exact, from the metadata
+    pub min_version: Option<&'static str>, // from opcodes when there's no metadata
+    pub pipeline: Option<Pipeline>,        // guessed from code generation idioms
+    pub optimized: Option<bool>,
+    pub guessed: bool, // family guessed from idioms, the code has no metadata
+}
+
+impl fmt::Display for Compiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.family.name())?;
+
+        match (&self.version, self.min_version) {
+            (Some(version), _) => write!(f, " {}", version)?,
+            (None, Some(version)) => write!(f, " >={}", version)?,
+            (None, None) => {}
+        }
+
+        match self.pipeline {
+            Some(Pipeline::Legacy) => write!(f, ", legacy codegen")?,
+            Some(Pipeline::ViaIr) => write!(f, ", via-ir")?,
+            None => {}
+        }
+
+        match self.optimized {
+            Some(true) => write!(f, ", optimized")?,
+            Some(false) => write!(f, ", not optimized")?,
+            None => {}
+        }
+
+        if self.guessed {
+            write!(f, " (guessed)")?;
+        }
+
+        Ok(())
+    }
+}
+
+// Compiler family and version, from the CBOR metadata when there is one, otherwise
+// guessed from code generation idioms. Code matching none of them may be hand written
+// and gets no compiler.
+pub fn detect_compiler(bytes: &[u8]) -> Option<Compiler> {
+    let (metadata_start, metadata) = match parse_metadata(bytes) {
+        Some((start, metadata)) => (start, Some(metadata)),
+        None => (bytes.len(), None),
+    };
+    let instructions = parser::parse_bytecode(&bytes[..metadata_start]);
+
+    let (family, version) = match &metadata {
+        Some(metadata) => {
+            let (family, version) = metadata_compiler(metadata)?;
+            (family, Some(version))
+        }
+        None => (guess_family(&instructions)?, None),
+    };
+
+    let push0 = instructions.iter().any(|i| i.opcode == 0x5F);
+    let min_version = match family {
+        _ if version.is_some() => None,
+        Family::Solidity if push0 => Some("0.8.20"),
+        Family::Solidity if has_panic(&instructions) => Some("0.8.0"),
+        Family::Vyper if push0 => Some("0.3.8"),
+        _ => None,
+    };
+
+    let (pipeline, optimized) = match family {
+        Family::Solidity => (
+            solidity_pipeline(&instructions),
+            solidity_optimized(&bytes[..metadata_start]),
+        ),
+        _ => (None, None),
+    };
+
+    Some(Compiler {
+        family,
+        version,
+        min_version,
+        pipeline,
+        optimized,
+        guessed: metadata.is_none(),
+    })
+}
+
+// Offset the CBOR metadata starts at. Its length is stored in the last two bytes.
+pub fn metadata_start(bytes: &[u8]) -> Option<usize> {
+    parse_metadata(bytes).map(|(start, _)| start)
+}
+
+// Code layout vyper records in its metadata since 0.3.10, `[integrity hash (0.4+),
+// runtime size, [data section sizes], immutables size, {"vyper": version}]`. Immutables
+// are only appended to the deployed code, which carries no metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VyperLayout {
+    pub runtime_size: usize, // data sections included
+    pub data_sizes: Vec<usize>,
+}
+
+pub fn vyper_layout(bytes: &[u8]) -> Option<VyperLayout> {
+    let (_, Cbor::Array(items)) = parse_metadata(bytes)? else {
+        return None;
+    };
+    let items: Vec<&Cbor> = items
+        .iter()
+        .filter(|item| !matches!(item, Cbor::Bytes(_)))
+        .collect();
+
+    let [
+        Cbor::Uint(runtime_size),
+        Cbor::Array(data_sizes),
+        Cbor::Uint(_),
+        Cbor::Map(_),
+    ] = items.as_slice()
+    else {
+        return None;
+    };
+    let data_sizes = data_sizes
+        .iter()
+        .map(|size| match size {
+            Cbor::Uint(size) => usize::try_from(*size).ok(),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+
+    Some(VyperLayout {
+        runtime_size: usize::try_from(*runtime_size).ok()?,
+        data_sizes,
+    })
+}
+
+// Offset of the runtime code copied and returned by a deploy code that isn't solidity's,
+// `CODECOPY(dest, offset, size)` with a constant offset past the first RETURN.
+pub fn copied_runtime_offset(instructions: &[Instruction]) -> Option<usize> {
+    let end = instructions.iter().position(|i| i.opcode == 0xF3)?;
+    let return_offset = instructions[end].offset;
+    let code_size = instructions
+        .last()
+        .map_or(0, |i| i.offset + 1 + i.data.len());
+
+    let mut offset = None;
+    Cfg::from_instructions(&instructions[..=end]).walk_with::<Slot>(0, |instruction, inputs| {
+        if instruction.opcode == 0x39
+            && let [_, Slot::Const(source), _] = inputs
+            && *source > return_offset
+            && *source < code_size
+        {
+            offset = Some(offset.map_or(*source, |offset: usize| offset.min(*source)));
+        }
+    });
+
+    offset
+}
+
+fn guess_family(instructions: &[Instruction]) -> Option<Family> {
+    // solidity sets the free memory pointer first thing, in init and runtime code, legacy
+    // and via-ir alike
+    if let [
+        Instruction {
+            opcode: 0x60,
+            data: a,
+            ..
+        },
+        Instruction {
+            opcode: 0x60,
+            data: b,
+            ..
+        },
+        Instruction { opcode: 0x52, .. },
+        ..,
+    ] = instructions
+        && a[..] == [0x80]
+        && b[..] == [0x40]
+    {
+        return Some(Family::Solidity);
+    }
+
+    let runtime_start = copied_runtime_offset(instructions).unwrap_or(0);
+    let runtime = match instructions.iter().position(|i| i.offset == runtime_start) {
+        Some(index) => &instructions[index..],
+        None => instructions,
+    };
+    let prologue = &runtime[..runtime.len().min(8)];
+    let deploy = &instructions[..instructions.len() - runtime.len()];
+
+    // vyper checks the calldata size before loading the selector
+    if prologue.windows(2).any(|w| {
+        matches!(
+            (w[0].opcode, w[1].opcode),
+            (0x36, 0x10 | 0x11) // CALLDATASIZE LT/GT
+        )
+    }) {
+        return Some(Family::Vyper);
+    }
+
+    // deploy code doing nothing but `codecopy(0, offset, size) return(0, size)`, huff
+    // macros push the zeroes with RETURNDATASIZE, solc compiled yul objects with PUSH
+    match deploy {
+        [
+            Instruction {
+                opcode: 0x60..=0x7F,
+                ..
+            },
+            Instruction { opcode: 0x80, .. },
+            Instruction {
+                opcode: 0x60..=0x7F,
+                ..
+            },
+            zero,
+            Instruction { opcode: 0x39, .. },
+            other,
+            Instruction { opcode: 0xF3, .. },
+            ..,
+        ] if zero.opcode == other.opcode => match zero.opcode {
+            0x3D => Some(Family::Huff),
+            0x5F => Some(Family::Yul),
+            0x60 if zero.data == other.data && zero.data[..] == [0] => Some(Family::Yul),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn has_panic(instructions: &[Instruction]) -> bool {
+    instructions
+        .iter()
+        .any(|i| i.opcode == 0x7F && i.data.starts_with(&PANIC_SELECTOR))
+}
+
+fn parse_metadata(bytes: &[u8]) -> Option<(usize, Cbor)> {
+    let length =
+        u16::from_be_bytes([*bytes.get(bytes.len().checked_sub(2)?)?, *bytes.last()?]) as usize;
+    let start = bytes.len().checked_sub(length + 2)?;
+    let encoded = &bytes[start..bytes.len() - 2];
+
+    let mut position = 0;
+    let metadata = Cbor::decode(encoded, &mut position, 0)?;
+    if position != encoded.len() || !metadata.has_key() {
+        return None;
+    }
+
+    Some((start, metadata))
+}
+
+fn metadata_compiler(metadata: &Cbor) -> Option<(Family, String)> {
+    if let Some(version) = metadata.get("solc") {
+        let version = match version {
+            Cbor::Bytes(bytes) => bytes
+                .iter()
+                .map(|b| b.to_string())
+                .collect::<Vec<_>>()
+                .join("."),
+            Cbor::Text(text) => text.clone(), // prerelease and nightly builds
+            _ => return None,
+        };
+        return Some((Family::Solidity, version));
+    }
+
+    // `[major, minor, patch]`, nested in a list with section sizes since vyper 0.4
+    match metadata.get("vyper")? {
+        Cbor::Array(parts) => {
+            let parts: Option<Vec<String>> = parts
+                .iter()
+                .map(|part| match part {
+                    Cbor::Uint(n) => Some(n.to_string()),
+                    _ => None,
+                })
+                .collect();
+            Some((Family::Vyper, parts?.join(".")))
+        }
+        _ => None,
+    }
+}
+
+// The legacy code generator keeps the callvalue on the stack to test it, the IR one
+// jumps on it directly.
+fn solidity_pipeline(instructions: &[Instruction]) -> Option<Pipeline> {
+    let callvalue = instructions.iter().position(|i| i.opcode == 0x34)?;
+    match instructions.get(callvalue + 1)?.opcode {
+        0x80 => Some(Pipeline::Legacy),
+        0x15 | 0x60..=0x62 => Some(Pipeline::ViaIr),
+        _ => None,
+    }
+}
+
+// Without the optimizer reverts push both zeroes, `PUSH0 PUSH0 REVERT`, the optimizer
+// turns the second one into a DUP1. Older versions push 0 with PUSH1 either way.
+fn solidity_optimized(bytes: &[u8]) -> Option<bool> {
+    let count = |pattern: &[u8]| {
+        bytes
+            .windows(pattern.len())
+            .filter(|w| *w == pattern)
+            .count()
+    };
+    let plain = count(&[0x5F, 0x5F, 0xFD]);
+    let optimized = count(&[0x5F, 0x80, 0xFD]);
+
+    match (plain, optimized) {
+        (0, 0) => None,
+        (plain, optimized) => Some(optimized > plain),
+    }
+}
+
+// The subset of CBOR compilers emit in metadata.
+#[derive(Debug)]
+enum Cbor {
+    Uint(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Bool, // value unused, solc only sets `experimental: true`
+}
+
+impl Cbor {
+    fn decode(bytes: &[u8], position: &mut usize, depth: usize) -> Option<Cbor> {
+        if depth > 4 {
+            return None;
+        }
+
+        let head = *bytes.get(*position)?;
+        *position += 1;
+        let (major, info) = (head >> 5, head & 0x1F);
+
+        let argument = match info {
+            0..=23 => info as u64,
+            24..=27 => {
+                let size = 1 << (info - 24);
+                let value = bytes.get(*position..*position + size)?;
+                *position += size;
+                value.iter().fold(0, |acc, &b| (acc << 8) | b as u64)
+            }
+            _ => return None,
+        };
+
+        let mut take = |length: u64| -> Option<&[u8]> {
+            let end = position.checked_add(usize::try_from(length).ok()?)?;
+            let value = bytes.get(*position..end)?;
+            *position = end;
+            Some(value)
+        };
+
+        match major {
+            0 => Some(Cbor::Uint(argument)),
+            2 => Some(Cbor::Bytes(take(argument)?.to_vec())),
+            3 => Some(Cbor::Text(
+                String::from_utf8(take(argument)?.to_vec()).ok()?,
+            )),
+            4 => (0..argument)
+                .map(|_| Cbor::decode(bytes, position, depth + 1))
+                .collect::<Option<_>>()
+                .map(Cbor::Array),
+            5 => (0..argument)
+                .map(|_| {
+                    Some((
+                        Cbor::decode(bytes, position, depth + 1)?,
+                        Cbor::decode(bytes, position, depth + 1)?,
+                    ))
+                })
+                .collect::<Option<_>>()
+                .map(Cbor::Map),
+            7 if info == 20 || info == 21 => Some(Cbor::Bool),
+            _ => None,
+        }
+    }
+
+    // Value of a key in this map, or in a map nested in this array.
+    fn get(&self, key: &str) -> Option<&Cbor> {
+        match self {
+            Cbor::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(k, Cbor::Text(text) if text == key))
+                .map(|(_, value)| value),
+            Cbor::Array(items) => items.iter().find_map(|item| match item {
+                Cbor::Map(_) => item.get(key),
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+
+    fn has_key(&self) -> bool {
+        METADATA_KEYS.iter().any(|key| self.get(key).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solc_metadata_without_hash() {
+        // PUSH1 0x80 PUSH1 0x40 MSTORE PUSH0 PUSH0 REVERT, then `{"solc": 0.8.30}`
+        let code = hex::decode("60806040525f5ffdfea164736f6c634300081e000a").unwrap();
+
+        assert_eq!(metadata_start(&code), Some(9));
+        let compiler = detect_compiler(&code).unwrap();
+        assert_eq!(compiler.to_string(), "solidity 0.8.30, not optimized");
+    }
+
+    #[test]
+    fn vyper_metadata() {
+        // CALLDATASIZE guard, then `{"vyper": [0, 3, 10]}`
+        let code = hex::decode("6003361161000a57005b00a16576797065728300030a000b").unwrap();
+        let compiler = detect_compiler(&code).unwrap();
+        assert_eq!(compiler.family, Family::Vyper);
+        assert_eq!(compiler.version.as_deref(), Some("0.3.10"));
+        assert!(!compiler.guessed);
+
+        let stripped = &code[..code.len() - 13];
+        assert_eq!(metadata_start(stripped), None);
+        assert_eq!(
+            detect_compiler(stripped).unwrap().to_string(),
+            "vyper (guessed)"
+        );
+    }
+
+    #[test]
+    fn solidity_guessed_from_prologue() {
+        // PUSH1 0x80 PUSH1 0x40 MSTORE CALLVALUE DUP1 ISZERO PUSH1 0x0e JUMPI PUSH0 DUP1
+        // REVERT JUMPDEST STOP, no metadata
+        let code = hex::decode("6080604052348015600e575f80fd5b00").unwrap();
+        assert_eq!(
+            detect_compiler(&code).unwrap().to_string(),
+            "solidity >=0.8.20, legacy codegen, optimized (guessed)"
+        );
+
+        // hand written code matches no idiom
+        assert_eq!(detect_compiler(&hex::decode("33ff").unwrap()), None);
+    }
+
+    #[test]
+    fn huff_deploy_code() {
+        // PUSH1 0x06 DUP1 PUSH1 0x09 RETURNDATASIZE CODECOPY RETURNDATASIZE RETURN, runtime
+        let code = hex::decode("60068060093d393df35f3560e01c00").unwrap();
+        let instructions = parser::parse_bytecode(&code);
+
+        assert_eq!(copied_runtime_offset(&instructions), Some(9));
+        assert_eq!(detect_compiler(&code).unwrap().family, Family::Huff);
+
+        // the same deploy code with PUSH0 zeroes is solc's for a yul object
+        let code = hex::decode("60068060095f395ff35f3560e01c00").unwrap();
+        assert_eq!(detect_compiler(&code).unwrap().family, Family::Yul);
+
+        // other deploy code using RETURNDATASIZE names nothing
+        let code = hex::decode("3d60068060093d39f35f3560e01c00").unwrap();
+        assert_eq!(detect_compiler(&code), None);
+    }
+}
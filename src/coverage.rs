@@ -0,0 +1,293 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    fmt::Write,
+};
+
+use serde::Serialize;
+
+use crate::{
+    cfg::Cfg,
+    loader::{Program, SectionKind},
+    parser::Instruction,
+    trace::{Branch, Trace},
+};
+
+#[derive(Default, Clone, Copy, Serialize)]
+pub struct Counter {
+    pub hit: usize,
+    pub total: usize,
+}
+
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = if self.total == 0 {
+            0.0
+        } else {
+            self.hit as f64 * 100.0 / self.total as f64
+        };
+        write!(f, "{}/{} ({:.1}%)", self.hit, self.total, percent)
+    }
+}
+
+#[derive(Serialize)]
+pub struct BranchCoverage {
+    pub offset: usize, // JUMPI
+    pub jumped: usize,
+    pub fell_through: usize,
+}
+
+#[derive(Serialize)]
+pub struct FunctionCoverage {
+    pub selector: String,
+    pub signature: String,
+    pub offset: usize,
+    pub calls: usize,
+    pub instructions: Counter,
+    pub blocks: Counter,
+    pub branches: Vec<BranchCoverage>,
+}
+
+// Runtime code executed over a set of traces, per function selector. Blocks reachable
+// from several entries, like shared internal functions, count for each of them.
+#[derive(Serialize)]
+pub struct Coverage {
+    pub traces: usize,
+    pub instructions: Counter,
+    pub blocks: Counter,
+    pub functions: Vec<FunctionCoverage>,
+    pub shared: FunctionCoverage, // dispatcher and code outside any function
+    #[serde(skip)]
+    hits: BTreeMap<usize, usize>,
+}
+
+pub fn analyze_coverage(
+    program: &Program,
+    traces: &[Trace],
+    selectors: &HashMap<u32, String>,
+) -> Coverage {
+    let instructions: &[Instruction] = program
+        .sections
+        .iter()
+        .find(|section| matches!(section.kind, SectionKind::Runtime | SectionKind::Raw))
+        .and_then(|section| section.instructions.as_deref())
+        .unwrap_or_default();
+    let cfg = Cfg::from_instructions(instructions);
+
+    let mut hits: BTreeMap<usize, usize> = instructions.iter().map(|i| (i.offset, 0)).collect();
+    let mut branches: HashMap<usize, Branch> = HashMap::new();
+    for trace in traces {
+        for (pc, count) in trace.hits() {
+            // steps outside the code belong to another contract
+            if let Some(hit) = hits.get_mut(&pc) {
+                *hit += count;
+            }
+        }
+        for (pc, branch) in trace.branches(instructions) {
+            let total = branches.entry(pc).or_default();
+            total.jumped += branch.jumped;
+            total.fell_through += branch.fell_through;
+        }
+    }
+
+    let cover = |selector: String, signature: String, offset: usize, blocks: &BTreeSet<usize>| {
+        let mut coverage = FunctionCoverage {
+            selector,
+            signature,
+            offset,
+            calls: hits.get(&offset).copied().unwrap_or(0),
+            instructions: Counter::default(),
+            blocks: Counter::default(),
+            branches: Vec::new(),
+        };
+
+        for block in blocks.iter().filter_map(|start| cfg.blocks.get(start)) {
+            coverage.blocks.total += 1;
+            if hits[&block.start] > 0 {
+                coverage.blocks.hit += 1;
+            }
+
+            for instruction in &block.instructions {
+                coverage.instructions.total += 1;
+                if hits[&instruction.offset] > 0 {
+                    coverage.instructions.hit += 1;
+                }
+            }
+
+            if block.last().opcode == 0x57 {
+                let branch = branches.get(&block.end).copied().unwrap_or_default();
+                coverage.branches.push(BranchCoverage {
+                    offset: block.end,
+                    jumped: branch.jumped,
+                    fell_through: branch.fell_through,
+                });
+            }
+        }
+
+        coverage
+    };
+
+    let mut covered = BTreeSet::new();
+    let functions = program
+        .entrypoints
+        .iter()
+        .map(|entry| {
+            let blocks = cfg.walk(entry.offset).blocks;
+            covered.extend(blocks.iter().copied());
+            cover(
+                format!("0x{}", hex::encode(entry.selector)),
+                entry.signature(selectors).0,
+                entry.offset,
+                &blocks,
+            )
+        })
+        .collect();
+
+    let rest: BTreeSet<usize> = cfg
+        .blocks
+        .keys()
+        .filter(|start| !covered.contains(start))
+        .copied()
+        .collect();
+    let shared = cover(String::new(), String::new(), 0, &rest);
+
+    let all: BTreeSet<usize> = cfg.blocks.keys().copied().collect();
+    let total = cover(String::new(), String::new(), 0, &all);
+
+    Coverage {
+        traces: traces.len(),
+        instructions: total.instructions,
+        blocks: total.blocks,
+        functions,
+        shared,
+        hits,
+    }
+}
+
+impl Coverage {
+    // lcov tracefile for `name`, with instruction offsets in place of line numbers and
+    // both ways of every JUMPI as branches.
+    pub fn lcov(&self, name: &str) -> String {
+        let mut out = String::new();
+        writeln!(out, "TN:").unwrap();
+        writeln!(out, "SF:{}", name).unwrap();
+
+        for function in &self.functions {
+            writeln!(out, "FN:{},{}", function.offset, lcov_name(function)).unwrap();
+        }
+        for function in &self.functions {
+            writeln!(out, "FNDA:{},{}", function.calls, lcov_name(function)).unwrap();
+        }
+        writeln!(out, "FNF:{}", self.functions.len()).unwrap();
+        let called = self.functions.iter().filter(|f| f.calls > 0).count();
+        writeln!(out, "FNH:{}", called).unwrap();
+
+        let branches: BTreeMap<usize, &BranchCoverage> = self
+            .functions
+            .iter()
+            .chain([&self.shared])
+            .flat_map(|function| &function.branches)
+            .map(|branch| (branch.offset, branch))
+            .collect();
+        let mut branches_hit = 0;
+        for branch in branches.values() {
+            for (n, count) in [branch.jumped, branch.fell_through].into_iter().enumerate() {
+                // `-` marks a branch whose condition was never evaluated
+                if branch.jumped + branch.fell_through == 0 {
+                    writeln!(out, "BRDA:{},0,{},-", branch.offset, n).unwrap();
+                } else {
+                    writeln!(out, "BRDA:{},0,{},{}", branch.offset, n, count).unwrap();
+                }
+                if count > 0 {
+                    branches_hit += 1;
+                }
+            }
+        }
+        writeln!(out, "BRF:{}", branches.len() * 2).unwrap();
+        writeln!(out, "BRH:{}", branches_hit).unwrap();
+
+        for (offset, count) in &self.hits {
+            writeln!(out, "DA:{},{}", offset, count).unwrap();
+        }
+        writeln!(out, "LF:{}", self.instructions.total).unwrap();
+        writeln!(out, "LH:{}", self.instructions.hit).unwrap();
+        writeln!(out, "end_of_record").unwrap();
+
+        out
+    }
+}
+
+// Function name without parameters, lcov names can't hold commas.
+fn lcov_name(function: &FunctionCoverage) -> &str {
+    function
+        .signature
+        .split('(')
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(&function.selector)
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} traces, instructions {}, blocks {}",
+            self.traces, self.instructions, self.blocks
+        )?;
+
+        let functions = self.functions.iter().map(|function| {
+            let name = format!("{} {}", function.selector, function.signature);
+            (name, function)
+        });
+        let shared = ("dispatcher and shared code".to_string(), &self.shared);
+
+        for (name, function) in functions.chain([shared]) {
+            writeln!(
+                f,
+                "{}  calls {}, instructions {}, blocks {}",
+                name.trim_end(),
+                function.calls,
+                function.instructions,
+                function.blocks
+            )?;
+
+            // branches only partly followed, unreached code shows in the counts already
+            for branch in &function.branches {
+                let missing = match (branch.jumped, branch.fell_through) {
+                    (0, 0) => continue,
+                    (0, _) => "never jumps",
+                    (_, 0) => "never falls through",
+                    _ => continue,
+                };
+                writeln!(f, "    {:04x}: JUMPI {}", branch.offset, missing)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branch_never_taken() {
+        // PUSH1 0 PUSH1 6 JUMPI, STOP, JUMPDEST STOP
+        let program = Program::load(&hex::decode("6000600657005b00").unwrap(), true, false);
+        let trace = Trace::from_json(
+            r#"[{"pc":0,"depth":1,"stack":[]},{"pc":2,"depth":1,"stack":["0x0"]},
+            {"pc":4,"depth":1,"stack":["0x0","0x6"]},{"pc":5,"depth":1,"stack":[]}]"#,
+        )
+        .unwrap();
+
+        let coverage = analyze_coverage(&program, &[trace], &HashMap::new());
+        assert_eq!(coverage.instructions.hit, 4);
+        assert_eq!(coverage.blocks.hit, 2);
+        assert_eq!(coverage.blocks.total, 3);
+
+        let lcov = coverage.lcov("raw");
+        assert!(lcov.contains("BRDA:4,0,0,0\nBRDA:4,0,1,1\nBRF:2\nBRH:1\n"));
+        assert!(lcov.contains("DA:7,0\nLF:6\nLH:4\n"));
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    cfg::Cfg,
+    loader::{Program, SectionKind},
+    symbolic::runtime_code,
+    vyper,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadKind {
+    AfterTerminator, // code right after a halt or jump
+    NeverJumpedTo,   // starts with a JUMPDEST no resolved jump targets
+}
+
+#[derive(Serialize)]
+pub struct DeadRange {
+    pub start: usize,
+    pub end: usize, // exclusive
+    pub kind: DeadKind,
+}
+
+// Runtime code no resolved jump reaches from the start of the code or a function entry.
+// Jumps that couldn't be resolved may still reach it, they are listed so the rest can be
+// judged.
+#[derive(Serialize)]
+pub struct DeadCode {
+    pub unreachable: Vec<DeadRange>,
+    pub untargeted: Vec<usize>, // JUMPDESTs of reachable code only ever fallen into
+    pub unresolved: Vec<usize>, // JUMP/JUMPI offsets with unknown targets
+}
+
+pub fn analyze_dead_code(program: &Program) -> DeadCode {
+    let (instructions, _) = runtime_code(program);
+    let cfg = Cfg::from_instructions(instructions);
+
+    // dispatchers jumping through a table reach functions no walk from 0 resolves, vyper's
+    // sparse tables jump to a bucket checking the selectors first
+    let mut entries: Vec<usize> = program.entrypoints.iter().map(|e| e.offset).collect();
+    if let Some(data) = program
+        .sections
+        .iter()
+        .find(|section| section.kind == SectionKind::Data)
+    {
+        let jumpdests: Vec<usize> = cfg
+            .blocks
+            .values()
+            .filter(|block| block.is_jumpdest())
+            .map(|block| block.start)
+            .collect();
+        entries.extend(vyper::sparse_buckets(&data.raw_bytes, &jumpdests));
+    }
+    let mut walk = cfg.walk(0);
+    for &entry in &entries {
+        let function = cfg.walk(entry);
+        walk.blocks.extend(function.blocks);
+        walk.edges.extend(function.edges);
+        walk.unresolved.extend(function.unresolved);
+    }
+    // a jump resolved from one entry but not another still goes somewhere
+    walk.unresolved.retain(|&offset| {
+        cfg.block_at(offset).is_none_or(|block| {
+            !walk
+                .edges
+                .iter()
+                .any(|&(from, to)| from == block.start && block.fallthrough() != Some(to))
+        })
+    });
+
+    let mut unreachable: Vec<DeadRange> = Vec::new();
+    for block in cfg.blocks.values() {
+        if walk.blocks.contains(&block.start) {
+            continue;
+        }
+
+        let last = block.last();
+        let end = last.offset + 1 + last.data.len();
+        match unreachable.last_mut() {
+            Some(range) if range.end == block.start => range.end = end,
+            _ => unreachable.push(DeadRange {
+                start: block.start,
+                end,
+                kind: if block.is_jumpdest() {
+                    DeadKind::NeverJumpedTo
+                } else {
+                    DeadKind::AfterTerminator
+                },
+            }),
+        }
+    }
+
+    // jumps land on a block start, falling through only follows a block not ending in one
+    let untargeted = cfg
+        .blocks
+        .values()
+        .filter(|block| block.is_jumpdest() && walk.blocks.contains(&block.start))
+        .filter(|block| !entries.contains(&block.start))
+        .filter(|block| {
+            !walk.edges.iter().any(|&(from, to)| {
+                to == block.start && cfg.blocks[&from].fallthrough() != Some(block.start)
+            })
+        })
+        .map(|block| block.start)
+        .collect();
+
+    DeadCode {
+        unreachable,
+        untargeted,
+        unresolved: walk.unresolved.into_iter().collect(),
+    }
+}
+
+impl fmt::Display for DeadCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes: usize = self.unreachable.iter().map(|r| r.end - r.start).sum();
+        writeln!(
+            f,
+            "{} unreachable ranges, {} bytes",
+            self.unreachable.len(),
+            bytes
+        )?;
+        for range in &self.unreachable {
+            let kind = match range.kind {
+                DeadKind::AfterTerminator => "after terminator",
+                DeadKind::NeverJumpedTo => "never jumped to",
+            };
+            writeln!(
+                f,
+                "  {:04x}..{:04x}  {} bytes, {}",
+                range.start,
+                range.end,
+                range.end - range.start,
+                kind
+            )?;
+        }
+
+        if !self.untargeted.is_empty() {
+            let offsets: Vec<String> = self
+                .untargeted
+                .iter()
+                .map(|offset| format!("{:04x}", offset))
+                .collect();
+            writeln!(f, "JUMPDESTs never jumped to: {}", offsets.join(", "))?;
+        }
+        if !self.unresolved.is_empty() {
+            let offsets: Vec<String> = self
+                .unresolved
+                .iter()
+                .map(|offset| format!("{:04x}", offset))
+                .collect();
+            writeln!(
+                f,
+                "unresolved jumps at {}, unreachable code may be reached through them",
+                offsets.join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_hidden_behind_computed_jump() {
+        // 00: PUSH1 0x05 JUMP
+        // 03: INVALID INVALID
+        // 05: JUMPDEST PUSH1 0 CALLDATALOAD JUMP
+        // 0a: JUMPDEST CALLER SELFDESTRUCT
+        let bytecode = hex::decode("600556fefe5b600035565b33ff").unwrap();
+        let program = Program::load(&bytecode, true, false);
+
+        let dead = analyze_dead_code(&program);
+        let ranges: Vec<(usize, usize, DeadKind)> = dead
+            .unreachable
+            .iter()
+            .map(|range| (range.start, range.end, range.kind))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (0x03, 0x05, DeadKind::AfterTerminator),
+                (0x0a, 0x0d, DeadKind::NeverJumpedTo)
+            ]
+        );
+        assert_eq!(dead.unresolved, vec![0x09]);
+    }
+}
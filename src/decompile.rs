@@ -0,0 +1,573 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    analysis::{FunctionEntrypoint, Mutability},
+    input::StorageVariable,
+    loader::Program,
+    symbolic::{Effect, Executor, Expr, Halt, Names, State, Step, Terminator, runtime_code},
+    u256::U256,
+};
+
+// Runs of the symbolic executor per function, forks included.
+const MAX_RUNS: usize = 4096;
+// Forks tried to get through the dispatcher to a function entry.
+const MAX_DISPATCH_FORKS: usize = 1024;
+
+#[derive(Clone, PartialEq)]
+pub enum Stmt {
+    Effect(Effect),
+    Require(Expr, Option<String>), // condition and revert reason
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Option<Expr>, Vec<Stmt>), // none when the exit condition changes every iteration
+    Continue,
+    Halt(Halt),
+    Truncated, // exploration bounds reached
+}
+
+pub struct Function {
+    pub selector: [u8; 4],
+    pub signature: String,
+    pub mutability: Mutability,
+    pub body: Vec<Stmt>,
+}
+
+// Solidity-like pseudocode of each external function, lifted from symbolic execution of
+// the function with its selector in the calldata. Not meant to compile.
+pub struct Decompiled {
+    pub functions: Vec<Function>,
+    variables: Vec<StorageVariable>,
+    selectors: HashMap<u32, String>,
+}
+
+pub fn decompile_program(
+    program: &Program,
+    selectors: &HashMap<u32, String>,
+    storage: &[StorageVariable],
+) -> Decompiled {
+    let (instructions, code) = runtime_code(program);
+
+    let functions = program
+        .entrypoints
+        .iter()
+        .map(|entry| {
+            let entries = [entry.offset];
+            let executor = Executor::new(instructions, &code, &entries);
+            let body = match reach_entry(&executor, entry) {
+                Some(state) => {
+                    let mut lifter = Lifter {
+                        executor: &executor,
+                        runs: 0,
+                        loops: Vec::new(),
+                    };
+                    let mut body = lifter.lift(state);
+                    // falling off the end of a function is implied
+                    if let Some(Stmt::Halt(halt)) = body.last()
+                        && halt.terminator == Terminator::Stop
+                    {
+                        body.pop();
+                    }
+                    body
+                }
+                None => vec![Stmt::Truncated],
+            };
+
+            Function {
+                selector: entry.selector,
+                signature: entry.signature(selectors).0,
+                mutability: entry.mutability,
+                body,
+            }
+        })
+        .collect();
+
+    Decompiled {
+        functions,
+        variables: storage.to_vec(),
+        selectors: selectors.clone(),
+    }
+}
+
+// State at the function entry, going through the dispatcher with the selector set.
+fn reach_entry(executor: &Executor, entry: &FunctionEntrypoint) -> Option<State> {
+    let mut queue = vec![executor.start(Some(entry.selector))];
+    let mut forks = 0;
+
+    while let Some(mut state) = queue.pop() {
+        loop {
+            match executor.run(&mut state) {
+                Some(Step::Entry(_)) => return Some(state),
+                Some(Step::Effect(_)) => continue,
+                Some(Step::Fork { target, .. }) if forks < MAX_DISPATCH_FORKS => {
+                    forks += 1;
+                    let mut jumped = state.clone();
+                    executor.jump(&mut jumped, target);
+                    queue.push(state);
+                    queue.push(jumped);
+                    break;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    None
+}
+
+struct Lifter<'a> {
+    executor: &'a Executor<'a>,
+    runs: usize,
+    loops: Vec<Header>,
+}
+
+// JUMPI being lifted, with the return addresses it was reached with.
+struct Header {
+    offset: usize,
+    returns: Vec<usize>,
+    condition: Expr,
+    varies: bool, // condition differs when coming back, it uses a loop-carried value
+}
+
+impl Lifter<'_> {
+    // Statements from a state up to the end of each of its paths. Both sides of a fork are
+    // lifted separately, then merged back into `require`, `if` or `while`.
+    fn lift(&mut self, mut state: State) -> Vec<Stmt> {
+        let mut body = Vec::new();
+
+        loop {
+            self.runs += 1;
+            if self.runs > MAX_RUNS {
+                body.push(Stmt::Truncated);
+                return body;
+            }
+
+            match self.executor.run(&mut state) {
+                Some(Step::Effect(effect)) => body.push(Stmt::Effect(effect)),
+                Some(Step::Entry(_)) => {}
+                Some(Step::Halt(halt)) => {
+                    body.push(Stmt::Halt(halt));
+                    return body;
+                }
+                Some(Step::Fork {
+                    offset,
+                    target,
+                    condition,
+                }) => {
+                    let returns = self.executor.return_addresses(&state);
+                    if let Some(header) = self
+                        .loops
+                        .iter_mut()
+                        .find(|header| header.offset == offset && header.returns == returns)
+                    {
+                        header.varies |= header.condition != condition;
+                        body.push(Stmt::Continue);
+                        return body;
+                    }
+
+                    self.loops.push(Header {
+                        offset,
+                        returns,
+                        condition: condition.clone(),
+                        varies: false,
+                    });
+                    let mut jumped = state.clone();
+                    self.executor.jump(&mut jumped, target);
+                    let taken = self.lift(jumped);
+                    let not_taken = self.lift(state);
+                    let header = self.loops.pop().expect("pushed above");
+
+                    body.extend(structure(condition, header.varies, taken, not_taken));
+                    return body;
+                }
+                None => {
+                    body.push(Stmt::Truncated);
+                    return body;
+                }
+            }
+        }
+    }
+}
+
+// Statements of a fork on `condition`, `taken` when it holds. `varies` when the condition
+// was different coming back to the JUMPI, the first one would be wrong for a loop.
+fn structure(
+    condition: Expr,
+    varies: bool,
+    mut taken: Vec<Stmt>,
+    mut not_taken: Vec<Stmt>,
+) -> Vec<Stmt> {
+    // a side coming back to the JUMPI is the body of a loop
+    if ends_with_continue(&taken) {
+        taken.pop();
+        let mut body = vec![Stmt::While((!varies).then_some(condition), taken)];
+        body.extend(not_taken);
+        return body;
+    }
+    if ends_with_continue(&not_taken) {
+        not_taken.pop();
+        let mut body = vec![Stmt::While(
+            (!varies).then(|| condition.negate()),
+            not_taken,
+        )];
+        body.extend(taken);
+        return body;
+    }
+
+    // a side that only reverts is a require
+    if let Some(reason) = only_reverts(&not_taken) {
+        let mut body = vec![Stmt::Require(condition, reason)];
+        body.extend(taken);
+        return body;
+    }
+    if let Some(reason) = only_reverts(&taken) {
+        let mut body = vec![Stmt::Require(condition.negate(), reason)];
+        body.extend(not_taken);
+        return body;
+    }
+
+    // code both sides end with is hoisted after the if
+    let mut tail = Vec::new();
+    while !taken.is_empty() && taken.last() == not_taken.last() {
+        not_taken.pop();
+        tail.push(taken.pop().expect("not empty"));
+    }
+    tail.reverse();
+
+    let mut body = if taken.is_empty() {
+        vec![Stmt::If(condition.negate(), not_taken, Vec::new())]
+    } else {
+        vec![Stmt::If(condition, taken, not_taken)]
+    };
+    body.extend(tail);
+    body
+}
+
+fn ends_with_continue(body: &[Stmt]) -> bool {
+    matches!(body.last(), Some(Stmt::Continue))
+}
+
+// Revert reason of a side doing nothing but revert.
+fn only_reverts(body: &[Stmt]) -> Option<Option<String>> {
+    match body {
+        [Stmt::Halt(halt)] if halt.terminator == Terminator::Revert => Some(halt.reason.clone()),
+        _ => None,
+    }
+}
+
+// Names from the function signature, the storage layout and Solidity globals.
+struct Context<'a> {
+    variables: &'a [StorageVariable],
+    arguments: usize,
+}
+
+impl Context<'_> {
+    fn variable(&self, slot: &Expr) -> Option<String> {
+        match slot {
+            Expr::Const(slot) => {
+                let slot = slot.to_usize()?;
+                self.variables
+                    .iter()
+                    .find(|variable| variable.slot == slot && variable.offset == 0)
+                    .map(|variable| variable.label.clone())
+            }
+            // mapping values are at keccak(key, slot)
+            Expr::Keccak(words) => match words.as_slice() {
+                [key, base] => Some(format!("{}[{}]", self.variable(base)?, key.render(self))),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Names for Context<'_> {
+    fn name(&self, expr: &Expr) -> Option<String> {
+        let address_mask = U256::ONE.shift_left(160).wrapping_sub(U256::ONE);
+
+        match expr {
+            Expr::CallData(offset) => {
+                let Expr::Const(offset) = offset.as_ref() else {
+                    return None;
+                };
+                let offset = offset.to_usize()?.checked_sub(4)?;
+                let index = offset / 32;
+                (offset % 32 == 0 && index < self.arguments).then(|| format!("arg{}", index))
+            }
+            Expr::Storage(slot) => self.variable(slot),
+            Expr::Env(opcode) => Some(
+                match opcode {
+                    0x30 => "address(this)",
+                    0x32 => "tx.origin",
+                    0x33 => "msg.sender",
+                    0x34 => "msg.value",
+                    0x36 => "msg.data.length",
+                    0x3A => "tx.gasprice",
+                    0x41 => "block.coinbase",
+                    0x42 => "block.timestamp",
+                    0x43 => "block.number",
+                    0x44 => "block.prevrandao",
+                    0x45 => "block.gaslimit",
+                    0x46 => "block.chainid",
+                    0x47 => "address(this).balance",
+                    0x48 => "block.basefee",
+                    _ => return None,
+                }
+                .to_string(),
+            ),
+            Expr::Op(0x16, inputs) => match inputs.as_slice() {
+                [value, Expr::Const(mask)] | [Expr::Const(mask), value]
+                    if *mask == address_mask =>
+                {
+                    Some(format!("address({})", value.render(self)))
+                }
+                _ => None,
+            },
+            Expr::Unknown(0xF1 | 0xF2 | 0xF4 | 0xFA, offset) => {
+                Some(format!("call_{:04x}", offset))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Decompiled {
+    fn write_body(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        body: &[Stmt],
+        context: &Context,
+        depth: usize,
+    ) -> fmt::Result {
+        let indent = "    ".repeat(depth);
+        let render = |expr: &Expr| expr.render(context);
+        let list = |exprs: &[Expr]| exprs.iter().map(render).collect::<Vec<_>>().join(", ");
+
+        for stmt in body {
+            match stmt {
+                Stmt::Effect(Effect::Store { slot, value }) => {
+                    let variable = Expr::Storage(Box::new(slot.clone()));
+                    writeln!(f, "{}{} = {};", indent, render(&variable), render(value))?;
+                }
+                Stmt::Effect(Effect::Log { topics, data }) => match topics.split_first() {
+                    Some((Expr::Const(topic), indexed)) => {
+                        let mut arguments = indexed.to_vec();
+                        arguments.extend(data.iter().cloned());
+                        let name = hex::encode(&topic.to_be_bytes()[..4]);
+                        writeln!(f, "{}emit event_{}({});", indent, name, list(&arguments))?;
+                    }
+                    _ => {
+                        let mut arguments = topics.clone();
+                        arguments.extend(data.iter().cloned());
+                        writeln!(f, "{}log({});", indent, list(&arguments))?;
+                    }
+                },
+                Stmt::Effect(Effect::Call {
+                    opcode,
+                    offset,
+                    target,
+                    value,
+                    data,
+                }) => {
+                    let kind = match opcode {
+                        0xF1 => "call",
+                        0xF2 => "callcode",
+                        0xF4 => "delegatecall",
+                        _ => "staticcall",
+                    };
+                    let value = match value {
+                        Some(value) if *value != Expr::Const(U256::ZERO) => {
+                            format!("{{value: {}}}", render(value))
+                        }
+                        _ => String::new(),
+                    };
+                    writeln!(
+                        f,
+                        "{}call_{:04x} = {}.{}{}({});",
+                        indent,
+                        offset,
+                        render(target),
+                        kind,
+                        value,
+                        self.call_data(data, context)
+                    )?;
+                }
+                Stmt::Require(condition, reason) => match reason {
+                    Some(reason) => {
+                        writeln!(f, "{}require({}, {});", indent, render(condition), reason)?
+                    }
+                    None => writeln!(f, "{}require({});", indent, render(condition))?,
+                },
+                Stmt::If(condition, taken, not_taken) => {
+                    writeln!(f, "{}if ({}) {{", indent, render(condition))?;
+                    self.write_body(f, taken, context, depth + 1)?;
+                    if !not_taken.is_empty() {
+                        writeln!(f, "{}}} else {{", indent)?;
+                        self.write_body(f, not_taken, context, depth + 1)?;
+                    }
+                    writeln!(f, "{}}}", indent)?;
+                }
+                Stmt::While(Some(condition), body) => {
+                    writeln!(f, "{}while ({}) {{", indent, render(condition))?;
+                    self.write_body(f, body, context, depth + 1)?;
+                    writeln!(f, "{}}}", indent)?;
+                }
+                Stmt::While(None, body) => {
+                    writeln!(
+                        f,
+                        "{}while (...) {{ // exit condition changes every iteration",
+                        indent
+                    )?;
+                    self.write_body(f, body, context, depth + 1)?;
+                    writeln!(f, "{}}}", indent)?;
+                }
+                Stmt::Continue => writeln!(f, "{}continue;", indent)?,
+                Stmt::Halt(halt) => {
+                    let arguments = match (&halt.reason, halt.values.is_empty()) {
+                        (Some(reason), _) => reason.clone(),
+                        (None, false) => list(&halt.values),
+                        (None, true) => String::new(),
+                    };
+                    match halt.terminator {
+                        Terminator::Stop => writeln!(f, "{}return;", indent)?,
+                        Terminator::Return if arguments.is_empty() => {
+                            writeln!(f, "{}return;", indent)?
+                        }
+                        Terminator::Return => writeln!(f, "{}return {};", indent, arguments)?,
+                        Terminator::Revert => writeln!(f, "{}revert({});", indent, arguments)?,
+                        Terminator::SelfDestruct => {
+                            writeln!(f, "{}selfdestruct({});", indent, arguments)?
+                        }
+                        Terminator::Invalid => writeln!(f, "{}invalid();", indent)?,
+                    }
+                }
+                Stmt::Truncated => writeln!(f, "{}// ...", indent)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    // Calldata of an external call, a known selector is shown as the function called.
+    fn call_data(&self, data: &[Expr], context: &Context) -> String {
+        let render = |exprs: &[Expr]| {
+            let exprs: Vec<String> = exprs.iter().map(|e| e.render(context)).collect();
+            exprs.join(", ")
+        };
+
+        if let Some((Expr::Const(selector), arguments)) = data.split_first()
+            && let Some(selector) = selector.to_usize().and_then(|s| u32::try_from(s).ok())
+            && let Some(signature) = self.selectors.get(&selector)
+        {
+            let name = signature.split('(').next().unwrap_or_default();
+            return format!("{}({})", name, render(arguments));
+        }
+
+        render(data)
+    }
+}
+
+// Parameter types of a signature, tuples are kept whole.
+fn parameter_types(signature: &str) -> Vec<&str> {
+    let Some(start) = signature.find('(') else {
+        return Vec::new();
+    };
+    let inner = &signature[start + 1..signature.len().saturating_sub(1)];
+    if inner.is_empty() {
+        return Vec::new();
+    }
+
+    let mut types = Vec::new();
+    let (mut depth, mut from) = (0, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                types.push(&inner[from..i]);
+                from = i + 1;
+            }
+            _ => {}
+        }
+    }
+    types.push(&inner[from..]);
+    types
+}
+
+impl fmt::Display for Decompiled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, function) in self.functions.iter().enumerate() {
+            if n > 0 {
+                writeln!(f)?;
+            }
+
+            let types = parameter_types(&function.signature);
+            let name = function.signature.split('(').next().unwrap_or_default();
+            let parameters: Vec<String> = types
+                .iter()
+                .enumerate()
+                .map(|(i, t)| format!("{} arg{}", t, i))
+                .collect();
+            let mutability = match function.mutability {
+                Mutability::NonPayable => String::new(),
+                mutability => format!(" {}", mutability),
+            };
+
+            writeln!(
+                f,
+                "function {}({}) external{} {{ // 0x{}",
+                name,
+                parameters.join(", "),
+                mutability,
+                hex::encode(function.selector)
+            )?;
+            let context = Context {
+                variables: &self.variables,
+                arguments: types.len(),
+            };
+            self.write_body(f, &function.body, &context, 1)?;
+            writeln!(f, "}}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tuple_parameters() {
+        assert_eq!(
+            parameter_types("swap((address,uint256)[],bytes)"),
+            vec!["(address,uint256)[]", "bytes"]
+        );
+        assert!(parameter_types("number()").is_empty());
+    }
+
+    #[test]
+    fn loop_until_calldata_bound() {
+        // for (i = 0; i < calldataload(0); i++) sstore(i, i)
+        let code = hex::decode("60005b600035811015601557808055600101600256").unwrap();
+        let program = Program::load(&[code, hex::decode("5b00").unwrap()].concat(), true, false);
+        let (instructions, code) = runtime_code(&program);
+        let executor = Executor::new(instructions, &code, &[]);
+        let mut lifter = Lifter {
+            executor: &executor,
+            runs: 0,
+            loops: Vec::new(),
+        };
+
+        let body = lifter.lift(executor.start(None));
+        assert_eq!(body.len(), 2);
+        let Stmt::While(condition, inner) = &body[0] else {
+            panic!("no loop");
+        };
+        // i is folded to 0 on the first iteration, that condition isn't the loop's
+        assert!(condition.is_none());
+        assert!(matches!(
+            inner.as_slice(),
+            [Stmt::Effect(Effect::Store { .. })]
+        ));
+        assert!(matches!(&body[1], Stmt::Halt(halt) if halt.terminator == Terminator::Stop));
+    }
+}
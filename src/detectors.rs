@@ -0,0 +1,416 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use serde::Serialize;
+
+use crate::{
+    analysis::FunctionEntrypoint,
+    cfg::{Cfg, Slot},
+    ir::{Definition, Ir, Operand, Statement, origins},
+    loader::Program,
+    symbolic::runtime_code,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    High,
+    Medium,
+    Low,
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::High => "high",
+            Severity::Medium => "medium",
+            Severity::Low => "low",
+            Severity::Info => "info",
+        };
+        f.pad(name)
+    }
+}
+
+// Code reachable from one external function, or from the dispatcher for code outside any.
+pub struct Scope<'a> {
+    pub selector: Option<[u8; 4]>,
+    pub entry: usize, // function entry, or the start of the code
+    pub blocks: &'a BTreeSet<usize>,
+    pub cfg: &'a Cfg,
+    pub ir: &'a Ir,
+    pub definitions: &'a HashMap<usize, Definition<'a>>,
+}
+
+impl Scope<'_> {
+    pub fn statements(&self) -> impl Iterator<Item = &Statement> {
+        self.blocks
+            .iter()
+            .filter_map(|start| self.ir.blocks.get(start))
+            .flat_map(|block| &block.statements)
+    }
+}
+
+pub struct Hit {
+    pub offset: usize,
+    pub description: String,
+}
+
+// A check run over the code of each external function. Detectors see the CFG and the
+// SSA form, new ones only need to be added to `default_detectors`.
+pub trait Detector {
+    fn id(&self) -> &'static str;
+
+    fn severity(&self) -> Severity;
+
+    fn detect(&self, scope: &Scope) -> Vec<Hit>;
+}
+
+pub fn default_detectors() -> Vec<Box<dyn Detector>> {
+    vec![
+        Box::new(SelfDestruct),
+        Box::new(DelegateCallToCalldata),
+        Box::new(TxOrigin),
+        Box::new(UncheckedCall),
+        Box::new(CallCode),
+        Box::new(StoreAfterCall),
+    ]
+}
+
+#[derive(Serialize)]
+pub struct Finding {
+    pub detector: &'static str,
+    pub severity: Severity,
+    pub offset: usize,
+    pub selector: Option<String>, // none for code outside any function
+    pub signature: Option<String>,
+    pub description: String,
+}
+
+#[derive(Serialize)]
+pub struct Findings {
+    pub findings: Vec<Finding>,
+}
+
+pub fn run_detectors(
+    program: &Program,
+    selectors: &HashMap<u32, String>,
+    detectors: &[Box<dyn Detector>],
+) -> Findings {
+    let (instructions, _) = runtime_code(program);
+    let cfg = Cfg::from_instructions(instructions);
+    let ir = Ir::from_instructions(instructions);
+    let definitions = ir.definitions();
+
+    let mut scopes: Vec<(Option<&FunctionEntrypoint>, BTreeSet<usize>)> = program
+        .entrypoints
+        .iter()
+        .map(|entry| (Some(entry), cfg.walk(entry.offset).blocks))
+        .collect();
+
+    // dispatcher, fallback and receive
+    let functions: BTreeSet<usize> = scopes
+        .iter()
+        .flat_map(|(_, blocks)| blocks.iter().copied())
+        .collect();
+    let rest: BTreeSet<usize> = cfg
+        .walk(0)
+        .blocks
+        .into_iter()
+        .filter(|start| !functions.contains(start))
+        .collect();
+    scopes.push((None, rest));
+
+    let mut findings = Vec::new();
+    for (entry, blocks) in &scopes {
+        let scope = Scope {
+            selector: entry.map(|entry| entry.selector),
+            entry: entry.map_or(0, |entry| entry.offset),
+            blocks,
+            cfg: &cfg,
+            ir: &ir,
+            definitions: &definitions,
+        };
+        for detector in detectors {
+            for hit in detector.detect(&scope) {
+                findings.push(Finding {
+                    detector: detector.id(),
+                    severity: detector.severity(),
+                    offset: hit.offset,
+                    selector: entry.map(|entry| format!("0x{}", hex::encode(entry.selector))),
+                    signature: entry.map(|entry| entry.signature(selectors).0),
+                    description: hit.description,
+                });
+            }
+        }
+    }
+
+    findings.sort_by_key(|finding| (finding.severity, finding.offset));
+    Findings { findings }
+}
+
+// Values used as an operand somewhere, a value never used was popped right away.
+fn used_values(ir: &Ir) -> HashSet<usize> {
+    let phis = ir
+        .blocks
+        .values()
+        .flat_map(|block| &block.phis)
+        .flat_map(|phi| phi.incoming.iter().map(|(_, operand)| *operand));
+    let operands = ir
+        .statements()
+        .flat_map(|statement| statement.operands.iter().copied());
+
+    phis.chain(operands)
+        .filter_map(|operand| match operand {
+            Operand::Value(value) => Some(value),
+            Operand::Const(_) => None,
+        })
+        .collect()
+}
+
+fn has_origin(operand: Operand, scope: &Scope, opcodes: &[u8]) -> bool {
+    origins(operand, scope.definitions)
+        .iter()
+        .any(|origin| opcodes.contains(&origin.opcode))
+}
+
+pub struct SelfDestruct;
+
+impl Detector for SelfDestruct {
+    fn id(&self) -> &'static str {
+        "selfdestruct"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+
+    fn detect(&self, scope: &Scope) -> Vec<Hit> {
+        scope
+            .statements()
+            .filter(|statement| statement.opcode == 0xFF)
+            .map(|statement| Hit {
+                offset: statement.offset,
+                description: "SELFDESTRUCT is reachable".to_string(),
+            })
+            .collect()
+    }
+}
+
+pub struct DelegateCallToCalldata;
+
+impl Detector for DelegateCallToCalldata {
+    fn id(&self) -> &'static str {
+        "delegatecall-calldata"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+
+    fn detect(&self, scope: &Scope) -> Vec<Hit> {
+        scope
+            .statements()
+            .filter(|statement| statement.opcode == 0xF4)
+            .filter(|statement| has_origin(statement.operands[1], scope, &[0x35]))
+            .map(|statement| Hit {
+                offset: statement.offset,
+                description: "DELEGATECALL to an address taken from calldata".to_string(),
+            })
+            .collect()
+    }
+}
+
+pub struct TxOrigin;
+
+impl Detector for TxOrigin {
+    fn id(&self) -> &'static str {
+        "tx-origin"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+
+    fn detect(&self, scope: &Scope) -> Vec<Hit> {
+        // LT, GT, SLT, SGT, EQ
+        scope
+            .statements()
+            .filter(|statement| matches!(statement.opcode, 0x10..=0x14))
+            .filter(|statement| {
+                statement
+                    .operands
+                    .iter()
+                    .any(|operand| has_origin(*operand, scope, &[0x32]))
+            })
+            .map(|statement| Hit {
+                offset: statement.offset,
+                description: "ORIGIN compared, tx.origin used for authorization".to_string(),
+            })
+            .collect()
+    }
+}
+
+pub struct UncheckedCall;
+
+impl Detector for UncheckedCall {
+    fn id(&self) -> &'static str {
+        "unchecked-call"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+
+    fn detect(&self, scope: &Scope) -> Vec<Hit> {
+        let used = used_values(scope.ir);
+        scope
+            .statements()
+            .filter(|statement| matches!(statement.opcode, 0xF1 | 0xF2 | 0xF4))
+            .filter(|statement| statement.result.is_some_and(|value| !used.contains(&value)))
+            .map(|statement| Hit {
+                offset: statement.offset,
+                description: "return value of the call is never checked".to_string(),
+            })
+            .collect()
+    }
+}
+
+pub struct CallCode;
+
+impl Detector for CallCode {
+    fn id(&self) -> &'static str {
+        "callcode"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+
+    fn detect(&self, scope: &Scope) -> Vec<Hit> {
+        scope
+            .statements()
+            .filter(|statement| statement.opcode == 0xF2)
+            .map(|statement| Hit {
+                offset: statement.offset,
+                description: "CALLCODE is deprecated, DELEGATECALL keeps the caller".to_string(),
+            })
+            .collect()
+    }
+}
+
+pub struct StoreAfterCall;
+
+impl Detector for StoreAfterCall {
+    fn id(&self) -> &'static str {
+        "store-after-call"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+
+    fn detect(&self, scope: &Scope) -> Vec<Hit> {
+        let in_scope = |offset: usize| {
+            scope
+                .cfg
+                .block_at(offset)
+                .is_some_and(|block| scope.blocks.contains(&block.start))
+        };
+
+        // paths from the entry, following internal functions back to their own caller only
+        let mut stores = BTreeMap::new();
+        scope.cfg.walk_marked::<Slot>(
+            scope.entry,
+            |instruction| instruction.opcode == 0xF1 && in_scope(instruction.offset),
+            |instruction, _, call| {
+                if let Some(call) = call
+                    && instruction.opcode == 0x55
+                    && in_scope(instruction.offset)
+                {
+                    stores.entry(instruction.offset).or_insert(call);
+                }
+            },
+        );
+
+        stores
+            .into_iter()
+            .map(|(store, call)| Hit {
+                offset: store,
+                description: format!("SSTORE after external CALL at {:04x}, reentrancy", call),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let function = match (&self.selector, &self.signature) {
+            (Some(selector), Some(signature)) => format!("{} {}", selector, signature),
+            _ => "dispatcher".to_string(),
+        };
+        write!(
+            f,
+            "{:<6} {:04x}  {}  {}: {}",
+            self.severity, self.offset, function, self.detector, self.description
+        )
+    }
+}
+
+impl fmt::Display for Findings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.findings.is_empty() {
+            return writeln!(f, "no findings");
+        }
+        for finding in &self.findings {
+            writeln!(f, "{}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unchecked_call_then_store() {
+        // CALL(gas, caller, 0, 0, 0, 0, 0) POP, then SSTORE(0, 1) and SELFDESTRUCT(caller)
+        let bytecode = hex::decode("60006000600060006000335af150600160005533ff").unwrap();
+        let program = Program::load(&bytecode, true, false);
+
+        let findings = run_detectors(&program, &HashMap::new(), &default_detectors()).findings;
+        let found: Vec<(&str, usize)> = findings
+            .iter()
+            .map(|finding| (finding.detector, finding.offset))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("selfdestruct", 0x14),
+                ("unchecked-call", 0x0c),
+                ("store-after-call", 0x12)
+            ]
+        );
+    }
+
+    #[test]
+    fn store_before_call_through_shared_helper() {
+        // 00: PUSH1 0x05 PUSH1 0x1c JUMP
+        // 05: JUMPDEST SSTORE(0, 1) CALL(gas, caller, 0, 0, 0, 0, 0) POP PUSH1 0x1a PUSH1 0x1c JUMP
+        // 1a: JUMPDEST STOP
+        // 1c: JUMPDEST JUMP
+        let bytecode =
+            hex::decode("6005601c565b6001600055600080808080335af150601a601c565b005b56").unwrap();
+        let program = Program::load(&bytecode, true, false);
+
+        let findings = run_detectors(&program, &HashMap::new(), &default_detectors()).findings;
+        let found: Vec<(&str, usize)> = findings
+            .iter()
+            .map(|finding| (finding.detector, finding.offset))
+            .collect();
+        assert_eq!(found, vec![("unchecked-call", 0x13)]);
+    }
+}
@@ -0,0 +1,542 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use serde::Serialize;
+
+use crate::{
+    cfg::{AbstractValue, Cfg},
+    loader::{Program, SectionKind},
+    parser::{Instruction, opcode_to_name},
+};
+
+// Instruction level LCS is quadratic, bigger runs are reported as replaced wholesale.
+const MAX_LCS_CELLS: usize = 1 << 22;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Unchanged,
+    Changed,
+    Added,
+    Removed,
+}
+
+#[derive(Serialize)]
+pub struct DiffLine {
+    pub kind: char, // '-' only in the old code, '+' only in the new one
+    pub offset: usize,
+    pub instruction: String,
+}
+
+impl fmt::Display for DiffLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:04x}: {}", self.kind, self.offset, self.instruction)
+    }
+}
+
+#[derive(Serialize)]
+pub struct FunctionDiff {
+    pub selector: String,
+    pub signature: String,
+    pub status: Status,
+    pub lines: Vec<DiffLine>,
+}
+
+// Two programs compared by function selector and basic block. Metadata is ignored and
+// pushed jump targets are compared as labels of the aligned blocks, so code shifting
+// around doesn't count.
+#[derive(Serialize)]
+pub struct ProgramDiff {
+    pub functions: Vec<FunctionDiff>,
+    pub shared: Vec<DiffLine>, // dispatcher and code outside any function
+    pub init_skipped: bool,    // only runtime code is compared
+}
+
+impl ProgramDiff {
+    pub fn is_identical(&self) -> bool {
+        self.shared.is_empty()
+            && self
+                .functions
+                .iter()
+                .all(|function| function.status == Status::Unchanged)
+    }
+}
+
+impl fmt::Display for ProgramDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |status| self.functions.iter().filter(|f| f.status == status).count();
+        writeln!(
+            f,
+            "functions: {} unchanged, {} changed, {} added, {} removed",
+            count(Status::Unchanged),
+            count(Status::Changed),
+            count(Status::Added),
+            count(Status::Removed)
+        )?;
+
+        for function in &self.functions {
+            let marker = match function.status {
+                Status::Unchanged => continue,
+                Status::Changed => '~',
+                Status::Added => '+',
+                Status::Removed => '-',
+            };
+
+            writeln!(f, "{} {} {}", marker, function.selector, function.signature)?;
+
+            for line in &function.lines {
+                writeln!(f, "    {}", line)?;
+            }
+        }
+
+        if !self.shared.is_empty() {
+            writeln!(f, "~ dispatcher and shared code")?;
+            for line in &self.shared {
+                writeln!(f, "    {}", line)?;
+            }
+        }
+
+        if self.init_skipped {
+            writeln!(f, "; init code not compared")?;
+        }
+
+        Ok(())
+    }
+}
+
+// Code of one program split for comparison.
+struct Side<'a> {
+    cfg: Cfg,
+    targets: HashMap<usize, usize>, // PUSH offset to the block it jumps to
+    normalized: HashMap<usize, String>, // instruction offset to normalized text
+    entrypoints: HashMap<[u8; 4], usize>,
+    program: &'a Program,
+}
+
+impl<'a> Side<'a> {
+    fn new(program: &'a Program) -> Self {
+        let instructions: &[Instruction] = program
+            .sections
+            .iter()
+            .find(|section| matches!(section.kind, SectionKind::Runtime))
+            .and_then(|section| section.instructions.as_deref())
+            .unwrap_or_default();
+
+        let jumpdests: HashSet<usize> = instructions
+            .iter()
+            .filter(|i| i.opcode == 0x5B)
+            .map(|i| i.offset)
+            .collect();
+
+        // PUSHes whose value is jumped to, other constants matching a JUMPDEST are kept
+        let cfg = Cfg::from_instructions(instructions);
+        let mut targets = HashMap::new();
+        cfg.walk_with::<Pushed>(0, |instruction, inputs| {
+            if matches!(instruction.opcode, 0x56 | 0x57)
+                && let Some(Pushed::Push { offset, value }) = inputs.first()
+                && jumpdests.contains(value)
+            {
+                targets.insert(*offset, *value);
+            }
+        });
+
+        // the same label for every target until the blocks are aligned
+        let labels = targets
+            .keys()
+            .map(|&offset| (offset, "@label".to_string()))
+            .collect();
+        let normalized = instructions
+            .iter()
+            .map(|instruction| (instruction.offset, normalize(instruction, &labels)))
+            .collect();
+
+        Side {
+            cfg,
+            targets,
+            normalized,
+            entrypoints: program
+                .entrypoints
+                .iter()
+                .map(|entry| (entry.selector, entry.offset))
+                .collect(),
+            program,
+        }
+    }
+
+    // Blocks reachable from the function entry, in code order.
+    fn function_blocks(&self, entry: usize) -> BTreeSet<usize> {
+        self.cfg.walk(entry).blocks
+    }
+
+    // Name jump targets after the block they go to.
+    fn label(&mut self, blocks: &HashMap<usize, String>) {
+        for (&offset, target) in &self.targets {
+            let instruction = self
+                .cfg
+                .block_at(offset)
+                .and_then(|block| block.instructions.iter().find(|i| i.offset == offset))
+                .expect("target pushed by an instruction");
+            let labels = HashMap::from([(offset, blocks[target].clone())]);
+            self.normalized
+                .insert(offset, normalize(instruction, &labels));
+        }
+    }
+
+    fn block_text(&self, start: usize) -> Vec<&str> {
+        self.cfg.blocks[&start]
+            .instructions
+            .iter()
+            .map(|i| self.normalized[&i.offset].as_str())
+            .collect()
+    }
+}
+
+// Value left on the stack by a PUSH, tracked to the JUMP or JUMPI using it.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Pushed {
+    Push { offset: usize, value: usize },
+    Unknown,
+}
+
+impl AbstractValue for Pushed {
+    fn unknown() -> Self {
+        Pushed::Unknown
+    }
+
+    fn jump_target(&self) -> Option<usize> {
+        match self {
+            Pushed::Push { value, .. } => Some(*value),
+            Pushed::Unknown => None,
+        }
+    }
+
+    fn apply(instruction: &Instruction, _: &[Self]) -> Self {
+        let value = instruction.data.iter().try_fold(0usize, |acc, &b| {
+            acc.checked_mul(256).map(|acc| acc + b as usize)
+        });
+        match (instruction.opcode, value) {
+            (0x60..=0x7F, Some(value)) => Pushed::Push {
+                offset: instruction.offset,
+                value,
+            },
+            _ => Pushed::Unknown,
+        }
+    }
+}
+
+// Mnemonic and data, PUSH of a jump target becomes its label whatever its width.
+fn normalize(instruction: &Instruction, labels: &HashMap<usize, String>) -> String {
+    if instruction.data.is_empty() {
+        return opcode_to_name(instruction.opcode).to_string();
+    }
+
+    if let Some(label) = labels.get(&instruction.offset) {
+        return format!("PUSH {}", label);
+    }
+
+    format!(
+        "{} 0x{}",
+        opcode_to_name(instruction.opcode),
+        hex::encode(&instruction.data)
+    )
+}
+
+pub fn diff_programs(
+    old: &Program,
+    new: &Program,
+    selectors: &HashMap<u32, String>,
+) -> ProgramDiff {
+    let init_skipped = [old, new].iter().any(|program| {
+        program
+            .sections
+            .iter()
+            .any(|section| section.kind == SectionKind::Init)
+    });
+    let mut old = Side::new(old);
+    let mut new = Side::new(new);
+    align(&mut old, &mut new);
+
+    let all: BTreeSet<[u8; 4]> = old
+        .entrypoints
+        .keys()
+        .chain(new.entrypoints.keys())
+        .copied()
+        .collect();
+
+    let mut functions = Vec::new();
+    let mut old_covered = BTreeSet::new();
+    let mut new_covered = BTreeSet::new();
+
+    for selector in all {
+        let old_blocks = old
+            .entrypoints
+            .get(&selector)
+            .map(|&e| old.function_blocks(e));
+        let new_blocks = new
+            .entrypoints
+            .get(&selector)
+            .map(|&e| new.function_blocks(e));
+
+        // guessed parameters of the new code win, it's usually the one under review
+        let signature = [&new, &old]
+            .iter()
+            .find_map(|side| {
+                side.program
+                    .entrypoints
+                    .iter()
+                    .find(|entry| entry.selector == selector)
+            })
+            .map(|entry| entry.signature(selectors).0)
+            .unwrap_or_default();
+
+        let (status, lines) = match (&old_blocks, &new_blocks) {
+            (Some(a), Some(b)) => {
+                let lines = diff_blocks(&old, a, &new, b);
+                let status = if lines.is_empty() {
+                    Status::Unchanged
+                } else {
+                    Status::Changed
+                };
+                (status, lines)
+            }
+            (Some(_), None) => (Status::Removed, Vec::new()),
+            (None, Some(_)) => (Status::Added, Vec::new()),
+            (None, None) => unreachable!(),
+        };
+
+        old_covered.extend(old_blocks.unwrap_or_default());
+        new_covered.extend(new_blocks.unwrap_or_default());
+
+        functions.push(FunctionDiff {
+            selector: format!("0x{}", hex::encode(selector)),
+            signature,
+            status,
+            lines,
+        });
+    }
+
+    let rest = |side: &Side, covered: &BTreeSet<usize>| -> BTreeSet<usize> {
+        side.cfg
+            .blocks
+            .keys()
+            .filter(|start| !covered.contains(start))
+            .copied()
+            .collect()
+    };
+    let shared = diff_blocks(
+        &old,
+        &rest(&old, &old_covered),
+        &new,
+        &rest(&new, &new_covered),
+    );
+
+    ProgramDiff {
+        functions,
+        shared,
+        init_skipped,
+    }
+}
+
+// Label the blocks of both sides after their counterpart in the other, so jumps to
+// matching blocks compare equal and jumps to different ones don't. Blocks are matched
+// with jump targets erased, unique blocks only when there are too many for an LCS.
+fn align(old: &mut Side, new: &mut Side) {
+    let old_blocks: Vec<usize> = old.cfg.blocks.keys().copied().collect();
+    let new_blocks: Vec<usize> = new.cfg.blocks.keys().copied().collect();
+    let old_text: Vec<Vec<&str>> = old_blocks.iter().map(|&b| old.block_text(b)).collect();
+    let new_text: Vec<Vec<&str>> = new_blocks.iter().map(|&b| new.block_text(b)).collect();
+
+    let pairs = if old_text.len() * new_text.len() > MAX_LCS_CELLS {
+        let (old_counts, new_counts) = (count(&old_text), count(&new_text));
+        let unique =
+            |text: &Vec<&str>| old_counts.get(text) == Some(&1) && new_counts.get(text) == Some(&1);
+        old_text
+            .iter()
+            .enumerate()
+            .filter(|(_, text)| unique(text))
+            .filter_map(|(i, text)| Some((i, new_text.iter().position(|t| t == text)?)))
+            .collect()
+    } else {
+        lcs(&old_text, &new_text)
+    };
+
+    let mut old_labels: HashMap<usize, String> = old_blocks
+        .iter()
+        .map(|&b| (b, format!("@old_{:04x}", b)))
+        .collect();
+    let mut new_labels: HashMap<usize, String> = new_blocks
+        .iter()
+        .map(|&b| (b, format!("@new_{:04x}", b)))
+        .collect();
+    for (n, (i, j)) in pairs.into_iter().enumerate() {
+        old_labels.insert(old_blocks[i], format!("@{}", n));
+        new_labels.insert(new_blocks[j], format!("@{}", n));
+    }
+
+    old.label(&old_labels);
+    new.label(&new_labels);
+}
+
+fn count<'a>(text: &[Vec<&'a str>]) -> HashMap<Vec<&'a str>, usize> {
+    let mut counts = HashMap::new();
+    for block in text {
+        *counts.entry(block.clone()).or_default() += 1;
+    }
+    counts
+}
+
+// Align blocks first, then instructions of the blocks left unmatched in between.
+fn diff_blocks(
+    old: &Side,
+    old_blocks: &BTreeSet<usize>,
+    new: &Side,
+    new_blocks: &BTreeSet<usize>,
+) -> Vec<DiffLine> {
+    let old_blocks: Vec<usize> = old_blocks.iter().copied().collect();
+    let new_blocks: Vec<usize> = new_blocks.iter().copied().collect();
+    let old_text: Vec<Vec<&str>> = old_blocks.iter().map(|&b| old.block_text(b)).collect();
+    let new_text: Vec<Vec<&str>> = new_blocks.iter().map(|&b| new.block_text(b)).collect();
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    let matches = lcs(&old_text, &new_text);
+    for (mi, mj) in matches
+        .into_iter()
+        .chain([(old_blocks.len(), new_blocks.len())])
+    {
+        if i < mi || j < mj {
+            let removed: Vec<&Instruction> = old_blocks[i..mi]
+                .iter()
+                .flat_map(|b| old.cfg.blocks[b].instructions.iter())
+                .collect();
+            let added: Vec<&Instruction> = new_blocks[j..mj]
+                .iter()
+                .flat_map(|b| new.cfg.blocks[b].instructions.iter())
+                .collect();
+            diff_instructions(old, &removed, new, &added, &mut lines);
+        }
+        i = mi + 1;
+        j = mj + 1;
+    }
+
+    lines
+}
+
+fn diff_instructions(
+    old: &Side,
+    removed: &[&Instruction],
+    new: &Side,
+    added: &[&Instruction],
+    lines: &mut Vec<DiffLine>,
+) {
+    let old_text: Vec<&str> = removed
+        .iter()
+        .map(|i| old.normalized[&i.offset].as_str())
+        .collect();
+    let new_text: Vec<&str> = added
+        .iter()
+        .map(|i| new.normalized[&i.offset].as_str())
+        .collect();
+
+    let line = |kind, instruction: &Instruction| DiffLine {
+        kind,
+        offset: instruction.offset,
+        instruction: instruction.to_string(),
+    };
+
+    let (mut i, mut j) = (0, 0);
+    for (mi, mj) in lcs(&old_text, &new_text)
+        .into_iter()
+        .chain([(removed.len(), added.len())])
+    {
+        lines.extend(
+            removed[i..mi]
+                .iter()
+                .map(|instruction| line('-', instruction)),
+        );
+        lines.extend(
+            added[j..mj]
+                .iter()
+                .map(|instruction| line('+', instruction)),
+        );
+        i = mi + 1;
+        j = mj + 1;
+    }
+}
+
+// Index pairs of a longest common subsequence.
+fn lcs<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let (n, m) = (a.len(), b.len());
+    if n * m > MAX_LCS_CELLS {
+        return Vec::new();
+    }
+
+    // suffix lengths, table[i][j] = LCS of a[i..] and b[j..]
+    let mut table = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i][j] = if a[i] == b[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[i] == b[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lcs_pairs() {
+        let a = ["PUSH1", "ADD", "JUMP", "STOP"];
+        let b = ["PUSH1", "SUB", "JUMP", "STOP"];
+        assert_eq!(lcs(&a, &b), vec![(0, 0), (2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn stored_constant_equal_to_jumpdest() {
+        // PUSH1 <value> PUSH0 SSTORE JUMPDEST JUMPDEST STOP, 0x04 and 0x05 are JUMPDESTs
+        // but never jumped to
+        let old = Program::load(&hex::decode("60055f555b5b00").unwrap(), false, true);
+        let new = Program::load(&hex::decode("60045f555b5b00").unwrap(), false, true);
+
+        let diff = diff_programs(&old, &new, &HashMap::new());
+        assert!(!diff.is_identical());
+        assert_eq!(diff.shared[0].instruction, "60 PUSH1 0x05");
+    }
+
+    #[test]
+    fn jump_to_other_block() {
+        // PUSH1 <target> JUMP INVALID JUMPDEST STOP INVALID JUMPDEST STOP
+        let old = Program::load(&hex::decode("600756fe5b00fe5b00").unwrap(), false, true);
+        let new = Program::load(&hex::decode("600456fe5b00fe5b00").unwrap(), false, true);
+        let diff = diff_programs(&old, &new, &HashMap::new());
+        assert!(!diff.is_identical());
+        assert!(!diff.init_skipped);
+
+        // a wider push moves the code, still jumping to the same block
+        let new = Program::load(&hex::decode("61000856fe5b00fe5b00").unwrap(), false, true);
+        assert!(diff_programs(&old, &new, &HashMap::new()).is_identical());
+    }
+}
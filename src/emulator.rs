@@ -0,0 +1,702 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
+
+use serde::Serialize;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{
+    parser::{self, opcode_to_name, stack_io},
+    u256::U256,
+};
+
+const MAX_STEPS: usize = 1_000_000;
+const MAX_MEMORY: usize = 1 << 25;
+const MAX_TRACE_STACK: usize = 1 << 25; // bytes of stack kept in a trace
+const MAX_STACK: usize = 1024;
+
+// Call context and state the code runs against. Accounts other than the executing one
+// don't exist, their balance and code are empty.
+pub struct Environment {
+    pub calldata: Vec<u8>,
+    pub callvalue: U256,
+    pub caller: U256,
+    pub origin: U256,
+    pub address: U256,
+    pub balance: U256,
+    pub storage: BTreeMap<U256, U256>,
+    pub block_number: U256,
+    pub timestamp: U256,
+    pub chain_id: U256,
+    pub gas_limit: U256,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment {
+            calldata: Vec::new(),
+            callvalue: U256::ZERO,
+            caller: U256::from_be_bytes(&[0xca; 20]),
+            origin: U256::from_be_bytes(&[0xca; 20]),
+            address: U256::from_be_bytes(&[0xc0; 20]),
+            balance: U256::ZERO,
+            storage: BTreeMap::new(),
+            block_number: U256::from_u64(1),
+            timestamp: U256::from_u64(1),
+            chain_id: U256::ONE,
+            gas_limit: U256::from_u64(30_000_000),
+        }
+    }
+}
+
+// Machine state before executing the instruction at `pc`, stack top first, and the
+// memory the instruction writes. Whole memory per step doesn't fit for long runs.
+#[derive(Serialize)]
+pub struct Step {
+    pub pc: usize,
+    pub opcode: &'static str,
+    pub stack: Vec<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryWrite>,
+}
+
+#[derive(Serialize)]
+pub struct MemoryWrite {
+    pub offset: usize,
+    #[serde(serialize_with = "serialize_bytes")]
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "data", rename_all = "lowercase")]
+pub enum Outcome {
+    Stop,
+    Return(#[serde(serialize_with = "serialize_bytes")] Vec<u8>),
+    Revert(#[serde(serialize_with = "serialize_bytes")] Vec<u8>),
+    SelfDestruct,
+    // exceptional halt: invalid opcode or jump, stack errors, unsupported instructions
+    Error(String),
+}
+
+#[derive(Serialize)]
+pub struct Log {
+    pub topics: Vec<U256>,
+    #[serde(serialize_with = "serialize_bytes")]
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize)]
+pub struct Execution {
+    pub outcome: Outcome,
+    pub storage: BTreeMap<U256, U256>, // storage after execution, reverted runs keep the input
+    pub logs: Vec<Log>,
+    pub steps: Vec<Step>, // empty unless traced
+    pub truncated: bool,  // later steps left out of the trace past MAX_TRACE_STACK
+}
+
+impl fmt::Display for Execution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            let stack: Vec<String> = step.stack.iter().map(|v| v.to_string()).collect();
+            writeln!(
+                f,
+                "{:04x}: {:<14} [{}]",
+                step.pc,
+                step.opcode,
+                stack.join(", ")
+            )?;
+        }
+
+        if self.truncated {
+            writeln!(f, "; trace truncated after {} steps", self.steps.len())?;
+        }
+
+        match &self.outcome {
+            Outcome::Stop => writeln!(f, "stop")?,
+            Outcome::Return(data) => writeln!(f, "return 0x{}", hex::encode(data))?,
+            Outcome::Revert(data) => match describe_revert(data) {
+                Some(reason) => writeln!(f, "revert {}", reason)?,
+                None => writeln!(f, "revert 0x{}", hex::encode(data))?,
+            },
+            Outcome::SelfDestruct => writeln!(f, "selfdestruct")?,
+            Outcome::Error(error) => writeln!(f, "error: {}", error)?,
+        }
+
+        for log in &self.logs {
+            let topics: Vec<String> = log.topics.iter().map(|t| t.to_string()).collect();
+            writeln!(
+                f,
+                "log [{}] 0x{}",
+                topics.join(", "),
+                hex::encode(&log.data)
+            )?;
+        }
+
+        if !self.storage.is_empty() {
+            writeln!(f, "storage:")?;
+            for (slot, value) in &self.storage {
+                writeln!(f, "  {}: {}", slot, value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Solidity's Error(string) and Panic(uint256) reverts.
+pub fn describe_revert(data: &[u8]) -> Option<String> {
+    let (selector, arguments) = (data.get(..4)?, &data[4..]);
+    match selector {
+        [0x08, 0xc3, 0x79, 0xa0] => {
+            let length = U256::from_be_bytes(arguments.get(32..64)?).to_usize()?;
+            let message = arguments.get(64..64usize.checked_add(length)?)?;
+            Some(format!("Error({:?})", String::from_utf8_lossy(message)))
+        }
+        [0x4e, 0x48, 0x7b, 0x71] => {
+            let code = U256::from_be_bytes(arguments.get(..32)?);
+            Some(format!("Panic({})", code))
+        }
+        _ => None,
+    }
+}
+
+fn serialize_bytes<S: serde::Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("0x{}", hex::encode(bytes)))
+}
+
+struct Machine<'a> {
+    code: &'a [u8],
+    env: &'a Environment,
+    jumpdests: HashSet<usize>,
+    pc: usize,
+    stack: Vec<U256>,
+    memory: Vec<u8>,
+    storage: BTreeMap<U256, U256>,
+    transient: BTreeMap<U256, U256>,
+    logs: Vec<Log>,
+}
+
+// Runs code to completion, without gas accounting. Calls to and creation of other
+// contracts are not supported and halt execution.
+pub fn execute(code: &[u8], env: &Environment, trace: bool) -> Execution {
+    let mut machine = Machine {
+        code,
+        env,
+        jumpdests: parser::parse_bytecode(code)
+            .iter()
+            .filter(|i| i.opcode == 0x5B)
+            .map(|i| i.offset)
+            .collect(),
+        pc: 0,
+        stack: Vec::new(),
+        memory: Vec::new(),
+        storage: env.storage.clone(),
+        transient: BTreeMap::new(),
+        logs: Vec::new(),
+    };
+
+    let mut steps: Vec<Step> = Vec::new();
+    // bytes of memory writes kept in the trace, later writes are left out past MAX_MEMORY
+    let mut recorded = 0;
+    // bytes of stack kept in the trace, the trace stops past MAX_TRACE_STACK
+    let mut stacked = 0;
+    let mut truncated = false;
+    let mut outcome = Outcome::Error(format!("step limit of {} reached", MAX_STEPS));
+    for _ in 0..MAX_STEPS {
+        let mut written = None;
+        if trace && !truncated {
+            stacked += machine.stack.len() * 32;
+            truncated = stacked > MAX_TRACE_STACK;
+        }
+        if trace && !truncated {
+            let opcode = code.get(machine.pc).copied().unwrap_or(0x00);
+            written = machine.written(opcode);
+            steps.push(Step {
+                pc: machine.pc,
+                opcode: opcode_to_name(opcode),
+                stack: machine.stack.iter().rev().copied().collect(),
+                memory: None,
+            });
+        }
+
+        let result = machine.step();
+        if let Some((offset, size)) = written
+            && result.is_ok()
+            && recorded + size <= MAX_MEMORY
+            && let Some(step) = steps.last_mut()
+        {
+            recorded += size;
+            step.memory = Some(MemoryWrite {
+                offset,
+                data: machine.memory[offset..offset + size].to_vec(),
+            });
+        }
+
+        match result {
+            Ok(None) => {}
+            Ok(Some(halt)) => {
+                outcome = halt;
+                break;
+            }
+            Err(error) => {
+                outcome = Outcome::Error(error);
+                break;
+            }
+        }
+    }
+
+    let success = matches!(
+        outcome,
+        Outcome::Stop | Outcome::Return(_) | Outcome::SelfDestruct
+    );
+    Execution {
+        storage: if success {
+            machine.storage
+        } else {
+            env.storage.clone()
+        },
+        logs: if success { machine.logs } else { Vec::new() },
+        outcome,
+        steps,
+        truncated,
+    }
+}
+
+impl Machine<'_> {
+    fn pop(&mut self) -> Result<U256, String> {
+        self.stack
+            .pop()
+            .ok_or_else(|| format!("stack underflow at 0x{:04x}", self.pc))
+    }
+
+    fn pop_usize(&mut self) -> Result<usize, String> {
+        let value = self.pop()?;
+        value
+            .to_usize()
+            .ok_or_else(|| format!("value {} too large at 0x{:04x}", value, self.pc))
+    }
+
+    fn push(&mut self, value: U256) -> Result<(), String> {
+        if self.stack.len() >= MAX_STACK {
+            return Err(format!("stack overflow at 0x{:04x}", self.pc));
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    // Memory range the instruction is about to write, if it writes any.
+    fn written(&self, opcode: u8) -> Option<(usize, usize)> {
+        let argument = |n: usize| {
+            self.stack
+                .len()
+                .checked_sub(n + 1)
+                .and_then(|index| self.stack[index].to_usize())
+        };
+        let (offset, size) = match opcode {
+            0x52 => (argument(0)?, 32),                         // MSTORE
+            0x53 => (argument(0)?, 1),                          // MSTORE8
+            0x37 | 0x39 | 0x5E => (argument(0)?, argument(2)?), // CALLDATACOPY, CODECOPY, MCOPY
+            0x3C => (argument(1)?, argument(3)?),               // EXTCODECOPY
+            _ => return None,
+        };
+        (size > 0).then_some((offset, size))
+    }
+
+    // Grows memory to cover `offset..offset + size`, zero sized accesses don't touch it.
+    fn expand(&mut self, offset: usize, size: usize) -> Result<(), String> {
+        if size == 0 {
+            return Ok(());
+        }
+        let end = offset
+            .checked_add(size)
+            .filter(|&end| end <= MAX_MEMORY)
+            .ok_or_else(|| format!("memory access too large at 0x{:04x}", self.pc))?;
+        if end > self.memory.len() {
+            self.memory.resize(end.div_ceil(32) * 32, 0);
+        }
+        Ok(())
+    }
+
+    fn read_memory(&mut self, offset: usize, size: usize) -> Result<Vec<u8>, String> {
+        self.expand(offset, size)?;
+        Ok(if size == 0 {
+            Vec::new()
+        } else {
+            self.memory[offset..offset + size].to_vec()
+        })
+    }
+
+    // CALLDATACOPY, CODECOPY and friends, reads past the source are zeroes.
+    fn copy_to_memory(&mut self, source: &[u8]) -> Result<(), String> {
+        let dest = self.pop_usize()?;
+        let offset = self.pop()?;
+        let size = self.pop_usize()?;
+        self.expand(dest, size)?;
+
+        for i in 0..size {
+            let byte = offset
+                .to_usize()
+                .and_then(|offset| offset.checked_add(i))
+                .and_then(|index| source.get(index))
+                .copied()
+                .unwrap_or(0);
+            self.memory[dest + i] = byte;
+        }
+        Ok(())
+    }
+
+    fn jump(&mut self, target: U256) -> Result<(), String> {
+        match target.to_usize().filter(|t| self.jumpdests.contains(t)) {
+            Some(target) => {
+                self.pc = target;
+                Ok(())
+            }
+            None => Err(format!("invalid jump to {} at 0x{:04x}", target, self.pc)),
+        }
+    }
+
+    // Executes one instruction, returns the outcome once execution halts.
+    fn step(&mut self) -> Result<Option<Outcome>, String> {
+        let Some(&opcode) = self.code.get(self.pc) else {
+            return Ok(Some(Outcome::Stop)); // running off the end of the code is a STOP
+        };
+        let env = self.env;
+        let mut next = self.pc + 1;
+
+        match opcode {
+            0x00 => return Ok(Some(Outcome::Stop)),
+            0x01..=0x0B | 0x10..=0x1D => {
+                let inputs = (0..stack_io(opcode).0)
+                    .map(|_| self.pop())
+                    .collect::<Result<Vec<_>, _>>()?;
+                let value = evaluate(opcode, &inputs).expect("arithmetic opcode");
+                self.push(value)?;
+            }
+            0x20 => {
+                let offset = self.pop_usize()?;
+                let size = self.pop_usize()?;
+                let data = self.read_memory(offset, size)?;
+                self.push(U256::from_be_bytes(&keccak(&data)))?;
+            }
+            0x30 => self.push(env.address)?,
+            0x31 => {
+                let account = self.pop()?;
+                self.push(if account == env.address {
+                    env.balance
+                } else {
+                    U256::ZERO
+                })?;
+            }
+            0x32 => self.push(env.origin)?,
+            0x33 => self.push(env.caller)?,
+            0x34 => self.push(env.callvalue)?,
+            0x35 => {
+                let offset = self.pop()?;
+                let mut word = [0u8; 32];
+                if let Some(offset) = offset.to_usize() {
+                    for (i, byte) in word.iter_mut().enumerate() {
+                        *byte = offset
+                            .checked_add(i)
+                            .and_then(|index| env.calldata.get(index))
+                            .copied()
+                            .unwrap_or(0);
+                    }
+                }
+                self.push(U256::from_be_bytes(&word))?;
+            }
+            0x36 => self.push(U256::from_u64(env.calldata.len() as u64))?,
+            0x37 => self.copy_to_memory(&env.calldata)?,
+            0x38 => self.push(U256::from_u64(self.code.len() as u64))?,
+            0x39 => self.copy_to_memory(self.code)?,
+            0x3A | 0x48 => self.push(U256::ZERO)?, // GASPRICE, BASEFEE
+            0x3B | 0x3F => {
+                // EXTCODESIZE, EXTCODEHASH of the executing contract or an empty account
+                let account = self.pop()?;
+                let own = account == env.address;
+                self.push(match (opcode, own) {
+                    (0x3B, true) => U256::from_u64(self.code.len() as u64),
+                    (_, true) => U256::from_be_bytes(&keccak(self.code)),
+                    _ => U256::ZERO,
+                })?;
+            }
+            0x3C => {
+                let account = self.pop()?;
+                let code = if account == env.address {
+                    self.code
+                } else {
+                    &[]
+                };
+                self.copy_to_memory(code)?;
+            }
+            0x3D => self.push(U256::ZERO)?, // no calls, no return data
+            0x3E => {
+                let _ = self.pop()?;
+                let offset = self.pop()?;
+                let size = self.pop()?;
+                if !(offset.is_zero() && size.is_zero()) {
+                    return Err(format!("return data out of bounds at 0x{:04x}", self.pc));
+                }
+            }
+            0x40 => {
+                let _ = self.pop()?;
+                self.push(U256::ZERO)?;
+            }
+            0x41 => self.push(U256::ZERO)?, // COINBASE
+            0x42 => self.push(env.timestamp)?,
+            0x43 => self.push(env.block_number)?,
+            0x44 => self.push(U256::ZERO)?, // PREVRANDAO
+            0x45 => self.push(env.gas_limit)?,
+            0x46 => self.push(env.chain_id)?,
+            0x47 => self.push(env.balance)?,
+            0x49 => {
+                let _ = self.pop()?;
+                self.push(U256::ZERO)?;
+            }
+            0x4A => self.push(U256::ZERO)?, // BLOBBASEFEE
+            0x50 => {
+                self.pop()?;
+            }
+            0x51 => {
+                let offset = self.pop_usize()?;
+                let word = self.read_memory(offset, 32)?;
+                self.push(U256::from_be_bytes(&word))?;
+            }
+            0x52 | 0x53 => {
+                let offset = self.pop_usize()?;
+                let value = self.pop()?;
+                if opcode == 0x52 {
+                    self.expand(offset, 32)?;
+                    self.memory[offset..offset + 32].copy_from_slice(&value.to_be_bytes());
+                } else {
+                    self.expand(offset, 1)?;
+                    self.memory[offset] = value.to_be_bytes()[31];
+                }
+            }
+            0x54 | 0x5C => {
+                let key = self.pop()?;
+                let store = if opcode == 0x54 {
+                    &self.storage
+                } else {
+                    &self.transient
+                };
+                let value = store.get(&key).copied().unwrap_or_default();
+                self.push(value)?;
+            }
+            0x55 | 0x5D => {
+                let key = self.pop()?;
+                let value = self.pop()?;
+                let store = if opcode == 0x55 {
+                    &mut self.storage
+                } else {
+                    &mut self.transient
+                };
+                store.insert(key, value);
+            }
+            0x56 => {
+                let target = self.pop()?;
+                return self.jump(target).map(|_| None);
+            }
+            0x57 => {
+                let target = self.pop()?;
+                let condition = self.pop()?;
+                if !condition.is_zero() {
+                    return self.jump(target).map(|_| None);
+                }
+            }
+            0x58 => self.push(U256::from_u64(self.pc as u64))?,
+            0x59 => self.push(U256::from_u64(self.memory.len() as u64))?,
+            0x5A => self.push(env.gas_limit)?, // no gas accounting
+            0x5B => {}
+            0x5E => {
+                let dest = self.pop_usize()?;
+                let source = self.pop_usize()?;
+                let size = self.pop_usize()?;
+                let data = self.read_memory(source, size)?;
+                self.expand(dest, size)?;
+                self.memory[dest..dest + size].copy_from_slice(&data);
+            }
+            0x5F..=0x7F => {
+                let size = (opcode - 0x5F) as usize;
+                let start = (self.pc + 1).min(self.code.len());
+                let end = (self.pc + 1 + size).min(self.code.len());
+                // push data cut by the end of the code is zero padded on the right
+                let mut data = self.code[start..end].to_vec();
+                data.resize(size, 0);
+                self.push(U256::from_be_bytes(&data))?;
+                next += size;
+            }
+            0x80..=0x8F => {
+                let depth = (opcode - 0x7F) as usize;
+                let value = self
+                    .stack
+                    .len()
+                    .checked_sub(depth)
+                    .map(|index| self.stack[index])
+                    .ok_or_else(|| format!("stack underflow at 0x{:04x}", self.pc))?;
+                self.push(value)?;
+            }
+            0x90..=0x9F => {
+                let depth = (opcode - 0x8F) as usize;
+                let top = self.stack.len().checked_sub(1);
+                let other = self.stack.len().checked_sub(depth + 1);
+                match (top, other) {
+                    (Some(top), Some(other)) => self.stack.swap(top, other),
+                    _ => return Err(format!("stack underflow at 0x{:04x}", self.pc)),
+                }
+            }
+            0xA0..=0xA4 => {
+                let offset = self.pop_usize()?;
+                let size = self.pop_usize()?;
+                let topics = (0..opcode - 0xA0)
+                    .map(|_| self.pop())
+                    .collect::<Result<_, _>>()?;
+                let data = self.read_memory(offset, size)?;
+                self.logs.push(Log { topics, data });
+            }
+            0xF3 | 0xFD => {
+                let offset = self.pop_usize()?;
+                let size = self.pop_usize()?;
+                let data = self.read_memory(offset, size)?;
+                return Ok(Some(if opcode == 0xF3 {
+                    Outcome::Return(data)
+                } else {
+                    Outcome::Revert(data)
+                }));
+            }
+            0xFF => {
+                self.pop()?;
+                return Ok(Some(Outcome::SelfDestruct));
+            }
+            0xF0 | 0xF1 | 0xF2 | 0xF4 | 0xF5 | 0xFA => {
+                return Err(format!(
+                    "{} at 0x{:04x} is not supported",
+                    opcode_to_name(opcode),
+                    self.pc
+                ));
+            }
+            _ => {
+                return Err(format!(
+                    "invalid opcode 0x{:02x} at 0x{:04x}",
+                    opcode, self.pc
+                ));
+            }
+        }
+
+        self.pc = next;
+        Ok(None)
+    }
+}
+
+// Result of an arithmetic, comparison or bitwise opcode, `inputs[0]` is the top of the
+// stack. None for other opcodes or missing inputs.
+pub fn evaluate(opcode: u8, inputs: &[U256]) -> Option<U256> {
+    if !matches!(opcode, 0x01..=0x0B | 0x10..=0x1D) || inputs.len() < stack_io(opcode).0 {
+        return None;
+    }
+
+    let a = inputs[0];
+    let b = inputs.get(1).copied().unwrap_or_default();
+    let shift = a.to_usize().unwrap_or(usize::MAX);
+    let value = match opcode {
+        0x01 => a.wrapping_add(b),
+        0x02 => a.wrapping_mul(b),
+        0x03 => a.wrapping_sub(b),
+        0x04 => a.div_rem(b).map_or(U256::ZERO, |(q, _)| q),
+        0x05 => a.signed_div_rem(b).map_or(U256::ZERO, |(q, _)| q),
+        0x06 => a.div_rem(b).map_or(U256::ZERO, |(_, r)| r),
+        0x07 => a.signed_div_rem(b).map_or(U256::ZERO, |(_, r)| r),
+        0x08 => a.add_mod(b, inputs[2]),
+        0x09 => a.mul_mod(b, inputs[2]),
+        0x0A => a.wrapping_pow(b),
+        0x0B => b.sign_extend(a),
+        0x10 => bool_word(a < b),
+        0x11 => bool_word(a > b),
+        0x12 => bool_word(a.signed_cmp(b).is_lt()),
+        0x13 => bool_word(a.signed_cmp(b).is_gt()),
+        0x14 => bool_word(a == b),
+        0x15 => bool_word(a.is_zero()),
+        0x16 => a & b,
+        0x17 => a | b,
+        0x18 => a ^ b,
+        0x19 => !a,
+        0x1A => b.byte(a),
+        0x1B => b.shift_left(shift),
+        0x1C => b.shift_right(shift),
+        0x1D => b.arithmetic_shift_right(shift),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn bool_word(value: bool) -> U256 {
+    if value { U256::ONE } else { U256::ZERO }
+}
+
+pub fn keccak(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    let mut hash = [0u8; 32];
+    hasher.update(data);
+    hasher.finalize(&mut hash);
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn return_calldata_sum() {
+        // CALLDATALOAD(0) + CALLDATALOAD(32), stored in slot 1 and returned
+        let code = hex::decode("5f356020350180600155805f5260205ff3").unwrap();
+        let mut calldata = U256::from_u64(40).to_be_bytes().to_vec();
+        calldata.extend(U256::from_u64(2).to_be_bytes());
+        let env = Environment {
+            calldata,
+            ..Default::default()
+        };
+
+        let execution = execute(&code, &env, true);
+        assert_eq!(
+            execution.outcome,
+            Outcome::Return(U256::from_u64(42).to_be_bytes().to_vec())
+        );
+        assert_eq!(execution.storage[&U256::ONE], U256::from_u64(42));
+        assert_eq!(execution.steps[4].opcode, "ADD");
+        assert_eq!(
+            execution.steps[5].stack,
+            vec![U256::from_u64(42)] // stack before DUP1
+        );
+
+        // only the word MSTORE writes is recorded
+        let write = execution.steps[10].memory.as_ref().unwrap();
+        assert_eq!(execution.steps[10].opcode, "MSTORE");
+        assert_eq!(write.offset, 0);
+        assert_eq!(write.data, U256::from_u64(42).to_be_bytes().to_vec());
+        assert!(execution.steps[11].memory.is_none());
+    }
+
+    #[test]
+    fn trace_truncated_past_stack_budget() {
+        // JUMPDEST PUSH0 PUSH1 0 JUMP, the stack grows by a word each iteration
+        let code = hex::decode("5b5f600056").unwrap();
+
+        let execution = execute(&code, &Environment::default(), true);
+        assert!(execution.truncated);
+        let words: usize = execution.steps.iter().map(|step| step.stack.len()).sum();
+        assert!(words * 32 <= MAX_TRACE_STACK);
+        assert!(execution.steps.len() < MAX_STEPS);
+        assert!(
+            matches!(execution.outcome, Outcome::Error(ref error) if error.starts_with("stack overflow"))
+        );
+    }
+
+    #[test]
+    fn calldataload_past_usize() {
+        // CALLDATALOAD(2^64 - 1) STOP
+        let code = hex::decode("67ffffffffffffffff3500").unwrap();
+
+        let execution = execute(&code, &Environment::default(), false);
+        assert_eq!(execution.outcome, Outcome::Stop);
+    }
+}
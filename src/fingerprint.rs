@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::{
+    cfg::Cfg,
+    loader::{Program, SectionKind},
+    parser::Instruction,
+};
+
+const NGRAM: usize = 4;
+// Functions at least this similar are considered the same code.
+const FUNCTION_MATCH: f64 = 0.8;
+
+// Set of hashed opcode n-grams. PUSH data is masked except for PUSH4, which keeps
+// selectors and error ids, so offsets, addresses and metadata don't matter.
+#[derive(Debug, Clone, Default)]
+pub struct Fingerprint {
+    pub ngrams: HashSet<u64>,
+}
+
+impl Fingerprint {
+    fn from_instructions<'a>(instructions: impl Iterator<Item = &'a Instruction>) -> Self {
+        let tokens: Vec<u64> = instructions.map(token).collect();
+
+        // tiny functions still get a single, shorter n-gram
+        let ngrams = if tokens.len() < NGRAM {
+            [fnv(&tokens)].into_iter().collect()
+        } else {
+            tokens.windows(NGRAM).map(fnv).collect()
+        };
+
+        Fingerprint { ngrams }
+    }
+
+    // Jaccard index of the n-gram sets.
+    pub fn similarity(&self, other: &Fingerprint) -> f64 {
+        let union = self.ngrams.union(&other.ngrams).count();
+        if union == 0 {
+            return 1.0;
+        }
+        self.ngrams.intersection(&other.ngrams).count() as f64 / union as f64
+    }
+}
+
+fn token(instruction: &Instruction) -> u64 {
+    let data = match instruction.opcode {
+        0x63 => instruction
+            .data
+            .iter()
+            .fold(0, |acc, &b| (acc << 8) | b as u64),
+        _ => 0,
+    };
+    ((instruction.opcode as u64) << 32) | data
+}
+
+// FNV-1a, stable across builds unlike the std hasher.
+fn fnv(values: &[u64]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for value in values {
+        for byte in value.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+pub struct ContractFingerprint {
+    pub contract: Fingerprint,
+    pub functions: Vec<([u8; 4], Fingerprint)>,
+}
+
+// Fingerprint of the runtime code and of the blocks reachable from each function.
+pub fn fingerprint_program(program: &Program) -> ContractFingerprint {
+    let instructions: &[Instruction] = program
+        .sections
+        .iter()
+        .find(|section| matches!(section.kind, SectionKind::Runtime))
+        .and_then(|section| section.instructions.as_deref())
+        .unwrap_or_default();
+    let cfg = Cfg::from_instructions(instructions);
+
+    let functions = program
+        .entrypoints
+        .iter()
+        .map(|entry| {
+            let blocks = cfg.walk(entry.offset).blocks;
+            (
+                entry.selector,
+                Fingerprint::from_instructions(cfg.instructions(&blocks)),
+            )
+        })
+        .collect();
+
+    ContractFingerprint {
+        contract: Fingerprint::from_instructions(instructions.iter()),
+        functions,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Similarity {
+    pub score: f64,
+    pub functions_matched: usize, // target functions with a close match, renamed or not
+    pub functions_total: usize,
+}
+
+pub fn compare(target: &ContractFingerprint, candidate: &ContractFingerprint) -> Similarity {
+    let functions_matched = target
+        .functions
+        .iter()
+        .filter(|(_, function)| {
+            candidate
+                .functions
+                .iter()
+                .any(|(_, other)| function.similarity(other) >= FUNCTION_MATCH)
+        })
+        .count();
+
+    Similarity {
+        score: target.contract.similarity(&candidate.contract),
+        functions_matched,
+        functions_total: target.functions.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_bytecode;
+
+    #[test]
+    fn stable_under_shifted_pushes() {
+        // PUSH1 0x80 PUSH1 0x40 MSTORE PUSH2 0x0010 JUMP, then the same with other constants
+        let a = parse_bytecode(&hex::decode("60806040526100105600").unwrap());
+        let b = parse_bytecode(&hex::decode("60a06040526101205600").unwrap());
+        let c = parse_bytecode(&hex::decode("60806040016100105600").unwrap());
+
+        let a = Fingerprint::from_instructions(a.iter());
+        let b = Fingerprint::from_instructions(b.iter());
+        let c = Fingerprint::from_instructions(c.iter());
+
+        assert_eq!(a.similarity(&b), 1.0);
+        assert!(a.similarity(&c) < 0.5);
+    }
+}
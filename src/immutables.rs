@@ -0,0 +1,244 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    cfg::{AbstractValue, Cfg},
+    parser::Instruction,
+};
+
+// Solidity immutable. The runtime code carries zero PUSH32 placeholders which the
+// constructor overwrites in its in-memory copy of the runtime before returning it.
+#[derive(Debug, Serialize)]
+pub struct Immutable {
+    pub name: String,
+    pub offsets: Vec<usize>, // runtime offsets of the PUSH32 instructions
+    #[serde(serialize_with = "serialize_value")]
+    pub value: Option<[u8; 32]>, // read from deployed runtime code
+}
+
+impl fmt::Display for Immutable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        if let Some(value) = &self.value {
+            let significant: Vec<u8> = value.iter().copied().skip_while(|&b| b == 0).collect();
+            if significant.is_empty() {
+                write!(f, " = 0x0")?;
+            } else {
+                write!(f, " = 0x{}", hex::encode(significant))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn serialize_value<S: serde::Serializer>(
+    value: &Option<[u8; 32]>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.serialize_str(&format!("0x{}", hex::encode(value))),
+        None => serializer.serialize_none(),
+    }
+}
+
+// Constants are needed for memory offsets, everything else is told apart by the
+// instruction producing it so DUPs of a single value can be grouped. via-IR copies the
+// runtime to the free memory pointer, offsets from it are kept with the MLOAD producing it.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Value {
+    Const(usize),
+    Source(usize),
+    Offset(usize, usize), // source plus a constant
+    Unknown,
+}
+
+impl Value {
+    // memory address as the source it is relative to, if any, and a constant
+    fn address(&self) -> Option<(Option<usize>, usize)> {
+        match self {
+            Value::Const(address) => Some((None, *address)),
+            Value::Source(base) => Some((Some(*base), 0)),
+            Value::Offset(base, offset) => Some((Some(*base), *offset)),
+            Value::Unknown => None,
+        }
+    }
+}
+
+impl AbstractValue for Value {
+    fn unknown() -> Self {
+        Value::Unknown
+    }
+
+    fn jump_target(&self) -> Option<usize> {
+        match self {
+            Value::Const(target) => Some(*target),
+            _ => None,
+        }
+    }
+
+    fn apply(instruction: &Instruction, inputs: &[Self]) -> Self {
+        match (instruction.opcode, inputs) {
+            // PUSH0..PUSH32, wide constants are never offsets
+            (0x5F..=0x7F, _) if instruction.data.iter().skip_while(|&&b| b == 0).count() <= 8 => {
+                Value::Const(
+                    instruction
+                        .data
+                        .iter()
+                        .fold(0usize, |acc, &b| (acc << 8) | b as usize),
+                )
+            }
+            (0x01, [Value::Const(a), Value::Const(b)]) => a
+                .checked_add(*b)
+                .map(Value::Const)
+                .unwrap_or(Value::Unknown),
+            (
+                0x01,
+                [Value::Source(base), Value::Const(n)] | [Value::Const(n), Value::Source(base)],
+            ) => Value::Offset(*base, *n),
+            (
+                0x01,
+                [Value::Offset(base, a), Value::Const(b)]
+                | [Value::Const(b), Value::Offset(base, a)],
+            ) => a
+                .checked_add(*b)
+                .map(|offset| Value::Offset(*base, offset))
+                .unwrap_or(Value::Unknown),
+            _ => Value::Source(instruction.offset),
+        }
+    }
+}
+
+// Correlate constructor MSTOREs into the memory copy of the runtime with zero PUSH32
+// placeholders in the runtime. `runtime_offset` is where the runtime starts in the code.
+pub fn detect_immutables(
+    init: &[Instruction],
+    runtime_offset: usize,
+    runtime: &[Instruction],
+) -> Vec<Immutable> {
+    let placeholders: Vec<usize> = runtime
+        .iter()
+        .filter(|i| i.opcode == 0x7F && i.data.iter().all(|&b| b == 0))
+        .map(|i| i.offset)
+        .collect();
+    if placeholders.is_empty() {
+        return Vec::new();
+    }
+
+    // memory offset the runtime is copied to, and stores as (memory offset, value)
+    let mut bases = Vec::new();
+    let mut stores = Vec::new();
+    Cfg::from_instructions(init).walk_with::<Value>(0, |instruction, inputs| {
+        match (instruction.opcode, inputs) {
+            (0x39, [dest, Value::Const(offset), _]) if *offset == runtime_offset => {
+                bases.extend(dest.address());
+            }
+            (0x52, [address, value, ..]) => {
+                if let Some(address) = address.address() {
+                    stores.push((address, value.clone()));
+                }
+            }
+            _ => {}
+        }
+    });
+
+    // placeholder offsets grouped by the stored value, unknown values stay apart
+    let mut groups: Vec<(Value, Vec<usize>)> = Vec::new();
+    for ((root, address), value) in stores {
+        // the PUSH32 data starts one byte after the opcode
+        let Some(offset) = bases
+            .iter()
+            .filter(|(base_root, _)| *base_root == root)
+            .find_map(|(_, base)| address.checked_sub(base + 1))
+            .filter(|offset| placeholders.contains(offset))
+        else {
+            continue;
+        };
+
+        match groups
+            .iter_mut()
+            .find(|(v, _)| *v == value && value != Value::Unknown)
+        {
+            Some((_, offsets)) => offsets.push(offset),
+            None => groups.push((value, vec![offset])),
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = groups
+        .into_iter()
+        .map(|(_, mut offsets)| {
+            offsets.sort();
+            offsets.dedup();
+            offsets
+        })
+        .collect();
+    // numbered in runtime order, walking may store the same value more than once
+    groups.sort();
+    groups.dedup();
+
+    groups
+        .into_iter()
+        .enumerate()
+        .map(|(n, offsets)| Immutable {
+            name: format!("immutable_{}", n),
+            offsets,
+            value: None,
+        })
+        .collect()
+}
+
+// Fill in values from the deployed runtime code.
+pub fn resolve_immutables(immutables: &mut [Immutable], deployed: &[u8]) {
+    for immutable in immutables {
+        immutable.value = immutable
+            .offsets
+            .first()
+            .and_then(|offset| deployed.get(offset + 1..offset + 33))
+            .map(|data| data.try_into().unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_bytecode;
+
+    #[test]
+    fn constructor_patches_placeholders() {
+        // CALLER PUSH2 0x0043 PUSH1 0x18 PUSH0 CODECOPY
+        // PUSH0 DUP2 DUP2 PUSH1 0x01 ADD MSTORE PUSH1 0x22 ADD MSTORE
+        // PUSH1 0x43 PUSH0 RETURN INVALID
+        let init = parse_bytecode(
+            &hex::decode("3361004360185f395f8181600101526022015260435ff3fe").unwrap(),
+        );
+        let runtime = parse_bytecode(
+            &hex::decode(format!("7f{}7f{}00", "00".repeat(32), "00".repeat(32))).unwrap(),
+        );
+
+        let immutables = detect_immutables(&init, 0x18, &runtime);
+        assert_eq!(immutables.len(), 1);
+        assert_eq!(immutables[0].name, "immutable_0");
+        assert_eq!(immutables[0].offsets, vec![0x00, 0x21]);
+    }
+
+    #[test]
+    fn via_ir_copies_runtime_to_free_memory() {
+        // PUSH1 0x80 PUSH1 0x40 MSTORE PUSH1 0x40 MLOAD
+        // PUSH2 0x0043 PUSH1 0x20 DUP3 CODECOPY
+        // CALLER DUP1 DUP3 PUSH1 0x01 ADD MSTORE DUP2 PUSH1 0x22 ADD MSTORE
+        // PUSH1 0x43 SWAP1 RETURN INVALID
+        let init = parse_bytecode(
+            &hex::decode("608060405260405161004360208239338082600101528160220152604390f3fe")
+                .unwrap(),
+        );
+        let runtime = parse_bytecode(
+            &hex::decode(format!("7f{}7f{}00", "00".repeat(32), "00".repeat(32))).unwrap(),
+        );
+
+        let immutables = detect_immutables(&init, 0x20, &runtime);
+        assert_eq!(immutables.len(), 1);
+        assert_eq!(immutables[0].offsets, vec![0x00, 0x21]);
+    }
+}
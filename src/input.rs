@@ -0,0 +1,460 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{
+    loader::SectionKind,
+    source::{SourceFile, SourceMap, Sources},
+};
+
+// Bytecode to analyze together with whatever the compiler bundled with it.
+pub struct Input {
+    pub bytecode: Vec<u8>,
+    pub runtime: bool,                   // bytecode is runtime code
+    pub selectors: HashMap<u32, String>, // function and error selectors from the ABI
+    pub sources: Sources,                // source maps and files
+    pub storage: Vec<StorageVariable>,
+}
+
+impl Input {
+    // Bare bytecode without any compiler output.
+    pub fn from_bytecode(bytecode: Vec<u8>, runtime: bool) -> Self {
+        Input {
+            bytecode,
+            runtime,
+            selectors: HashMap::new(),
+            sources: Sources::default(),
+            storage: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StorageVariable {
+    pub label: String,
+    pub slot: usize,
+    pub offset: usize, // byte offset within the slot for packed variables
+    #[serde(rename = "type")]
+    pub type_name: String,
+}
+
+// Read a hex or raw binary file, or a compiler artifact: Foundry `out/*.json`, Hardhat
+// artifacts, solc `--standard-json` and `--combined-json` output. `-` reads from stdin.
+// Artifacts provide creation code unless `runtime` is requested or the contract has none
+// (abstract contracts, interfaces).
+pub fn read_input(path: &Path, runtime: bool, contract: Option<&str>) -> Result<Input, String> {
+    let content = if path == Path::new("-") {
+        let mut content = Vec::new();
+        io::stdin()
+            .read_to_end(&mut content)
+            .map_err(|e| format!("failed to read stdin: {}", e))?;
+        content
+    } else {
+        fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?
+    };
+
+    let mut input = parse_input(&content, runtime, contract)?;
+
+    // source paths are relative to the project root, which the artifact lives in
+    let mut roots = vec![PathBuf::from(".")];
+    if let Ok(path) = path.canonicalize() {
+        roots.extend(path.ancestors().skip(1).map(Path::to_path_buf));
+    }
+    input.sources.load_files(&roots);
+
+    Ok(input)
+}
+
+fn parse_input(content: &[u8], runtime: bool, contract: Option<&str>) -> Result<Input, String> {
+    let text = str::from_utf8(content).ok();
+
+    if let Some(text) = text
+        && text.trim_start().starts_with('{')
+    {
+        let json: Value =
+            serde_json::from_str(text).map_err(|e| format!("failed to parse JSON: {}", e))?;
+        return read_artifact(&json, runtime, contract);
+    }
+
+    // hex when nothing but hex digits is left, raw bytecode can be valid UTF-8 too
+    let hex = text.map(strip_whitespace).filter(|hex| {
+        let hex = hex.strip_prefix("0x").unwrap_or(hex);
+        unlink(hex).chars().all(|c| c.is_ascii_hexdigit())
+    });
+    let bytecode = match hex {
+        Some(hex) => decode_hex(&hex)?,
+        None => content.to_vec(),
+    };
+
+    Ok(Input::from_bytecode(bytecode, runtime))
+}
+
+// Hex split over lines or with spaces between bytes.
+fn strip_whitespace(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+fn decode_hex(content: &str) -> Result<Vec<u8>, String> {
+    let content = content.trim();
+    let content = content.strip_prefix("0x").unwrap_or(content);
+
+    hex::decode(unlink(content)).map_err(|e| format!("failed to parse hex: {}", e))
+}
+
+// Unlinked library references `__$<34 hex chars>$__` (or `__<name>___` in older solc) are
+// 20 byte placeholders, zero them so the rest of the code can be decoded.
+fn unlink(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+
+    while let Some(start) = rest.find("__") {
+        result.push_str(&rest[..start]);
+        if rest.len() < start + 40 {
+            rest = &rest[start..];
+            break;
+        }
+        result.push_str(&"0".repeat(40));
+        rest = &rest[start + 40..];
+    }
+    result.push_str(rest);
+
+    result
+}
+
+fn read_artifact(json: &Value, runtime: bool, contract: Option<&str>) -> Result<Input, String> {
+    let Some(contracts) = json.get("contracts").and_then(Value::as_object) else {
+        let mut input = from_artifact(json, runtime)?;
+
+        // Foundry, the contract's own file only
+        let path = json
+            .pointer("/ast/absolutePath")
+            .and_then(Value::as_str)
+            .or_else(|| compilation_target(json));
+        if let (Some(id), Some(path)) = (json["id"].as_u64(), path) {
+            add_file(&mut input.sources, id as usize, path);
+        }
+
+        return Ok(input);
+    };
+
+    // `--combined-json` keys contracts by `path:Name`, `--standard-json` by path then name
+    let combined = json.get("sourceList").is_some();
+    let mut candidates: Vec<(&str, &Value)> = if combined {
+        contracts
+            .iter()
+            .map(|(key, c)| (key.rsplit(':').next().unwrap_or(key), c))
+            .collect()
+    } else {
+        contracts
+            .values()
+            .filter_map(Value::as_object)
+            .flatten()
+            .map(|(name, c)| (name.as_str(), c))
+            .collect()
+    };
+    candidates.retain(|(name, _)| contract.is_none_or(|contract| contract == *name));
+
+    if contract.is_none() {
+        // skip interfaces and abstract contracts when there is no choice to make
+        let deployable: Vec<_> = candidates
+            .iter()
+            .copied()
+            .filter(|(_, c)| !object(creation_code(c)).is_empty())
+            .collect();
+        if !deployable.is_empty() {
+            candidates = deployable;
+        }
+    }
+
+    let mut input = match candidates.as_slice() {
+        [(_, artifact)] => from_artifact(artifact, runtime)?,
+        [] => {
+            return Err(match contract {
+                Some(name) => format!("contract {} not found", name),
+                None => "no contracts found".to_string(),
+            });
+        }
+        _ => {
+            let names: Vec<&str> = candidates.iter().map(|(name, _)| *name).collect();
+            return Err(format!(
+                "multiple contracts found, choose one with --contract: {}",
+                names.join(", ")
+            ));
+        }
+    };
+
+    if let Some(list) = json["sourceList"].as_array() {
+        for (id, path) in list.iter().enumerate() {
+            if let Some(path) = path.as_str() {
+                add_file(&mut input.sources, id, path);
+            }
+        }
+    }
+    if let Some(sources) = json["sources"].as_object() {
+        for (path, source) in sources {
+            if let Some(id) = source["id"].as_u64() {
+                add_file(&mut input.sources, id as usize, path);
+            }
+        }
+    }
+
+    Ok(input)
+}
+
+fn add_file(sources: &mut Sources, id: usize, path: &str) {
+    sources.files.insert(
+        id,
+        SourceFile {
+            path: path.to_string(),
+            content: None,
+        },
+    );
+}
+
+// Path of the compiled file from the metadata Foundry bundles as an object.
+fn compilation_target(artifact: &Value) -> Option<&str> {
+    let target = artifact.pointer("/metadata/settings/compilationTarget")?;
+    target.as_object()?.keys().next().map(String::as_str)
+}
+
+fn creation_code(artifact: &Value) -> Option<&Value> {
+    artifact
+        .get("bytecode")
+        .or_else(|| artifact.pointer("/evm/bytecode"))
+        .or_else(|| artifact.get("bin"))
+}
+
+fn runtime_code(artifact: &Value) -> Option<&Value> {
+    artifact
+        .get("deployedBytecode")
+        .or_else(|| artifact.pointer("/evm/deployedBytecode"))
+        .or_else(|| artifact.get("bin-runtime"))
+}
+
+// Single contract: Foundry and Hardhat artifacts, or a solc contract entry.
+fn from_artifact(artifact: &Value, runtime: bool) -> Result<Input, String> {
+    let creation = creation_code(artifact);
+    let deployed = runtime_code(artifact);
+
+    let runtime = runtime || object(creation).is_empty();
+    let hex = object(if runtime { deployed } else { creation });
+    if hex.is_empty() {
+        return Err("artifact contains no bytecode".to_string());
+    }
+
+    // solc and Foundry nest the map with the code, `--combined-json` keeps it aside
+    let source_map = |code: Option<&Value>, key: &str| {
+        code.and_then(|code| code.get("sourceMap"))
+            .or_else(|| artifact.get(key))
+            .and_then(Value::as_str)
+            .filter(|map| !map.is_empty())
+            .map(SourceMap::parse)
+    };
+    let mut sources = Sources::default();
+    if !runtime && let Some(map) = source_map(creation, "srcmap") {
+        sources.maps.push((SectionKind::Init, map));
+    }
+    if let Some(map) = source_map(deployed, "srcmap-runtime") {
+        sources.maps.push((SectionKind::Runtime, map));
+    }
+
+    // `--combined-json` with older solc stores the ABI as a JSON string
+    let abi = match artifact.get("abi") {
+        Some(Value::String(abi)) => serde_json::from_str(abi).ok(),
+        abi => abi.cloned(),
+    };
+    let selectors = abi
+        .as_ref()
+        .and_then(Value::as_array)
+        .map(|abi| abi_selectors(abi))
+        .unwrap_or_default();
+
+    let storage = artifact
+        .get("storageLayout")
+        .map(storage_layout)
+        .unwrap_or_default();
+
+    Ok(Input {
+        bytecode: decode_hex(hex)?,
+        runtime,
+        selectors,
+        sources,
+        storage,
+    })
+}
+
+// Hex string of `{"object": "..."}` or of a plain string.
+fn object(value: Option<&Value>) -> &str {
+    let hex = match value {
+        Some(Value::String(hex)) => hex,
+        Some(value) => value.get("object").and_then(Value::as_str).unwrap_or(""),
+        None => "",
+    };
+    hex.strip_prefix("0x").unwrap_or(hex)
+}
+
+fn abi_selectors(abi: &[Value]) -> HashMap<u32, String> {
+    abi.iter()
+        .filter(|item| matches!(item["type"].as_str(), Some("function" | "error")))
+        .filter_map(|item| {
+            let name = item["name"].as_str()?;
+            let inputs: Vec<String> = item["inputs"]
+                .as_array()
+                .map(|inputs| inputs.iter().map(canonical_type).collect())
+                .unwrap_or_default();
+            let signature = format!("{}({})", name, inputs.join(","));
+
+            Some((selector(&signature), signature))
+        })
+        .collect()
+}
+
+// `tuple[]` with components becomes `(address,uint256)[]`.
+fn canonical_type(param: &Value) -> String {
+    let kind = param["type"].as_str().unwrap_or_default();
+
+    match kind.strip_prefix("tuple") {
+        Some(suffix) => {
+            let components: Vec<String> = param["components"]
+                .as_array()
+                .map(|components| components.iter().map(canonical_type).collect())
+                .unwrap_or_default();
+            format!("({}){}", components.join(","), suffix)
+        }
+        None => kind.to_string(),
+    }
+}
+
+pub fn selector(signature: &str) -> u32 {
+    let mut hash = [0u8; 32];
+    let mut keccak = Keccak::v256();
+    keccak.update(signature.as_bytes());
+    keccak.finalize(&mut hash);
+
+    u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]])
+}
+
+fn storage_layout(layout: &Value) -> Vec<StorageVariable> {
+    let types = &layout["types"];
+
+    layout["storage"]
+        .as_array()
+        .map(|storage| {
+            storage
+                .iter()
+                .filter_map(|entry| {
+                    let type_id = entry["type"].as_str().unwrap_or_default();
+                    Some(StorageVariable {
+                        label: entry["label"].as_str()?.to_string(),
+                        slot: entry["slot"].as_str()?.parse().ok()?,
+                        offset: entry["offset"].as_u64().unwrap_or(0) as usize,
+                        type_name: types[type_id]["label"]
+                            .as_str()
+                            .unwrap_or(type_id)
+                            .to_string(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abi_selectors_with_tuples() {
+        let abi: Value = serde_json::from_str(
+            r#"[
+                {"type": "function", "name": "transfer", "inputs": [
+                    {"name": "to", "type": "address"}, {"name": "amount", "type": "uint256"}
+                ]},
+                {"type": "function", "name": "swap", "inputs": [
+                    {"name": "legs", "type": "tuple[]", "components": [
+                        {"name": "pool", "type": "address"}, {"name": "amount", "type": "uint256"}
+                    ]},
+                    {"name": "data", "type": "bytes"}
+                ]},
+                {"type": "event", "name": "Transfer", "inputs": []}
+            ]"#,
+        )
+        .unwrap();
+
+        let selectors = abi_selectors(abi.as_array().unwrap());
+        assert_eq!(selectors.len(), 2);
+        assert_eq!(selectors[&0xa9059cbb], "transfer(address,uint256)");
+        assert_eq!(
+            selectors[&selector("swap((address,uint256)[],bytes)")],
+            "swap((address,uint256)[],bytes)"
+        );
+    }
+
+    #[test]
+    fn standard_json_and_hardhat_artifacts() {
+        let output: Value = serde_json::from_str(
+            r#"{"contracts": {"src/Token.sol": {
+                "IToken": {"abi": [], "evm": {"bytecode": {"object": ""}, "deployedBytecode": {"object": ""}}},
+                "Token": {"abi": [], "evm": {
+                    "bytecode": {"object": "6080", "sourceMap": "0:1:0:-:0"},
+                    "deployedBytecode": {"object": "6001", "sourceMap": "1:1:0:-:0"}
+                }}
+            }}}"#,
+        )
+        .unwrap();
+
+        let input = read_artifact(&output, false, None).unwrap();
+        assert_eq!(input.bytecode, vec![0x60, 0x80]);
+        assert!(!input.runtime);
+        assert_eq!(input.sources.maps.len(), 2);
+
+        let input = read_artifact(&output, true, Some("Token")).unwrap();
+        assert_eq!(input.bytecode, vec![0x60, 0x01]);
+        assert!(input.runtime);
+
+        assert!(read_artifact(&output, false, Some("Missing")).is_err());
+
+        // no creation code, fall back to runtime
+        let hardhat: Value = serde_json::from_str(
+            r#"{"_format": "hh-sol-artifact-1", "abi": [], "bytecode": "0x", "deployedBytecode": "0x6001"}"#,
+        )
+        .unwrap();
+        let input = read_artifact(&hardhat, false, None).unwrap();
+        assert_eq!(input.bytecode, vec![0x60, 0x01]);
+        assert!(input.runtime);
+    }
+
+    #[test]
+    fn hex_with_whitespace_and_raw_binary() {
+        let input = parse_input(b"0x6080\n6040 52\r\n", false, None).unwrap();
+        assert_eq!(input.bytecode, vec![0x60, 0x80, 0x60, 0x40, 0x52]);
+
+        let input = parse_input(&[0x60, 0x80, 0x60, 0x40, 0x52], false, None).unwrap();
+        assert_eq!(input.bytecode, vec![0x60, 0x80, 0x60, 0x40, 0x52]);
+
+        assert!(parse_input(b"0x608", false, None).is_err());
+        // raw bytecode below 0x80 is valid UTF-8 but not hex
+        let input = parse_input(&[0x60, 0x01, 0x60, 0x00, 0x55, 0x00], false, None).unwrap();
+        assert_eq!(input.bytecode, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00]);
+        let input = parse_input(b"not bytecode\n", false, None).unwrap();
+        assert_eq!(input.bytecode, b"not bytecode\n".to_vec());
+    }
+
+    #[test]
+    fn unlinked_library_placeholders() {
+        let code = format!("73{}5af4", "__$d7b3a5d8b4a0f1e2c3b4a5d6e7f8091a2b$__");
+        assert_eq!(decode_hex(&code).unwrap(), {
+            let mut expected = vec![0x73];
+            expected.extend([0u8; 20]);
+            expected.extend([0x5a, 0xf4]);
+            expected
+        });
+    }
+}
@@ -0,0 +1,315 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::loader::{Program, SectionKind};
+use crate::parser::Instruction;
+
+pub struct Interface {
+    pub name: &'static str,
+    // base interface an extension is only reported together with
+    pub extends: Option<&'static str>,
+    pub members: &'static [(u32, &'static str)],
+}
+
+impl Interface {
+    // ERC-165 interface id, XOR of all member selectors.
+    pub fn id(&self) -> u32 {
+        self.members
+            .iter()
+            .fold(0, |id, (selector, _)| id ^ selector)
+    }
+}
+
+pub const INTERFACES: &[Interface] = &[
+    Interface {
+        name: "ERC-20",
+        extends: None,
+        members: &[
+            (0x18160ddd, "totalSupply()"),
+            (0x70a08231, "balanceOf(address)"),
+            (0xa9059cbb, "transfer(address,uint256)"),
+            (0x23b872dd, "transferFrom(address,address,uint256)"),
+            (0x095ea7b3, "approve(address,uint256)"),
+            (0xdd62ed3e, "allowance(address,address)"),
+        ],
+    },
+    Interface {
+        name: "ERC-20 metadata",
+        extends: Some("ERC-20"),
+        members: &[
+            (0x06fdde03, "name()"),
+            (0x95d89b41, "symbol()"),
+            (0x313ce567, "decimals()"),
+        ],
+    },
+    Interface {
+        name: "ERC-2612 permit",
+        extends: None,
+        members: &[
+            (
+                0xd505accf,
+                "permit(address,address,uint256,uint256,uint8,bytes32,bytes32)",
+            ),
+            (0x7ecebe00, "nonces(address)"),
+            (0x3644e515, "DOMAIN_SEPARATOR()"),
+        ],
+    },
+    Interface {
+        name: "ERC-165",
+        extends: None,
+        members: &[(0x01ffc9a7, "supportsInterface(bytes4)")],
+    },
+    Interface {
+        name: "ERC-721",
+        extends: None,
+        members: &[
+            (0x70a08231, "balanceOf(address)"),
+            (0x6352211e, "ownerOf(uint256)"),
+            (0x42842e0e, "safeTransferFrom(address,address,uint256)"),
+            (
+                0xb88d4fde,
+                "safeTransferFrom(address,address,uint256,bytes)",
+            ),
+            (0x23b872dd, "transferFrom(address,address,uint256)"),
+            (0x095ea7b3, "approve(address,uint256)"),
+            (0xa22cb465, "setApprovalForAll(address,bool)"),
+            (0x081812fc, "getApproved(uint256)"),
+            (0xe985e9c5, "isApprovedForAll(address,address)"),
+        ],
+    },
+    Interface {
+        name: "ERC-721 metadata",
+        extends: Some("ERC-721"),
+        members: &[
+            (0x06fdde03, "name()"),
+            (0x95d89b41, "symbol()"),
+            (0xc87b56dd, "tokenURI(uint256)"),
+        ],
+    },
+    Interface {
+        name: "ERC-721 enumerable",
+        extends: Some("ERC-721"),
+        members: &[
+            (0x18160ddd, "totalSupply()"),
+            (0x2f745c59, "tokenOfOwnerByIndex(address,uint256)"),
+            (0x4f6ccce7, "tokenByIndex(uint256)"),
+        ],
+    },
+    Interface {
+        name: "ERC-1155",
+        extends: None,
+        members: &[
+            (
+                0xf242432a,
+                "safeTransferFrom(address,address,uint256,uint256,bytes)",
+            ),
+            (
+                0x2eb2c2d6,
+                "safeBatchTransferFrom(address,address,uint256[],uint256[],bytes)",
+            ),
+            (0x00fdd58e, "balanceOf(address,uint256)"),
+            (0x4e1273f4, "balanceOfBatch(address[],uint256[])"),
+            (0xa22cb465, "setApprovalForAll(address,bool)"),
+            (0xe985e9c5, "isApprovedForAll(address,address)"),
+        ],
+    },
+    Interface {
+        name: "ERC-1155 metadata URI",
+        extends: Some("ERC-1155"),
+        members: &[(0x0e89341c, "uri(uint256)")],
+    },
+    Interface {
+        name: "ERC-4626",
+        extends: Some("ERC-20"),
+        members: &[
+            (0x38d52e0f, "asset()"),
+            (0x01e1d114, "totalAssets()"),
+            (0xc6e6f592, "convertToShares(uint256)"),
+            (0x07a2d13a, "convertToAssets(uint256)"),
+            (0x402d267d, "maxDeposit(address)"),
+            (0xef8b30f7, "previewDeposit(uint256)"),
+            (0x6e553f65, "deposit(uint256,address)"),
+            (0xc63d75b6, "maxMint(address)"),
+            (0xb3d7f6b9, "previewMint(uint256)"),
+            (0x94bf804d, "mint(uint256,address)"),
+            (0xce96cb77, "maxWithdraw(address)"),
+            (0x0a28a477, "previewWithdraw(uint256)"),
+            (0xb460af94, "withdraw(uint256,address,address)"),
+            (0xd905777e, "maxRedeem(address)"),
+            (0x4cdad506, "previewRedeem(uint256)"),
+            (0xba087652, "redeem(uint256,address,address)"),
+        ],
+    },
+];
+
+#[derive(Serialize)]
+pub struct InterfaceMatch {
+    pub name: &'static str,
+    pub complete: bool,
+    pub missing: Vec<&'static str>,
+    // interface id pushed in the code, as done by `supportsInterface` implementations
+    pub advertised: bool,
+}
+
+impl fmt::Display for InterfaceMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.complete {
+            write!(f, "{} (complete", self.name)?;
+        } else {
+            write!(
+                f,
+                "{} (partial, missing {}",
+                self.name,
+                self.missing.join(", ")
+            )?;
+        }
+
+        if self.advertised {
+            write!(f, ", advertised")?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+// Match the program against well-known interfaces. An interface is reported when at
+// least half of its members are implemented or when its id is pushed in the runtime code.
+// Extensions such as ERC-721 metadata are only reported along with their base interface,
+// and a partial match is dropped when its members are better covered by other interfaces,
+// so an ERC-721 isn't also a partial ERC-20 for sharing balanceOf, approve and transferFrom.
+pub fn detect_interfaces(program: &Program) -> Vec<InterfaceMatch> {
+    let selectors: Vec<u32> = program
+        .entrypoints
+        .iter()
+        .map(|entry| u32::from_be_bytes(entry.selector))
+        .collect();
+
+    let constants: Vec<u32> = program
+        .sections
+        .iter()
+        .filter(|section| matches!(section.kind, SectionKind::Runtime))
+        .filter_map(|section| section.instructions.as_ref())
+        .flat_map(|instructions| pushed_ids(instructions))
+        .collect();
+
+    let present: Vec<Vec<u32>> = INTERFACES
+        .iter()
+        .map(|interface| {
+            interface
+                .members
+                .iter()
+                .map(|(selector, _)| *selector)
+                .filter(|selector| selectors.contains(selector))
+                .collect()
+        })
+        .collect();
+
+    let mut matches: Vec<InterfaceMatch> = Vec::new();
+
+    for (index, interface) in INTERFACES.iter().enumerate() {
+        let missing: Vec<&'static str> = interface
+            .members
+            .iter()
+            .filter(|(selector, _)| !selectors.contains(selector))
+            .map(|(_, signature)| *signature)
+            .collect();
+        let count = present[index].len();
+
+        // single member interfaces are advertised by their only selector, don't count it twice
+        let advertised = interface.members.len() > 1 && constants.contains(&interface.id());
+
+        if count * 2 < interface.members.len() && !advertised {
+            continue;
+        }
+
+        // every implemented member belongs to an interface implemented to a larger extent
+        let covers = |other: usize| {
+            let size = INTERFACES[other].members.len();
+            other != index
+                && present[other].len() * 2 >= size
+                && present[other].len() * interface.members.len() > count * size
+        };
+        if !missing.is_empty()
+            && !advertised
+            && present[index].iter().all(|selector| {
+                (0..INTERFACES.len())
+                    .any(|other| covers(other) && present[other].contains(selector))
+            })
+        {
+            continue;
+        }
+        if let Some(base) = interface.extends
+            && !advertised
+            && !matches.iter().any(|m| m.name == base)
+        {
+            continue;
+        }
+
+        matches.push(InterfaceMatch {
+            name: interface.name,
+            complete: missing.is_empty(),
+            missing,
+            advertised,
+        });
+    }
+
+    matches
+}
+
+// Constants that may be interface ids: PUSH4 values, narrower pushes shifted into the top
+// bytes with `PUSH1 0xe0 SHL`, and ids pushed already left-aligned in a full word.
+fn pushed_ids(instructions: &[Instruction]) -> Vec<u32> {
+    let mut ids = Vec::new();
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        let data = &instruction.data;
+        if data.is_empty() {
+            continue;
+        }
+
+        let shifted = matches!(
+            instructions.get(index + 1..index + 3),
+            Some([push, shl]) if push.opcode == 0x60 && push.data == [0xe0] && shl.opcode == 0x1b
+        );
+        if data.len() == 4 || (data.len() < 4 && shifted) {
+            let mut word = [0u8; 4];
+            word[4 - data.len()..].copy_from_slice(data);
+            ids.push(u32::from_be_bytes(word));
+        } else if data.len() > 28 && data[data.len() - 28..].iter().all(|&byte| byte == 0) {
+            let mut word = [0u8; 4];
+            word[32 - data.len()..].copy_from_slice(&data[..data.len() - 28]);
+            ids.push(u32::from_be_bytes(word));
+        }
+    }
+
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interface_ids() {
+        let id = |name: &str| INTERFACES.iter().find(|i| i.name == name).unwrap().id();
+
+        assert_eq!(id("ERC-165"), 0x01ffc9a7);
+        assert_eq!(id("ERC-721"), 0x80ac58cd);
+        assert_eq!(id("ERC-721 metadata"), 0x5b5e139f);
+        assert_eq!(id("ERC-721 enumerable"), 0x780e9d63);
+        assert_eq!(id("ERC-1155"), 0xd9b67a26);
+        assert_eq!(id("ERC-1155 metadata URI"), 0x0e89341c);
+    }
+
+    #[test]
+    fn shifted_and_aligned_ids() {
+        // PUSH3 0xfdd58e PUSH1 0xe0 SHL, PUSH31 0x00fdd58e << 224, PUSH2 0x1234 PUSH1 0xe0 SHR
+        let mut code = hex::decode("62fdd58e60e01b7efdd58e").unwrap();
+        code.extend([0; 28]);
+        code.extend(hex::decode("61123460e01c").unwrap());
+
+        let ids = pushed_ids(&crate::parser::parse_bytecode(&code));
+        assert_eq!(ids, vec![0x00fdd58e, 0x00fdd58e]);
+    }
+}
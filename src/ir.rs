@@ -0,0 +1,631 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    cfg::Cfg,
+    emulator::evaluate,
+    parser::{self, Instruction},
+    u256::U256,
+};
+
+// Predecessors followed at once to find a stack item, deeper ones are taken as inputs.
+const MAX_NESTING: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Value(usize), // result of a statement, phi or block input
+    Const(U256),
+}
+
+// Instruction with its stack operands named, PUSH, DUPn, SWAPn, POP and JUMPDEST have
+// no statement since they only move values around.
+pub struct Statement {
+    pub offset: usize,
+    pub opcode: u8,
+    pub result: Option<usize>,
+    pub operands: Vec<Operand>, // top of the stack first
+}
+
+// Value of a stack item at a block join, by predecessor block.
+pub struct Phi {
+    pub result: usize,
+    pub incoming: Vec<(usize, Operand)>,
+}
+
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    // stack items below the block entry that no predecessor pushes, by depth
+    pub inputs: Vec<(usize, usize)>,
+    pub phis: Vec<Phi>,
+    pub statements: Vec<Statement>,
+    pub successors: BTreeSet<usize>,
+}
+
+// Stack-free form of the code: every stack item is a value defined once, by a statement,
+// a phi at a block join, or as an input of a block entered with an unknown stack.
+// Instructions with constant operands are folded.
+pub struct Ir {
+    pub blocks: BTreeMap<usize, Block>,
+}
+
+// Stack item while lifting a block, entry items are resolved once all blocks are lifted.
+#[derive(Clone, Copy, PartialEq)]
+enum Local {
+    Operand(Operand),
+    Entry(usize), // depth below the block entry
+}
+
+struct Lifted {
+    statements: Vec<(usize, u8, Option<usize>, Vec<Local>)>,
+    exit: Vec<Local>, // top first, then entry items from `consumed` on
+    consumed: usize,
+}
+
+impl Lifted {
+    // Item at `depth` on the stack when the block is left.
+    fn exit(&self, depth: usize) -> Local {
+        match self.exit.get(depth) {
+            Some(local) => *local,
+            None => Local::Entry(self.consumed + depth - self.exit.len()),
+        }
+    }
+}
+
+impl Ir {
+    pub fn from_instructions(instructions: &[Instruction]) -> Self {
+        let cfg = Cfg::from_instructions(instructions);
+        let mut next = 0;
+        let lifted: BTreeMap<usize, Lifted> = cfg
+            .blocks
+            .iter()
+            .map(|(&start, block)| (start, lift_block(&block.instructions, &mut next)))
+            .collect();
+
+        let mut resolver = Resolver {
+            cfg: &cfg,
+            reachable: reachable(&cfg),
+            lifted: &lifted,
+            next,
+            resolved: HashMap::new(),
+            following: HashSet::new(),
+            phis: BTreeMap::new(),
+            inputs: BTreeMap::new(),
+        };
+
+        let mut blocks = BTreeMap::new();
+        for (&start, block) in &lifted {
+            let statements = block
+                .statements
+                .iter()
+                .map(|(offset, opcode, result, operands)| Statement {
+                    offset: *offset,
+                    opcode: *opcode,
+                    result: *result,
+                    operands: operands
+                        .iter()
+                        .map(|local| resolver.local(start, *local))
+                        .collect(),
+                })
+                .collect();
+            let cfg_block = &cfg.blocks[&start];
+            blocks.insert(
+                start,
+                Block {
+                    start,
+                    end: cfg_block.end,
+                    inputs: Vec::new(),
+                    phis: Vec::new(),
+                    statements,
+                    successors: cfg_block.successors.clone(),
+                },
+            );
+        }
+
+        for (start, phis) in std::mem::take(&mut resolver.phis) {
+            blocks.get_mut(&start).expect("phi of a lifted block").phis = phis;
+        }
+        for (start, inputs) in std::mem::take(&mut resolver.inputs) {
+            blocks
+                .get_mut(&start)
+                .expect("input of a lifted block")
+                .inputs = inputs;
+        }
+
+        let mut ir = Ir { blocks };
+        ir.simplify();
+        ir.renumber();
+        ir
+    }
+
+    // Statement or phi defining each value.
+    pub fn definitions(&self) -> HashMap<usize, Definition<'_>> {
+        let mut definitions = HashMap::new();
+        for block in self.blocks.values() {
+            for &(value, depth) in &block.inputs {
+                definitions.insert(value, Definition::Input(block.start, depth));
+            }
+            for phi in &block.phis {
+                definitions.insert(phi.result, Definition::Phi(phi));
+            }
+            for statement in &block.statements {
+                if let Some(value) = statement.result {
+                    definitions.insert(value, Definition::Statement(statement));
+                }
+            }
+        }
+        definitions
+    }
+
+    pub fn statements(&self) -> impl Iterator<Item = &Statement> {
+        self.blocks.values().flat_map(|block| &block.statements)
+    }
+
+    // Removes phis joining a single value and folds the statements left with constant
+    // operands, until neither applies.
+    fn simplify(&mut self) {
+        loop {
+            let mut replaced: HashMap<usize, Operand> = HashMap::new();
+
+            for block in self.blocks.values_mut() {
+                block.phis.retain(|phi| {
+                    let mut incoming = phi
+                        .incoming
+                        .iter()
+                        .map(|(_, operand)| *operand)
+                        .filter(|operand| *operand != Operand::Value(phi.result));
+                    let Some(first) = incoming.next() else {
+                        return true;
+                    };
+                    if incoming.all(|operand| operand == first) {
+                        replaced.insert(phi.result, first);
+                        return false;
+                    }
+                    true
+                });
+
+                block.statements.retain(|statement| {
+                    let (Some(result), true) = (statement.result, foldable(statement.opcode))
+                    else {
+                        return true;
+                    };
+                    let value = statement
+                        .operands
+                        .iter()
+                        .map(|operand| match operand {
+                            Operand::Const(value) => Some(*value),
+                            Operand::Value(_) => None,
+                        })
+                        .collect::<Option<Vec<U256>>>()
+                        .and_then(|inputs| evaluate(statement.opcode, &inputs));
+                    match value {
+                        Some(value) => {
+                            replaced.insert(result, Operand::Const(value));
+                            false
+                        }
+                        None => true,
+                    }
+                });
+            }
+
+            if replaced.is_empty() {
+                return;
+            }
+
+            let substitute = |operand: &mut Operand| {
+                while let Operand::Value(value) = operand
+                    && let Some(replacement) = replaced.get(value)
+                {
+                    *operand = *replacement;
+                }
+            };
+            for block in self.blocks.values_mut() {
+                for phi in &mut block.phis {
+                    phi.incoming
+                        .iter_mut()
+                        .for_each(|(_, operand)| substitute(operand));
+                }
+                for statement in &mut block.statements {
+                    statement.operands.iter_mut().for_each(substitute);
+                }
+            }
+        }
+    }
+
+    // Numbers values in the order they are defined in the listing.
+    fn renumber(&mut self) {
+        let mut numbers = HashMap::new();
+        for block in self.blocks.values() {
+            let defined = block
+                .inputs
+                .iter()
+                .map(|(value, _)| *value)
+                .chain(block.phis.iter().map(|phi| phi.result))
+                .chain(block.statements.iter().filter_map(|s| s.result));
+            for value in defined {
+                let number = numbers.len();
+                numbers.insert(value, number);
+            }
+        }
+
+        let number = |operand: &mut Operand| {
+            if let Operand::Value(value) = operand {
+                *value = numbers[value];
+            }
+        };
+        for block in self.blocks.values_mut() {
+            for (value, _) in &mut block.inputs {
+                *value = numbers[value];
+            }
+            for phi in &mut block.phis {
+                phi.result = numbers[&phi.result];
+                phi.incoming
+                    .iter_mut()
+                    .for_each(|(_, operand)| number(operand));
+            }
+            for statement in &mut block.statements {
+                statement.result = statement.result.map(|value| numbers[&value]);
+                statement.operands.iter_mut().for_each(number);
+            }
+        }
+    }
+}
+
+pub enum Definition<'a> {
+    Input(usize, usize), // block and depth below its entry
+    Phi(&'a Phi),
+    Statement(&'a Statement),
+}
+
+// Statements `operand` is computed from, following phis and any other statement down to
+// what produced their inputs: loads, environment reads and calls.
+pub fn origins<'a>(
+    operand: Operand,
+    definitions: &HashMap<usize, Definition<'a>>,
+) -> Vec<&'a Statement> {
+    let mut origins = Vec::new();
+    let mut visited = BTreeSet::new();
+    let mut queue = vec![operand];
+
+    while let Some(operand) = queue.pop() {
+        let Operand::Value(value) = operand else {
+            continue;
+        };
+        if !visited.insert(value) {
+            continue;
+        }
+
+        match definitions.get(&value) {
+            Some(Definition::Phi(phi)) => {
+                queue.extend(phi.incoming.iter().map(|(_, operand)| *operand));
+            }
+            Some(Definition::Statement(statement)) if source(statement.opcode) => {
+                origins.push(*statement)
+            }
+            Some(Definition::Statement(statement)) => {
+                queue.extend(statement.operands.iter().copied());
+            }
+            Some(Definition::Input(..)) | None => {}
+        }
+    }
+
+    origins
+}
+
+// Opcodes whose result doesn't come from their operands: environment, MLOAD, SLOAD and
+// calls.
+fn source(opcode: u8) -> bool {
+    matches!(opcode, 0x30..=0x48 | 0x51 | 0x54 | 0x58..=0x5A | 0xF0..=0xFA)
+}
+
+// Opcodes evaluated when all their operands are constants.
+fn foldable(opcode: u8) -> bool {
+    matches!(opcode, 0x01..=0x0B | 0x10..=0x1D)
+}
+
+// Blocks reachable from the start of the code.
+fn reachable(cfg: &Cfg) -> BTreeSet<usize> {
+    let mut reachable = BTreeSet::new();
+    let mut queue = vec![0];
+    while let Some(start) = queue.pop() {
+        if let Some(block) = cfg.blocks.get(&start)
+            && reachable.insert(start)
+        {
+            queue.extend(block.successors.iter().copied());
+        }
+    }
+    reachable
+}
+
+fn lift_block(instructions: &[Instruction], next: &mut usize) -> Lifted {
+    let mut stack: Vec<Local> = Vec::new(); // top last
+    let mut consumed = 0;
+    let mut statements = Vec::new();
+
+    // items below what the block pushed come from its entry stack
+    let mut ensure = |stack: &mut Vec<Local>, depth: usize| {
+        while stack.len() < depth {
+            stack.insert(0, Local::Entry(consumed));
+            consumed += 1;
+        }
+    };
+
+    for instruction in instructions {
+        let opcode = instruction.opcode;
+        match opcode {
+            // PUSH0..PUSH32
+            0x5F..=0x7F => {
+                let value = U256::from_be_bytes(&instruction.data);
+                stack.push(Local::Operand(Operand::Const(value)));
+            }
+            // DUP1..DUP16
+            0x80..=0x8F => {
+                let n = (opcode - 0x7F) as usize;
+                ensure(&mut stack, n);
+                stack.push(stack[stack.len() - n]);
+            }
+            // SWAP1..SWAP16
+            0x90..=0x9F => {
+                let n = (opcode - 0x8F) as usize;
+                ensure(&mut stack, n + 1);
+                let len = stack.len();
+                stack.swap(len - 1, len - 1 - n);
+            }
+            // POP
+            0x50 => {
+                ensure(&mut stack, 1);
+                stack.pop();
+            }
+            0x5B => {}
+            _ => {
+                let (pops, pushes) = parser::stack_io(opcode);
+                ensure(&mut stack, pops);
+                let operands: Vec<Local> = (0..pops).map(|_| stack.pop().unwrap()).collect();
+
+                let constants: Option<Vec<U256>> = operands
+                    .iter()
+                    .map(|local| match local {
+                        Local::Operand(Operand::Const(value)) => Some(*value),
+                        _ => None,
+                    })
+                    .collect();
+                if let Some(inputs) = &constants
+                    && let Some(value) = evaluate(opcode, inputs)
+                {
+                    stack.push(Local::Operand(Operand::Const(value)));
+                    continue;
+                }
+
+                let result = (pushes == 1).then(|| {
+                    *next += 1;
+                    *next - 1
+                });
+                if let Some(result) = result {
+                    stack.push(Local::Operand(Operand::Value(result)));
+                }
+                statements.push((instruction.offset, opcode, result, operands));
+            }
+        }
+    }
+
+    stack.reverse();
+    Lifted {
+        statements,
+        exit: stack,
+        consumed,
+    }
+}
+
+// Resolves entry stack items of blocks to the values their predecessors leave, with phis
+// where several predecessors join.
+struct Resolver<'a> {
+    cfg: &'a Cfg,
+    reachable: BTreeSet<usize>,
+    lifted: &'a BTreeMap<usize, Lifted>,
+    next: usize,
+    resolved: HashMap<(usize, usize), Operand>,
+    following: HashSet<(usize, usize)>, // stack items being resolved
+    phis: BTreeMap<usize, Vec<Phi>>,
+    inputs: BTreeMap<usize, Vec<(usize, usize)>>,
+}
+
+impl Resolver<'_> {
+    fn local(&mut self, block: usize, local: Local) -> Operand {
+        match local {
+            Local::Operand(operand) => operand,
+            Local::Entry(depth) => self.entry(block, depth),
+        }
+    }
+
+    // Stack item at `depth` when entering `block`.
+    fn entry(&mut self, block: usize, depth: usize) -> Operand {
+        if let Some(operand) = self.resolved.get(&(block, depth)) {
+            return *operand;
+        }
+
+        // code that is never run leaves nothing on the stack of reachable blocks
+        let predecessors: Vec<usize> = self.cfg.blocks[&block]
+            .predecessors
+            .iter()
+            .filter(|predecessor| self.reachable.contains(predecessor))
+            .copied()
+            .collect();
+
+        match predecessors.as_slice() {
+            // blocks that are each other's only predecessor, or that pop more than they push
+            // on every way back, nothing enters them with a value
+            _ if self.following.contains(&(block, depth))
+                || self.following.len() >= MAX_NESTING =>
+            {
+                self.input(block, depth)
+            }
+            [] => self.input(block, depth),
+            [predecessor] if *predecessor != block => {
+                self.following.insert((block, depth));
+                let local = self.lifted[predecessor].exit(depth);
+                let operand = self.local(*predecessor, local);
+                self.following.remove(&(block, depth));
+                self.resolved.insert((block, depth), operand);
+                operand
+            }
+            _ => {
+                // registered before the incoming values, loops come back to it
+                let value = self.value();
+                self.resolved.insert((block, depth), Operand::Value(value));
+                self.following.insert((block, depth));
+                let incoming = predecessors
+                    .iter()
+                    .map(|&predecessor| {
+                        let local = self.lifted[&predecessor].exit(depth);
+                        (predecessor, self.local(predecessor, local))
+                    })
+                    .collect();
+                self.following.remove(&(block, depth));
+                self.phis.entry(block).or_default().push(Phi {
+                    result: value,
+                    incoming,
+                });
+                Operand::Value(value)
+            }
+        }
+    }
+
+    // Stack item the code starts with, or an undefined one.
+    fn input(&mut self, block: usize, depth: usize) -> Operand {
+        let value = self.value();
+        self.resolved.insert((block, depth), Operand::Value(value));
+        self.inputs.entry(block).or_default().push((value, depth));
+        Operand::Value(value)
+    }
+
+    fn value(&mut self) -> usize {
+        self.next += 1;
+        self.next - 1
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Value(value) => write!(f, "v{}", value),
+            Operand::Const(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(result) = self.result {
+            write!(f, "v{} = ", result)?;
+        }
+        write!(f, "{}", parser::opcode_to_name(self.opcode))?;
+        for (n, operand) in self.operands.iter().enumerate() {
+            write!(f, "{}{}", if n == 0 { " " } else { ", " }, operand)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Ir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for block in self.blocks.values() {
+            writeln!(f, "block_{:04x}:", block.start)?;
+            for (value, depth) in &block.inputs {
+                writeln!(f, "        v{} = STACK[{}]", value, depth)?;
+            }
+            for phi in &block.phis {
+                let incoming: Vec<String> = phi
+                    .incoming
+                    .iter()
+                    .map(|(block, operand)| format!("block_{:04x}: {}", block, operand))
+                    .collect();
+                writeln!(f, "        v{} = PHI [{}]", phi.result, incoming.join(", "))?;
+            }
+            for statement in &block.statements {
+                writeln!(f, "  {:04x}: {}", statement.offset, statement)?;
+            }
+            if !block.successors.is_empty() {
+                let successors: Vec<String> = block
+                    .successors
+                    .iter()
+                    .map(|start| format!("block_{:04x}", start))
+                    .collect();
+                writeln!(f, "        -> {}", successors.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_bytecode;
+
+    #[test]
+    fn phi_at_loop_header() {
+        // 00: PUSH1 0
+        // 02: JUMPDEST PUSH1 1 ADD DUP1 CALLDATASIZE GT PUSH1 2 JUMPI
+        // 0c: STOP
+        let bytecode = hex::decode("60005b60010180361160025700").unwrap();
+        let ir = Ir::from_instructions(&parse_bytecode(&bytecode));
+
+        let header = &ir.blocks[&0x02];
+        assert_eq!(header.phis.len(), 1);
+        assert_eq!(
+            header.phis[0].incoming,
+            vec![
+                (0x00, Operand::Const(U256::ZERO)),
+                (0x02, Operand::Value(1))
+            ]
+        );
+        let statements: Vec<String> = header.statements.iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            statements,
+            vec![
+                "v1 = ADD 0x1, v0",
+                "v2 = CALLDATASIZE",
+                "v3 = GT v2, v1",
+                "JUMPI 0x2, v3"
+            ]
+        );
+    }
+
+    #[test]
+    fn blocks_only_reached_from_each_other() {
+        // 00: JUMPDEST ADD PUSH1 5 JUMP
+        // 05: JUMPDEST PUSH1 0 JUMP
+        let bytecode = hex::decode("5b016005565b600056").unwrap();
+        let ir = Ir::from_instructions(&parse_bytecode(&bytecode));
+
+        let statements: Vec<String> = ir.blocks[&0x00]
+            .statements
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        // the top of the stack comes back from 05, what's under it was never pushed
+        assert_eq!(statements, vec!["v1 = ADD v1, v0", "JUMP 0x5"]);
+    }
+
+    #[test]
+    fn origins_through_keccak() {
+        // PUSH1 0x20 CALLDATALOAD PUSH1 0 CALLDATALOAD KECCAK256
+        let bytecode = hex::decode("60203560003520").unwrap();
+        let ir = Ir::from_instructions(&parse_bytecode(&bytecode));
+        let definitions = ir.definitions();
+
+        let hash = ir
+            .statements()
+            .find(|statement| statement.opcode == 0x20)
+            .and_then(|statement| statement.result)
+            .unwrap();
+        let mut found: Vec<usize> = origins(Operand::Value(hash), &definitions)
+            .iter()
+            .map(|statement| statement.offset)
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![0x02, 0x05]);
+    }
+}
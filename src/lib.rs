@@ -1,5 +1,29 @@
+pub mod abi;
 pub mod analysis;
+pub mod cfg;
+pub mod compiler;
+pub mod coverage;
+pub mod deadcode;
+pub mod decompile;
+pub mod detectors;
+pub mod diff;
+pub mod emulator;
+pub mod fingerprint;
+pub mod immutables;
+pub mod input;
+pub mod interfaces;
+pub mod ir;
 pub mod loader;
+pub mod loops;
 pub mod parser;
+pub mod proxy;
+pub mod report;
+pub mod rpc;
 pub mod selectors;
+pub mod shape;
+pub mod source;
+pub mod symbolic;
+pub mod trace;
+pub mod u256;
 pub mod view;
+pub mod vyper;
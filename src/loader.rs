@@ -1,11 +1,34 @@
+use std::collections::BTreeMap;
+
 use crate::{
+    abi::{self, CustomError, Event},
     analysis::{Analysis, FunctionEntrypoint},
+    cfg::{Cfg, Slot},
+    compiler::{self, Compiler, Family},
+    immutables::{self, Immutable},
     parser::{self, Instruction},
+    proxy::{self, Proxy},
+    shape::{self, Shape},
+    vyper,
 };
 
 pub struct Program {
     pub sections: Vec<Section>,
     pub entrypoints: Vec<FunctionEntrypoint>,
+    pub events: Vec<Event>,
+    pub errors: Vec<CustomError>,
+    pub proxy: Option<Proxy>,
+    pub shape: Option<Shape>,
+    pub children: Vec<Child>,
+    pub immutables: Vec<Immutable>,
+    pub compiler: Option<Compiler>,
+}
+
+// Creation code of another contract embedded by a factory.
+pub struct Child {
+    pub parent: SectionKind, // section the creation code is copied from
+    pub offset: usize,       // offset within the parent section
+    pub program: Program,
 }
 
 #[derive(Clone)]
@@ -16,14 +39,27 @@ pub struct Section {
     pub start_pc: usize,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum SectionKind {
     Init,
     Runtime,
+    Data,
     Metadata,
     Raw,
 }
 
+impl SectionKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SectionKind::Init => "init",
+            SectionKind::Runtime => "runtime",
+            SectionKind::Data => "data",
+            SectionKind::Metadata => "metadata",
+            SectionKind::Raw => "raw",
+        }
+    }
+}
+
 impl Program {
     pub fn load(bytes: &[u8], raw: bool, runtime: bool) -> Self {
         if raw {
@@ -35,23 +71,72 @@ impl Program {
                     start_pc: 0,
                 }],
                 entrypoints: Vec::new(),
+                events: Vec::new(),
+                errors: Vec::new(),
+                proxy: None,
+                shape: None,
+                children: Vec::new(),
+                immutables: Vec::new(),
+                compiler: None,
+            };
+        }
+
+        // empty code and delegations are described rather than disassembled, stubs are
+        // disassembled with their shape as an annotation
+        let shape = shape::detect_shape(bytes);
+        if let Some(shape @ (Shape::Empty | Shape::Delegation { .. })) = shape {
+            let sections = if bytes.is_empty() {
+                Vec::new()
+            } else {
+                vec![Section {
+                    kind: SectionKind::Raw,
+                    instructions: None,
+                    raw_bytes: bytes.to_vec(),
+                    start_pc: 0,
+                }]
+            };
+
+            return Program {
+                sections,
+                entrypoints: Vec::new(),
+                events: Vec::new(),
+                errors: Vec::new(),
+                proxy: None,
+                shape: Some(shape),
+                children: Vec::new(),
+                immutables: Vec::new(),
+                compiler: None,
             };
         }
 
         let mut sections = Vec::new();
 
-        let metadata_split_offset = Self::detect_metadata_split(&bytes);
+        let compiler = compiler::detect_compiler(bytes);
+
+        let metadata_split_offset = Self::detect_metadata_split(bytes);
 
         let code_bytes = &bytes[0..metadata_split_offset];
 
-        let runtime_split_offset = if runtime {
-            0 // no init in runtime mode, we assume runtime starts at 0 offset
-        } else {
-            Self::detect_runtime_split(&code_bytes)
+        let runtime_split_offset = match compiler.as_ref().map(|c| c.family) {
+            _ if runtime => 0, // no init in runtime mode, we assume runtime starts at 0 offset
+            None | Some(Family::Solidity) => Self::detect_runtime_split(code_bytes),
+            // other compilers don't separate init and runtime with INVALID, vyper's runtime
+            // ends where the metadata starts
+            Some(_) => compiler::vyper_layout(bytes)
+                .and_then(|layout| metadata_split_offset.checked_sub(layout.runtime_size))
+                .or_else(|| compiler::copied_runtime_offset(&parser::parse_bytecode(code_bytes)))
+                .unwrap_or_else(|| match &compiler {
+                    // a guess may be wrong, solidity's split still finds RETURN INVALID
+                    Some(compiler) if compiler.guessed => Self::detect_runtime_split(code_bytes),
+                    _ => 0,
+                }),
         };
 
+        let mut children = Vec::new();
+
         if runtime_split_offset > 0 {
             let init_bytes = &code_bytes[0..runtime_split_offset];
+            let init_bytes = Self::split_children(init_bytes, SectionKind::Init, &mut children);
             sections.push(Section {
                 kind: SectionKind::Init,
                 instructions: Some(parser::parse_bytecode(init_bytes)),
@@ -61,8 +146,25 @@ impl Program {
         }
 
         let mut entrypoints = Vec::new();
+        let mut events = Vec::new();
+        let mut errors = Vec::new();
+        let mut runtime_instructions = Vec::new();
 
         let runtime_bytes = &code_bytes[runtime_split_offset..];
+
+        // without metadata, a dispatcher reading its jump table from the code is vyper's
+        let vyper = match &compiler {
+            Some(compiler) if !compiler.guessed => compiler.family == Family::Vyper,
+            _ => vyper::data_section_start(&parser::parse_bytecode(runtime_bytes)).is_some(),
+        };
+        let data_split_offset = if vyper {
+            Self::detect_data_split(bytes, runtime_bytes)
+        } else {
+            runtime_bytes.len()
+        };
+        let (runtime_bytes, data_bytes) = runtime_bytes.split_at(data_split_offset);
+        let runtime_bytes =
+            Self::split_children(runtime_bytes, SectionKind::Runtime, &mut children);
         if !runtime_bytes.is_empty() {
             let instructions = parser::parse_bytecode(runtime_bytes);
 
@@ -73,8 +175,26 @@ impl Program {
                 start_pc: 0,
             });
 
-            let analysis = Analysis::from_instructions(&instructions);
-            entrypoints = analysis.function_entrypoints
+            // vyper has its own dispatchers and no custom errors
+            if vyper {
+                entrypoints = vyper::analyze_entrypoints(&instructions, data_bytes);
+                events = abi::analyze_events(&Cfg::from_instructions(&instructions));
+            } else {
+                let analysis = Analysis::from_instructions(&instructions);
+                entrypoints = analysis.function_entrypoints;
+                events = analysis.events;
+                errors = analysis.errors;
+            }
+            runtime_instructions = instructions;
+        }
+
+        if !data_bytes.is_empty() {
+            sections.push(Section {
+                kind: SectionKind::Data,
+                instructions: None,
+                raw_bytes: data_bytes.to_vec(),
+                start_pc: 0,
+            });
         }
 
         if metadata_split_offset < bytes.len() {
@@ -86,12 +206,82 @@ impl Program {
             });
         }
 
+        let proxy = proxy::detect_proxy(bytes, &runtime_instructions, &entrypoints);
+
+        let immutables = match sections.first() {
+            Some(Section {
+                kind: SectionKind::Init,
+                instructions: Some(init),
+                ..
+            }) => immutables::detect_immutables(init, runtime_split_offset, &runtime_instructions),
+            _ => Vec::new(),
+        };
+
         Program {
             sections,
             entrypoints,
+            events,
+            errors,
+            proxy,
+            shape,
+            children,
+            immutables,
+            compiler,
         }
     }
 
+    // Factories copy the creation code of the contracts they deploy with CODECOPY before
+    // CREATE/CREATE2. solc places it after the factory code, each child ending with its
+    // own metadata. Returns the section code without the children.
+    fn split_children<'a>(
+        bytes: &'a [u8],
+        parent: SectionKind,
+        children: &mut Vec<Child>,
+    ) -> &'a [u8] {
+        let instructions = parser::parse_bytecode(bytes);
+        if !instructions.iter().any(|i| matches!(i.opcode, 0xF0 | 0xF5)) {
+            return bytes;
+        }
+
+        // CODECOPY(dest, offset, size) with a constant offset, size when known
+        let mut copies: BTreeMap<usize, Option<usize>> = BTreeMap::new();
+        Cfg::from_instructions(&instructions).walk_with::<Slot>(0, |instruction, inputs| {
+            if instruction.opcode == 0x39
+                && let [_, Slot::Const(offset), size] = inputs
+                && *offset > instruction.offset
+                && *offset < bytes.len()
+            {
+                let size = match size {
+                    Slot::Const(size) => Some(*size),
+                    Slot::Unknown => None,
+                };
+                copies.entry(*offset).or_insert(size);
+            }
+        });
+        let offsets: Vec<(usize, Option<usize>)> = copies.into_iter().collect();
+
+        let mut cut = bytes.len();
+        for (i, &(offset, size)) in offsets.iter().enumerate() {
+            let next = offsets.get(i + 1).map_or(bytes.len(), |(next, _)| *next);
+            let end = size.map_or(next, |size| offset.saturating_add(size).min(bytes.len()));
+            let code = &bytes[offset..end];
+
+            // plain data copies (strings, tables) carry no metadata
+            if Self::detect_metadata_split(code) == code.len() {
+                continue;
+            }
+
+            cut = cut.min(offset);
+            children.push(Child {
+                parent,
+                offset,
+                program: Program::load(code, false, false),
+            });
+        }
+
+        &bytes[..cut]
+    }
+
     // Detect runtime starts by looking for 0xF3FE bytes.
     // It looks like solidity uses that as delimeter, although might break.
     fn detect_runtime_split(bytes: &[u8]) -> usize {
@@ -104,27 +294,24 @@ impl Program {
         0
     }
 
-    // Detect metadata starts by looking at the last two bytes - potential length of metadata.
-    // Then, based on the potential length it looks for pair of magic bytes 0xa2 + 0x64/0x65/0x66.
-    // When metadata not found it returns the size of the whole bytecode.
-    fn detect_metadata_split(bytes: &[u8]) -> usize {
-        if bytes.len() < 2 {
-            return bytes.len();
-        }
-
-        let metadata_length =
-            u16::from_be_bytes([bytes[bytes.len() - 2], bytes[bytes.len() - 1]]) as usize;
-
-        let metadata_start = bytes.len() - metadata_length - 2; // 2 is the size of metadata length
-
-        if metadata_start + 1 < bytes.len() {
-            let first = bytes[metadata_start];
-            let second = bytes[metadata_start + 1];
-            if first == 0xa2 && (second >= 0x64 && second <= 0x66) {
-                return metadata_start;
+    // Vyper appends the selector jump tables of its dispatcher to the runtime code. Their
+    // sizes are in the metadata of the initcode, runtime code alone is searched for the
+    // dispatcher reading them.
+    fn detect_data_split(bytes: &[u8], runtime: &[u8]) -> usize {
+        match compiler::vyper_layout(bytes) {
+            Some(layout) => runtime
+                .len()
+                .saturating_sub(layout.data_sizes.iter().sum::<usize>()),
+            None => {
+                vyper::data_section_start(&parser::parse_bytecode(runtime)).unwrap_or(runtime.len())
             }
         }
+    }
 
-        bytes.len()
+    // Detect metadata starts by looking at the last two bytes - length of the CBOR encoded
+    // metadata solc and vyper append. When metadata not found it returns the size of the
+    // whole bytecode.
+    fn detect_metadata_split(bytes: &[u8]) -> usize {
+        compiler::metadata_start(bytes).unwrap_or(bytes.len())
     }
 }
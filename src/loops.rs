@@ -0,0 +1,321 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+use serde::Serialize;
+
+use crate::{
+    cfg::{BasicBlock, Cfg},
+    ir::{Ir, origins},
+    loader::Program,
+    parser,
+    symbolic::runtime_code,
+    u256::U256,
+};
+
+// Where the values an exit condition is computed from come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Storage,
+    Calldata,
+    Memory,
+    Call, // return data of an external call
+    Environment,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Source::Storage => "storage",
+            Source::Calldata => "calldata",
+            Source::Memory => "memory",
+            Source::Call => "call",
+            Source::Environment => "environment",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Serialize)]
+pub struct Loop {
+    pub header: usize,
+    pub back_edges: Vec<usize>, // blocks jumping back to the header
+    pub blocks: Vec<usize>,
+    pub exits: Vec<(usize, usize)>, // edges leaving the loop
+    pub functions: Vec<String>,
+    pub bound: BTreeSet<Source>, // constant when empty
+    // bound read from storage or calldata, a caller can make the loop run out of gas
+    pub unbounded: bool,
+}
+
+// Natural loops of the runtime code, found from back edges to a dominating block.
+#[derive(Serialize)]
+pub struct Loops {
+    pub loops: Vec<Loop>,
+}
+
+pub fn analyze_loops(program: &Program, selectors: &HashMap<u32, String>) -> Loops {
+    let (instructions, _) = runtime_code(program);
+    let cfg = Cfg::from_instructions(instructions);
+    let ir = Ir::from_instructions(instructions);
+    let dominators = cfg.dominators(0);
+
+    // back edges by header
+    let mut headers: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for block in cfg.blocks.values() {
+        for &successor in &block.successors {
+            if dominators.dominates(successor, block.start) && !is_call(&cfg, block, successor) {
+                headers.entry(successor).or_default().push(block.start);
+            }
+        }
+    }
+
+    let walks: Vec<(String, BTreeSet<usize>)> = program
+        .entrypoints
+        .iter()
+        .map(|entry| {
+            let name = format!(
+                "0x{} {}",
+                hex::encode(entry.selector),
+                entry.signature(selectors).0
+            );
+            (name, cfg.walk(entry.offset).blocks)
+        })
+        .collect();
+    let definitions = ir.definitions();
+
+    let loops = headers
+        .into_iter()
+        .map(|(header, back_edges)| {
+            // blocks reaching a back edge without going through the header
+            let mut blocks = BTreeSet::from([header]);
+            let mut queue = back_edges.clone();
+            while let Some(start) = queue.pop() {
+                if dominators.dominates(header, start) && blocks.insert(start) {
+                    queue.extend(cfg.blocks[&start].predecessors.iter().copied());
+                }
+            }
+
+            let exits: Vec<(usize, usize)> = blocks
+                .iter()
+                .flat_map(|start| {
+                    cfg.blocks[start]
+                        .successors
+                        .iter()
+                        .filter(|successor| !blocks.contains(successor))
+                        .map(|successor| (*start, *successor))
+                })
+                .collect();
+
+            let mut bound = BTreeSet::new();
+            for (from, _) in &exits {
+                let condition = ir.blocks[from]
+                    .statements
+                    .last()
+                    .filter(|statement| statement.opcode == 0x57)
+                    .map(|statement| statement.operands[1]);
+                let Some(condition) = condition else {
+                    continue;
+                };
+                for origin in origins(condition, &definitions) {
+                    let source = match origin.opcode {
+                        0x54 => Source::Storage,
+                        0x35 | 0x36 => Source::Calldata,
+                        0x51 => Source::Memory,
+                        0x3D | 0xF0..=0xFA => Source::Call,
+                        0x30..=0x48 | 0x58..=0x5A => Source::Environment,
+                        _ => continue,
+                    };
+                    bound.insert(source);
+                }
+            }
+
+            Loop {
+                header,
+                back_edges,
+                blocks: blocks.iter().copied().collect(),
+                exits,
+                functions: walks
+                    .iter()
+                    .filter(|(_, walk)| walk.contains(&header))
+                    .map(|(name, _)| name.clone())
+                    .collect(),
+                unbounded: bound.contains(&Source::Storage) || bound.contains(&Source::Calldata),
+                bound,
+            }
+        })
+        .collect();
+
+    Loops { loops }
+}
+
+// Jump to a block with a return address the block pushed still on the stack below the
+// target, and that the callee jumps back to. An internal function called twice from the
+// same function otherwise looks like a loop.
+fn is_call(cfg: &Cfg, block: &BasicBlock, target: usize) -> bool {
+    let (jump, rest) = block
+        .instructions
+        .split_last()
+        .expect("basic block is never empty");
+    if jump.opcode != 0x56 {
+        return false;
+    }
+
+    // constants the block pushes, items from before it are unknown
+    let mut stack: Vec<Option<usize>> = Vec::new();
+    let ensure = |stack: &mut Vec<Option<usize>>, n: usize| {
+        while stack.len() < n {
+            stack.insert(0, None);
+        }
+    };
+    for instruction in rest {
+        match instruction.opcode {
+            0x5F..=0x7F => stack.push(U256::from_be_bytes(&instruction.data).to_usize()),
+            // DUP1..DUP16
+            opcode @ 0x80..=0x8F => {
+                let n = (opcode - 0x7F) as usize;
+                ensure(&mut stack, n);
+                stack.push(stack[stack.len() - n]);
+            }
+            // SWAP1..SWAP16
+            opcode @ 0x90..=0x9F => {
+                let n = (opcode - 0x8F) as usize;
+                ensure(&mut stack, n + 1);
+                let len = stack.len();
+                stack.swap(len - 1, len - 1 - n);
+            }
+            opcode => {
+                let (pops, pushes) = parser::stack_io(opcode);
+                ensure(&mut stack, pops);
+                stack.truncate(stack.len() - pops);
+                if pushes == 1 {
+                    stack.push(None);
+                }
+            }
+        }
+    }
+
+    let Some((Some(jumped), below)) = stack.split_last() else {
+        return false;
+    };
+    let returns: Vec<usize> = below
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|&pushed| {
+            pushed != target && cfg.blocks.get(&pushed).is_some_and(|b| b.is_jumpdest())
+        })
+        .collect();
+    if *jumped != target || returns.is_empty() {
+        return false;
+    }
+
+    cfg.walk(block.start)
+        .edges
+        .iter()
+        .any(|(_, to)| returns.contains(to))
+}
+
+fn blocks(starts: &[usize]) -> String {
+    let starts: Vec<String> = starts
+        .iter()
+        .map(|start| format!("{:04x}", start))
+        .collect();
+    starts.join(", ")
+}
+
+impl fmt::Display for Loops {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.loops.is_empty() {
+            return writeln!(f, "no loops");
+        }
+
+        for l in &self.loops {
+            writeln!(
+                f,
+                "{:04x}: loop of {} blocks, back from {}",
+                l.header,
+                l.blocks.len(),
+                blocks(&l.back_edges)
+            )?;
+            for function in &l.functions {
+                writeln!(f, "    in {}", function)?;
+            }
+
+            let exits: Vec<String> = l
+                .exits
+                .iter()
+                .map(|(from, to)| format!("{:04x} -> {:04x}", from, to))
+                .collect();
+            if exits.is_empty() {
+                writeln!(f, "    no exits")?;
+            } else {
+                writeln!(f, "    exits {}", exits.join(", "))?;
+            }
+
+            let bound: Vec<String> = l.bound.iter().map(|source| source.to_string()).collect();
+            let bound = if bound.is_empty() {
+                "constant".to_string()
+            } else {
+                bound.join(", ")
+            };
+            if l.unbounded {
+                writeln!(f, "    bound: {} (unbounded)", bound)?;
+            } else {
+                writeln!(f, "    bound: {}", bound)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loop_bounded_by_storage() {
+        // 00: PUSH1 0
+        // 02: JUMPDEST PUSH1 0 SLOAD DUP2 LT ISZERO PUSH1 0x12 JUMPI
+        // 0c: PUSH1 1 ADD PUSH1 2 JUMP
+        // 12: JUMPDEST STOP
+        let bytecode = hex::decode("60005b6000548110156012576001016002565b00").unwrap();
+        let program = Program::load(&bytecode, true, false);
+
+        let loops = analyze_loops(&program, &HashMap::new()).loops;
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].header, 0x02);
+        assert_eq!(loops[0].back_edges, vec![0x0c]);
+        assert_eq!(loops[0].exits, vec![(0x02, 0x12)]);
+        assert_eq!(loops[0].bound, BTreeSet::from([Source::Storage]));
+        assert!(loops[0].unbounded);
+    }
+
+    #[test]
+    fn internal_function_called_twice_is_no_loop() {
+        // 00: PUSH1 0x07 PUSH1 0x0f JUMP
+        // 05: INVALID INVALID
+        // 07: JUMPDEST PUSH1 0x0d PUSH1 0x0f JUMP
+        // 0d: JUMPDEST STOP
+        // 0f: JUMPDEST JUMP
+        let bytecode = hex::decode("6007600f56fefe5b600d600f565b005b56").unwrap();
+        let program = Program::load(&bytecode, true, false);
+
+        assert!(analyze_loops(&program, &HashMap::new()).loops.is_empty());
+    }
+
+    #[test]
+    fn stride_equal_to_a_jumpdest() {
+        // loop_bounded_by_storage with i += 0x12, the JUMPDEST the loop exits to
+        let bytecode = hex::decode("60005b6000548110156012576012016002565b00").unwrap();
+        let program = Program::load(&bytecode, true, false);
+
+        let loops = analyze_loops(&program, &HashMap::new()).loops;
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].back_edges, vec![0x0c]);
+        assert!(loops[0].unbounded);
+    }
+}
@@ -1,12 +1,58 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
-use clap::Parser;
-use scent::{loader::Program, selectors::load_selectors, view::View};
+use clap::{Parser, Subcommand};
+use scent::{
+    abi::reconstruct_abi,
+    coverage::analyze_coverage,
+    deadcode::analyze_dead_code,
+    decompile::decompile_program,
+    detectors::{default_detectors, run_detectors},
+    diff::diff_programs,
+    emulator::{Environment, Execution, Outcome, execute},
+    fingerprint::{compare, fingerprint_program},
+    immutables::resolve_immutables,
+    input::{Input, read_input},
+    ir::Ir,
+    loader::Program,
+    loops::analyze_loops,
+    report::Report,
+    rpc::Client,
+    selectors::load_selectors,
+    symbolic::explore_program,
+    trace::{Trace, read_trace},
+    u256::U256,
+    view::View,
+};
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    #[arg(value_name = "PATH")]
-    path: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Hex or binary file, Foundry, Hardhat or solc standard JSON artifact, `-` for stdin.
+    /// Contract address with --rpc
+    #[arg(value_name = "PATH", required_unless_present = "tx")]
+    path: Option<PathBuf>,
+
+    /// JSON-RPC endpoint to fetch runtime code of the address from
+    #[arg(long, value_name = "URL")]
+    rpc: Option<String>,
+
+    /// Block number or tag to fetch code at
+    #[arg(long, requires = "rpc", default_value = "latest")]
+    block: String,
+
+    /// Fetch creation code from a deployment transaction instead
+    #[arg(long, value_name = "HASH", requires = "rpc", conflicts_with = "path")]
+    tx: Option<String>,
+
+    /// Contract to pick from solc standard JSON output
+    #[arg(long, value_name = "NAME")]
+    contract: Option<String>,
 
     /// Raw bytecode input
     #[arg(long)]
@@ -20,34 +66,690 @@ struct Cli {
     #[arg(long)]
     decorated: bool,
 
-    /// Selectors list as JSON (implies --decorated)
+    /// Selectors list as JSON (implies --decorated, as do artifacts with an ABI)
     #[arg(long)]
     selectors: Option<PathBuf>,
+
+    /// Deployed runtime bytecode, used to show immutable values
+    #[arg(long, value_name = "PATH")]
+    deployed: Option<PathBuf>,
+
+    /// Print analysis results as JSON instead of disassembly
+    #[arg(long)]
+    json: bool,
+
+    /// debug_traceTransaction structLogs or `run --trace --json` output to overlay
+    #[arg(long, value_name = "PATH", conflicts_with = "json")]
+    trace: Option<PathBuf>,
+
+    /// Print code sections in SSA form instead of disassembly
+    #[arg(long, conflicts_with_all = ["json", "trace"])]
+    ir: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Reconstruct a Solidity ABI JSON from bytecode
+    Abi {
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        /// Runtime bytecode input
+        #[arg(long)]
+        runtime: bool,
+
+        /// Contract to pick from solc standard JSON output
+        #[arg(long, value_name = "NAME")]
+        contract: Option<String>,
+
+        /// Selectors list as JSON
+        #[arg(long)]
+        selectors: Option<PathBuf>,
+    },
+    /// Compare two contracts by function and basic block
+    Diff {
+        #[arg(value_name = "OLD")]
+        old: PathBuf,
+
+        #[arg(value_name = "NEW")]
+        new: PathBuf,
+
+        /// Runtime bytecode inputs
+        #[arg(long)]
+        runtime: bool,
+
+        /// Selectors list as JSON
+        #[arg(long)]
+        selectors: Option<PathBuf>,
+
+        /// Print the differences as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Rank contracts of a corpus directory by similarity to a target
+    Similar {
+        #[arg(value_name = "TARGET")]
+        target: PathBuf,
+
+        #[arg(value_name = "CORPUS")]
+        corpus: PathBuf,
+
+        /// Runtime bytecode inputs
+        #[arg(long)]
+        runtime: bool,
+
+        /// Number of matches to print
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+
+        /// Print the matches as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Execute a contract call and print what it returns
+    Run {
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        /// Runtime bytecode input, creation code is deployed first otherwise
+        #[arg(long)]
+        runtime: bool,
+
+        /// Contract to pick from solc standard JSON output
+        #[arg(long, value_name = "NAME")]
+        contract: Option<String>,
+
+        /// Calldata as hex
+        #[arg(long, value_name = "HEX", default_value = "")]
+        calldata: String,
+
+        /// Wei sent with the call, or to the constructor with --deploy
+        #[arg(long, default_value = "0")]
+        value: U256,
+
+        /// Caller and origin address
+        #[arg(long, value_name = "ADDRESS")]
+        caller: Option<U256>,
+
+        /// Initial storage as `SLOT=VALUE`, can be repeated
+        #[arg(long, value_name = "SLOT=VALUE")]
+        storage: Vec<String>,
+
+        /// Constructor arguments as hex, appended to the creation code
+        #[arg(long, value_name = "HEX", default_value = "")]
+        args: String,
+
+        /// Only run the creation code, returning the deployed code
+        #[arg(long, conflicts_with = "runtime")]
+        deploy: bool,
+
+        /// Print every executed instruction with the stack
+        #[arg(long)]
+        trace: bool,
+
+        /// Print the execution as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Measure code covered by debug_traceTransaction or `run --trace --json` traces
+    Coverage {
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        #[arg(value_name = "TRACE", required = true)]
+        traces: Vec<PathBuf>,
+
+        /// Runtime bytecode input
+        #[arg(long)]
+        runtime: bool,
+
+        /// Contract to pick from solc standard JSON output
+        #[arg(long, value_name = "NAME")]
+        contract: Option<String>,
+
+        /// Selectors list as JSON
+        #[arg(long)]
+        selectors: Option<PathBuf>,
+
+        /// Print an lcov tracefile, offsets stand for line numbers
+        #[arg(long, conflicts_with_all = ["json", "listing"])]
+        lcov: bool,
+
+        /// Print the coverage as JSON
+        #[arg(long, conflicts_with = "listing")]
+        json: bool,
+
+        /// Print the disassembly with executed instructions marked
+        #[arg(long)]
+        listing: bool,
+    },
+    /// Enumerate paths to each RETURN, REVERT and STOP with the conditions to get there
+    Paths {
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        /// Runtime bytecode input
+        #[arg(long)]
+        runtime: bool,
+
+        /// Contract to pick from solc standard JSON output
+        #[arg(long, value_name = "NAME")]
+        contract: Option<String>,
+
+        /// Selectors list as JSON
+        #[arg(long)]
+        selectors: Option<PathBuf>,
+
+        /// Print the paths as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Find loops and what their exit conditions depend on
+    Loops {
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        /// Runtime bytecode input
+        #[arg(long)]
+        runtime: bool,
+
+        /// Contract to pick from solc standard JSON output
+        #[arg(long, value_name = "NAME")]
+        contract: Option<String>,
+
+        /// Selectors list as JSON
+        #[arg(long)]
+        selectors: Option<PathBuf>,
+
+        /// Print the loops as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Report unreachable code and JUMPDESTs never jumped to
+    DeadCode {
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        /// Runtime bytecode input
+        #[arg(long)]
+        runtime: bool,
+
+        /// Contract to pick from solc standard JSON output
+        #[arg(long, value_name = "NAME")]
+        contract: Option<String>,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run security-smell detectors over each function
+    Detect {
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        /// Runtime bytecode input
+        #[arg(long)]
+        runtime: bool,
+
+        /// Contract to pick from solc standard JSON output
+        #[arg(long, value_name = "NAME")]
+        contract: Option<String>,
+
+        /// Selectors list as JSON
+        #[arg(long)]
+        selectors: Option<PathBuf>,
+
+        /// Print the findings as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Lift each function to Solidity-like pseudocode
+    Decompile {
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        /// Runtime bytecode input
+        #[arg(long)]
+        runtime: bool,
+
+        /// Contract to pick from solc standard JSON output
+        #[arg(long, value_name = "NAME")]
+        contract: Option<String>,
+
+        /// Selectors list as JSON
+        #[arg(long)]
+        selectors: Option<PathBuf>,
+    },
+}
+
+fn read_or_exit(path: &Path, runtime: bool, contract: Option<&str>) -> Input {
+    read_input(path, runtime, contract).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+fn fetch_or_exit(url: &str, address: Option<&str>, block: &str, tx: Option<&str>) -> Input {
+    let fetched = Client::new(url).and_then(|client| match tx {
+        Some(hash) => client
+            .get_creation_code(hash)
+            .map(|code| Input::from_bytecode(code, false)),
+        None => client
+            .get_code(address.unwrap_or_default(), block)
+            .map(|code| Input::from_bytecode(code, true)),
+    });
+
+    fetched.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
 }
 
-fn read_hex_file(path: &PathBuf) -> Result<Vec<u8>, String> {
-    let content = fs::read_to_string(path)
-        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+fn print_execution(execution: &Execution, json: bool) {
+    if json {
+        println!("{}", serde_json::to_string_pretty(execution).unwrap());
+    } else {
+        print!("{}", execution);
+    }
+}
 
-    let content = content.trim().strip_prefix("0x").unwrap_or(content.trim());
+fn parse_storage(entries: &[String]) -> Result<BTreeMap<U256, U256>, String> {
+    entries
+        .iter()
+        .map(|entry| {
+            let (slot, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("invalid storage entry {}, expected SLOT=VALUE", entry))?;
+            Ok((slot.parse()?, value.parse()?))
+        })
+        .collect()
+}
 
-    hex::decode(content).map_err(|e| format!("failed to parse hex: {}", e))
+fn decode_hex_or_exit(value: &str) -> Vec<u8> {
+    hex::decode(value.trim_start_matches("0x")).unwrap_or_else(|e| {
+        eprintln!("failed to parse hex {}: {}", value, e);
+        std::process::exit(1);
+    })
+}
+
+// Files below a directory, in path order.
+fn corpus_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("failed to read {}: {}", dir.display(), e))?
+            .path();
+        if path.is_dir() {
+            files.extend(corpus_files(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
 }
 
 fn main() {
     let cli = Cli::parse();
 
-    let bytes = read_hex_file(&cli.path).unwrap_or_else(|e| {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    if let Some(command) = cli.command {
+        match command {
+            Command::Abi {
+                path,
+                runtime,
+                contract,
+                selectors,
+            } => {
+                let input = read_or_exit(&path, runtime, contract.as_deref());
+                let program = Program::load(&input.bytecode, false, input.runtime);
+                let mut selectors = selectors.map(load_selectors).unwrap_or_default();
+                selectors.extend(input.selectors);
+                let abi = reconstruct_abi(&program, &selectors);
+                println!("{}", serde_json::to_string_pretty(&abi).unwrap());
+            }
+            Command::Diff {
+                old,
+                new,
+                runtime,
+                selectors,
+                json,
+            } => {
+                let old = read_or_exit(&old, runtime, None);
+                let new = read_or_exit(&new, runtime, None);
+                let mut selectors = selectors.map(load_selectors).unwrap_or_default();
+                selectors.extend(old.selectors);
+                selectors.extend(new.selectors);
+
+                let diff = diff_programs(
+                    &Program::load(&old.bytecode, false, old.runtime),
+                    &Program::load(&new.bytecode, false, new.runtime),
+                    &selectors,
+                );
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&diff).unwrap());
+                } else {
+                    print!("{}", diff);
+                }
+
+                // like diff(1), differences are reported through the exit code
+                if !diff.is_identical() {
+                    std::process::exit(1);
+                }
+            }
+            Command::Similar {
+                target,
+                corpus,
+                runtime,
+                top,
+                json,
+            } => {
+                let target = read_or_exit(&target, runtime, None);
+                let target =
+                    fingerprint_program(&Program::load(&target.bytecode, false, target.runtime));
+
+                let files = corpus_files(&corpus).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+
+                let mut matches = Vec::new();
+                for path in files {
+                    // a corpus is rarely clean, unreadable entries are skipped
+                    let input = match read_input(&path, runtime, None) {
+                        Ok(input) => input,
+                        Err(e) => {
+                            eprintln!("skipping {}: {}", path.display(), e);
+                            continue;
+                        }
+                    };
+                    let program = Program::load(&input.bytecode, false, input.runtime);
+                    matches.push((path, compare(&target, &fingerprint_program(&program))));
+                }
+
+                matches.sort_by(|a, b| b.1.score.total_cmp(&a.1.score));
+                matches.truncate(top);
+
+                if json {
+                    let matches: Vec<_> = matches
+                        .iter()
+                        .map(|(path, similarity)| {
+                            serde_json::json!({"path": path, "similarity": similarity})
+                        })
+                        .collect();
+                    println!("{}", serde_json::to_string_pretty(&matches).unwrap());
+                } else {
+                    for (path, similarity) in &matches {
+                        println!(
+                            "{:.3}  {}  functions {}/{}",
+                            similarity.score,
+                            path.display(),
+                            similarity.functions_matched,
+                            similarity.functions_total
+                        );
+                    }
+                }
+            }
+            Command::Run {
+                path,
+                runtime,
+                contract,
+                calldata,
+                value,
+                caller,
+                storage,
+                args,
+                deploy,
+                trace,
+                json,
+            } => {
+                let input = read_or_exit(&path, runtime, contract.as_deref());
+                let storage = parse_storage(&storage).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+
+                // the constructor is only paid when it is what runs
+                let defaults = Environment::default();
+                let mut env = Environment {
+                    callvalue: if deploy { value } else { U256::ZERO },
+                    caller: caller.unwrap_or(defaults.caller),
+                    origin: caller.unwrap_or(defaults.origin),
+                    storage,
+                    ..defaults
+                };
+
+                let code = if input.runtime {
+                    input.bytecode
+                } else {
+                    let mut init = input.bytecode;
+                    init.extend(decode_hex_or_exit(&args));
+
+                    let execution = execute(&init, &env, trace && deploy);
+                    if deploy {
+                        print_execution(&execution, json);
+                        return;
+                    }
+
+                    // the constructor's storage writes are kept for the call
+                    match execution.outcome {
+                        Outcome::Return(code) => {
+                            env.storage = execution.storage;
+                            code
+                        }
+                        _ => {
+                            eprintln!("deployment failed: {}", execution.to_string().trim_end());
+                            std::process::exit(1);
+                        }
+                    }
+                };
+
+                env.calldata = decode_hex_or_exit(&calldata);
+                env.callvalue = value;
+                let execution = execute(&code, &env, trace);
+                print_execution(&execution, json);
+
+                if !matches!(execution.outcome, Outcome::Stop | Outcome::Return(_)) {
+                    std::process::exit(1);
+                }
+            }
+            Command::Coverage {
+                path,
+                traces,
+                runtime,
+                contract,
+                selectors,
+                lcov,
+                json,
+                listing,
+            } => {
+                let input = read_or_exit(&path, runtime, contract.as_deref());
+                let program = Program::load(&input.bytecode, false, input.runtime);
+                let mut selectors = selectors.map(load_selectors).unwrap_or_default();
+                selectors.extend(input.selectors);
+
+                let traces: Vec<Trace> = traces
+                    .iter()
+                    .map(|path| {
+                        read_trace(path).unwrap_or_else(|e| {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        })
+                    })
+                    .collect();
+
+                if listing {
+                    let view = View::from_program(
+                        &program,
+                        true,
+                        selectors,
+                        &input.storage,
+                        &input.sources,
+                        Some(&Trace::merge(traces)),
+                    );
+                    print!("{}", view);
+                    return;
+                }
+
+                let coverage = analyze_coverage(&program, &traces, &selectors);
+                if lcov {
+                    print!("{}", coverage.lcov(&path.display().to_string()));
+                } else if json {
+                    println!("{}", serde_json::to_string_pretty(&coverage).unwrap());
+                } else {
+                    print!("{}", coverage);
+                }
+            }
+            Command::Paths {
+                path,
+                runtime,
+                contract,
+                selectors,
+                json,
+            } => {
+                let input = read_or_exit(&path, runtime, contract.as_deref());
+                let program = Program::load(&input.bytecode, false, input.runtime);
+                let mut selectors = selectors.map(load_selectors).unwrap_or_default();
+                selectors.extend(input.selectors);
+
+                let exploration = explore_program(&program, &selectors);
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&exploration).unwrap());
+                } else {
+                    print!("{}", exploration);
+                }
+            }
+            Command::Loops {
+                path,
+                runtime,
+                contract,
+                selectors,
+                json,
+            } => {
+                let input = read_or_exit(&path, runtime, contract.as_deref());
+                let program = Program::load(&input.bytecode, false, input.runtime);
+                let mut selectors = selectors.map(load_selectors).unwrap_or_default();
+                selectors.extend(input.selectors);
+
+                let loops = analyze_loops(&program, &selectors);
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&loops).unwrap());
+                } else {
+                    print!("{}", loops);
+                }
+            }
+            Command::DeadCode {
+                path,
+                runtime,
+                contract,
+                json,
+            } => {
+                let input = read_or_exit(&path, runtime, contract.as_deref());
+                let program = Program::load(&input.bytecode, false, input.runtime);
+
+                let dead = analyze_dead_code(&program);
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&dead).unwrap());
+                } else {
+                    print!("{}", dead);
+                }
+            }
+            Command::Detect {
+                path,
+                runtime,
+                contract,
+                selectors,
+                json,
+            } => {
+                let input = read_or_exit(&path, runtime, contract.as_deref());
+                let program = Program::load(&input.bytecode, false, input.runtime);
+                let mut selectors = selectors.map(load_selectors).unwrap_or_default();
+                selectors.extend(input.selectors);
+
+                let findings = run_detectors(&program, &selectors, &default_detectors());
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&findings).unwrap());
+                } else {
+                    print!("{}", findings);
+                }
+            }
+            Command::Decompile {
+                path,
+                runtime,
+                contract,
+                selectors,
+            } => {
+                let input = read_or_exit(&path, runtime, contract.as_deref());
+                let program = Program::load(&input.bytecode, false, input.runtime);
+                let mut selectors = selectors.map(load_selectors).unwrap_or_default();
+                selectors.extend(input.selectors);
+
+                print!(
+                    "{}",
+                    decompile_program(&program, &selectors, &input.storage)
+                );
+            }
+        }
+        return;
+    }
+
+    let contract = cli.contract.as_deref();
+    let input = match &cli.rpc {
+        Some(url) => {
+            let address = cli.path.as_ref().and_then(|path| path.to_str());
+            fetch_or_exit(url, address, &cli.block, cli.tx.as_deref())
+        }
+        None => read_or_exit(&cli.path.expect("path is required"), cli.runtime, contract),
+    };
+
+    let mut program = Program::load(&input.bytecode, cli.raw, input.runtime);
+    if let Some(path) = &cli.deployed {
+        let deployed = read_or_exit(path, true, contract);
+        resolve_immutables(&mut program.immutables, &deployed.bytecode);
+    }
+
+    let decorated = cli.decorated
+        || cli.selectors.is_some()
+        || !input.selectors.is_empty()
+        || !input.storage.is_empty();
+
+    // the bundled ABI takes precedence over the selectors database
+    let mut selectors = cli.selectors.map(load_selectors).unwrap_or_default();
+    selectors.extend(input.selectors);
+
+    if cli.json {
+        let report = Report::from_program(&program, &selectors);
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        return;
+    }
+
+    if cli.ir {
+        for section in &program.sections {
+            if let Some(instructions) = &section.instructions {
+                println!("; {}", section.kind.name());
+                print!("{}", Ir::from_instructions(instructions));
+            }
+        }
+        return;
+    }
+
+    let trace = cli.trace.map(|path| {
+        read_trace(&path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
     });
 
-    let program = Program::load(&bytes, cli.raw, cli.runtime);
-    let decorated = cli.decorated || cli.selectors.is_some();
-    let selectors = cli
-        .selectors
-        .map(|path| load_selectors(path))
-        .unwrap_or_default();
-    let view = View::from_program(&program, decorated, selectors);
+    let view = View::from_program(
+        &program,
+        decorated,
+        selectors,
+        &input.storage,
+        &input.sources,
+        trace.as_ref(),
+    );
     print!("{}", view);
 }
@@ -34,7 +34,7 @@ pub fn parse_bytecode(bytes: &[u8]) -> Vec<Instruction> {
         i += 1;
 
         // push1 = 0x60, push32 = 0x7F
-        let data = if opcode >= 0x60 && opcode <= 0x7F {
+        let data = if (0x60..=0x7F).contains(&opcode) {
             let push_size = (opcode - 0x5F) as usize;
             let mut push_data = Vec::new();
 
@@ -209,10 +209,47 @@ pub fn opcode_to_name(opcode: u8) -> &'static str {
         0xFA => "STATICCALL",
         0xFD => "REVERT",
         0xFE => "INVALID",
+        0xFF => "SELFDESTRUCT",
         _ => "UNKNOWN",
     }
 }
 
+// Number of stack items consumed and produced by an opcode.
+pub fn stack_io(opcode: u8) -> (usize, usize) {
+    match opcode {
+        0x00 | 0x5B | 0xFE => (0, 0),
+        0x01..=0x07 | 0x0A | 0x0B | 0x10..=0x14 | 0x16..=0x18 | 0x1A..=0x1D | 0x20 => (2, 1),
+        0x08 | 0x09 => (3, 1),
+        0x15 | 0x19 => (1, 1),
+        0x30 | 0x32..=0x34 | 0x36 | 0x38 | 0x3A | 0x3D | 0x41..=0x48 | 0x4A => (0, 1),
+        0x31 | 0x35 | 0x3B | 0x3F | 0x40 | 0x49 | 0x51 | 0x54 | 0x5C => (1, 1),
+        0x37 | 0x39 | 0x3E | 0x5E => (3, 0),
+        0x3C => (4, 0),
+        0x50 | 0x56 | 0xFF => (1, 0),
+        0x52 | 0x53 | 0x55 | 0x57 | 0x5D | 0xF3 | 0xFD => (2, 0),
+        0x58..=0x5A | 0x5F..=0x7F => (0, 1),
+        0x80..=0x8F => {
+            let n = (opcode - 0x7F) as usize;
+            (n, n + 1)
+        }
+        0x90..=0x9F => {
+            let n = (opcode - 0x8F) as usize;
+            (n + 1, n + 1)
+        }
+        0xA0..=0xA4 => ((opcode - 0xA0) as usize + 2, 0),
+        0xF0 => (3, 1),
+        0xF1 | 0xF2 => (7, 1),
+        0xF4 | 0xFA => (6, 1),
+        0xF5 => (4, 1),
+        _ => (0, 0),
+    }
+}
+
+// RETURN, REVERT, STOP, INVALID, SELFDESTRUCT or an unknown opcode halt execution.
+pub fn is_terminator(opcode: u8) -> bool {
+    matches!(opcode, 0x00 | 0xF3 | 0xFD | 0xFE | 0xFF) || opcode_to_name(opcode) == "UNKNOWN"
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
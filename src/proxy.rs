@@ -0,0 +1,275 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{analysis::FunctionEntrypoint, parser::Instruction};
+
+// EIP-1167 minimal proxy runtime, split around the PUSHn implementation address
+const EIP1167_PREFIX: &[u8] = &[0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d, 0x3d, 0x36, 0x3d];
+const EIP1167_SUFFIX: &[u8] = &[0x5a, 0xf4, 0x3d, 0x82, 0x80, 0x3e, 0x90, 0x3d, 0x91];
+// ERC-7511 minimal proxy using PUSH0
+const ERC7511_PREFIX: &[u8] = &[0x36, 0x5f, 0x5f, 0x37, 0x5f, 0x5f, 0x36, 0x5f];
+const ERC7511_SUFFIX: &[u8] = &[0x5a, 0xf4, 0x3d, 0x5f, 0x5f, 0x3e, 0x5f, 0x3d, 0x91];
+// both end with PUSH1 <success> JUMPI REVERT JUMPDEST RETURN
+const MINIMAL_PROXY_TAIL: &[u8] = &[0x57, 0xfd, 0x5b, 0xf3];
+
+// keccak256("eip1967.proxy.implementation") - 1
+const EIP1967_IMPLEMENTATION_SLOT: &str =
+    "360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+// keccak256("eip1967.proxy.admin") - 1
+const EIP1967_ADMIN_SLOT: &str = "b53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103";
+// keccak256("eip1967.proxy.beacon") - 1
+const EIP1967_BEACON_SLOT: &str =
+    "a3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50";
+// keccak256("PROXIABLE")
+const EIP1822_PROXIABLE_SLOT: &str =
+    "c5f16f0fcc639fa48a6947836d9850f504798523bf8c9a3a87d5876cf622bcf7";
+// keccak256("org.zeppelinos.proxy.implementation")
+const ZEPPELINOS_IMPLEMENTATION_SLOT: &str =
+    "7050c9e0f4ca769c69bd3a8ef740bc37934f8e2c036e5a723fd8ee048ed3f8c3";
+
+const PROXIABLE_UUID_SELECTOR: [u8; 4] = [0x52, 0xd1, 0x90, 0x2d]; // proxiableUUID()
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ProxyKind {
+    #[serde(rename = "EIP-1167")]
+    Eip1167,
+    #[serde(rename = "ERC-7511")]
+    Erc7511,
+    #[serde(rename = "EIP-1967")]
+    Eip1967,
+    #[serde(rename = "EIP-1967 transparent")]
+    Eip1967Transparent,
+    #[serde(rename = "EIP-1967 beacon")]
+    Eip1967Beacon,
+    #[serde(rename = "EIP-1822 UUPS")]
+    Eip1822,
+    #[serde(rename = "UUPS implementation")]
+    UupsImplementation,
+    #[serde(rename = "ZeppelinOS")]
+    ZeppelinOs,
+}
+
+impl fmt::Display for ProxyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ProxyKind::Eip1167 => "EIP-1167 minimal proxy",
+            ProxyKind::Erc7511 => "ERC-7511 minimal proxy (PUSH0)",
+            ProxyKind::Eip1967 => "EIP-1967 proxy",
+            ProxyKind::Eip1967Transparent => "EIP-1967 transparent proxy",
+            ProxyKind::Eip1967Beacon => "EIP-1967 beacon proxy",
+            ProxyKind::Eip1822 => "EIP-1822 UUPS proxy",
+            ProxyKind::UupsImplementation => "UUPS implementation (not a proxy)",
+            ProxyKind::ZeppelinOs => "ZeppelinOS proxy",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Where the proxy finds the code it delegates to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Implementation {
+    Address(Vec<u8>),             // hardcoded in the code
+    Slot(&'static str, String),   // storage slot (name, hex value)
+    Beacon(&'static str, String), // storage slot holding the beacon, asked for `implementation()`
+}
+
+impl Serialize for Implementation {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl fmt::Display for Implementation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Implementation::Address(address) => write!(f, "0x{}", hex::encode(address)),
+            Implementation::Slot(name, slot) => write!(f, "storage slot 0x{} ({})", slot, name),
+            Implementation::Beacon(name, slot) => {
+                write!(f, "beacon at storage slot 0x{} ({})", slot, name)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Proxy {
+    pub kind: ProxyKind,
+    pub implementation: Implementation,
+}
+
+impl fmt::Display for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, implementation: {}", self.kind, self.implementation)
+    }
+}
+
+// Minimal proxies are matched on the whole code, either runtime or creation code
+// ending with the runtime. Upgradeable proxies are recognized by well-known storage
+// slot constants pushed by runtime code that also contains DELEGATECALL.
+pub fn detect_proxy(
+    bytes: &[u8],
+    runtime: &[Instruction],
+    entrypoints: &[FunctionEntrypoint],
+) -> Option<Proxy> {
+    if let Some(proxy) = detect_minimal_proxy(bytes) {
+        return Some(proxy);
+    }
+
+    let slots: Vec<String> = runtime
+        .iter()
+        .filter(|instruction| instruction.opcode == 0x7F) // PUSH32
+        .map(|instruction| hex::encode(&instruction.data))
+        .collect();
+    let has = |slot: &str| slots.iter().any(|s| s == slot);
+    let delegates = runtime.iter().any(|instruction| instruction.opcode == 0xF4);
+
+    if !delegates {
+        return None;
+    }
+
+    let slot = |name, slot: &'static str| Implementation::Slot(name, slot.to_string());
+
+    let proxy = if has(EIP1967_BEACON_SLOT) {
+        Proxy {
+            kind: ProxyKind::Eip1967Beacon,
+            implementation: Implementation::Beacon(
+                "eip1967.proxy.beacon",
+                EIP1967_BEACON_SLOT.to_string(),
+            ),
+        }
+    } else if has(EIP1967_IMPLEMENTATION_SLOT) {
+        // UUPS implementations carry the slot for `upgradeToAndCall` and answer `proxiableUUID()`
+        let kind = if entrypoints
+            .iter()
+            .any(|e| e.selector == PROXIABLE_UUID_SELECTOR)
+        {
+            ProxyKind::UupsImplementation
+        } else if has(EIP1967_ADMIN_SLOT) {
+            ProxyKind::Eip1967Transparent
+        } else {
+            ProxyKind::Eip1967
+        };
+
+        Proxy {
+            kind,
+            implementation: slot("eip1967.proxy.implementation", EIP1967_IMPLEMENTATION_SLOT),
+        }
+    } else if has(EIP1822_PROXIABLE_SLOT) {
+        Proxy {
+            kind: ProxyKind::Eip1822,
+            implementation: slot("PROXIABLE", EIP1822_PROXIABLE_SLOT),
+        }
+    } else if has(ZEPPELINOS_IMPLEMENTATION_SLOT) {
+        Proxy {
+            kind: ProxyKind::ZeppelinOs,
+            implementation: slot(
+                "org.zeppelinos.proxy.implementation",
+                ZEPPELINOS_IMPLEMENTATION_SLOT,
+            ),
+        }
+    } else {
+        return None;
+    };
+
+    Some(proxy)
+}
+
+fn detect_minimal_proxy(bytes: &[u8]) -> Option<Proxy> {
+    let variants = [
+        (ProxyKind::Eip1167, EIP1167_PREFIX, EIP1167_SUFFIX),
+        (ProxyKind::Erc7511, ERC7511_PREFIX, ERC7511_SUFFIX),
+    ];
+
+    for (kind, prefix, suffix) in variants {
+        // runtime alone or at the end of creation code
+        let Some(start) = (0..bytes.len()).find(|&i| bytes[i..].starts_with(prefix)) else {
+            continue;
+        };
+
+        let code = &bytes[start + prefix.len()..];
+        // PUSH1..PUSH20 address, shorter for vanity addresses with leading zero bytes
+        let Some(&push) = code.first() else {
+            continue;
+        };
+        if !(0x60..=0x73).contains(&push) {
+            continue;
+        }
+
+        let size = (push - 0x5F) as usize;
+        let Some(address) = code.get(1..1 + size) else {
+            continue;
+        };
+        let rest = &code[1 + size..];
+
+        // suffix, PUSH1 <jumpdest>, tail
+        if rest.len() == suffix.len() + 2 + MINIMAL_PROXY_TAIL.len()
+            && rest.starts_with(suffix)
+            && rest[suffix.len()] == 0x60
+            && rest.ends_with(MINIMAL_PROXY_TAIL)
+        {
+            let mut implementation = vec![0u8; 20 - size];
+            implementation.extend_from_slice(address);
+
+            return Some(Proxy {
+                kind,
+                implementation: Implementation::Address(implementation),
+            });
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eip1167_runtime_and_creation() {
+        let runtime = hex::decode(
+            "363d3d373d3d3d363d73bebebebebebebebebebebebebebebebebebebebe5af43d82803e903d91602b57fd5bf3",
+        )
+        .unwrap();
+        let mut creation = hex::decode("3d602d80600a3d3981f3").unwrap();
+        creation.extend(&runtime);
+
+        for code in [runtime, creation] {
+            let proxy = detect_proxy(&code, &[], &[]).unwrap();
+            assert_eq!(proxy.kind, ProxyKind::Eip1167);
+            assert_eq!(
+                proxy.implementation,
+                Implementation::Address(vec![0xbe; 20])
+            );
+        }
+    }
+
+    #[test]
+    fn eip1967_slot_with_delegatecall() {
+        use crate::parser::parse_bytecode;
+
+        // PUSH32 <implementation slot> SLOAD ... DELEGATECALL
+        let code = hex::decode(format!("7f{}545af4", EIP1967_IMPLEMENTATION_SLOT)).unwrap();
+        let proxy = detect_proxy(&code, &parse_bytecode(&code), &[]).unwrap();
+
+        assert_eq!(proxy.kind, ProxyKind::Eip1967);
+        assert_eq!(
+            proxy.to_string(),
+            format!(
+                "EIP-1967 proxy, implementation: storage slot 0x{} (eip1967.proxy.implementation)",
+                EIP1967_IMPLEMENTATION_SLOT
+            )
+        );
+    }
+
+    #[test]
+    fn erc7511_runtime() {
+        let runtime = hex::decode(
+            "365f5f375f5f365f73bebebebebebebebebebebebebebebebebebebebe5af43d5f5f3e5f3d91602a57fd5bf3",
+        )
+        .unwrap();
+
+        let proxy = detect_proxy(&runtime, &[], &[]).unwrap();
+        assert_eq!(proxy.kind, ProxyKind::Erc7511);
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{
+    analysis::Mutability,
+    compiler::Compiler,
+    immutables::Immutable,
+    interfaces::{InterfaceMatch, detect_interfaces},
+    loader::Program,
+    proxy::Proxy,
+    shape::Shape,
+};
+
+// Machine readable summary of a loaded program, printed with `--json`.
+#[derive(Serialize)]
+pub struct Report<'a> {
+    pub sections: Vec<SectionReport>,
+    pub compiler: Option<&'a Compiler>,
+    pub functions: Vec<FunctionReport>,
+    pub interfaces: Vec<InterfaceMatch>,
+    pub proxy: Option<&'a Proxy>,
+    pub shape: Option<&'a Shape>,
+    pub children: Vec<ChildReport<'a>>,
+    pub immutables: &'a [Immutable],
+}
+
+// Creation code embedded by a factory, reported recursively.
+#[derive(Serialize)]
+pub struct ChildReport<'a> {
+    pub parent: &'static str,
+    pub offset: usize,
+    #[serde(flatten)]
+    pub report: Report<'a>,
+}
+
+#[derive(Serialize)]
+pub struct SectionReport {
+    pub kind: &'static str,
+    pub size: usize,
+}
+
+#[derive(Serialize)]
+pub struct FunctionReport {
+    pub selector: String,
+    pub offset: usize,
+    pub signature: String,
+    pub guessed: bool, // signature not found in the selectors database
+    pub mutability: Mutability,
+}
+
+impl<'a> Report<'a> {
+    pub fn from_program(program: &'a Program, selectors: &HashMap<u32, String>) -> Self {
+        let sections = program
+            .sections
+            .iter()
+            .map(|section| SectionReport {
+                kind: section.kind.name(),
+                size: section.raw_bytes.len(),
+            })
+            .collect();
+
+        let functions = program
+            .entrypoints
+            .iter()
+            .map(|entry| {
+                let (signature, guessed) = entry.signature(selectors);
+                FunctionReport {
+                    selector: format!("0x{}", hex::encode(entry.selector)),
+                    offset: entry.offset,
+                    signature,
+                    guessed,
+                    mutability: entry.mutability,
+                }
+            })
+            .collect();
+
+        Report {
+            sections,
+            compiler: program.compiler.as_ref(),
+            functions,
+            interfaces: detect_interfaces(program),
+            proxy: program.proxy.as_ref(),
+            shape: program.shape.as_ref(),
+            children: program
+                .children
+                .iter()
+                .map(|child| ChildReport {
+                    parent: child.parent.name(),
+                    offset: child.offset,
+                    report: Report::from_program(&child.program, selectors),
+                })
+                .collect(),
+            immutables: &program.immutables,
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use serde_json::{Value, json};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// Minimal JSON-RPC client over plain HTTP, enough for a local node such as anvil.
+pub struct Client {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Client {
+    pub fn new(url: &str) -> Result<Self, String> {
+        if url.starts_with("https://") {
+            return Err("https endpoints are not supported, use a local node".to_string());
+        }
+        let rest = url.strip_prefix("http://").unwrap_or(url);
+
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| format!("invalid port in {}", url))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("invalid url {}", url));
+        }
+
+        Ok(Client {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    // Runtime code of an account at a block number or tag.
+    pub fn get_code(&self, address: &str, block: &str) -> Result<Vec<u8>, String> {
+        if !is_hex_of_len(address, 20) {
+            return Err(format!("invalid address {}", address));
+        }
+
+        let result = self.call("eth_getCode", json!([address, block_tag(block)?]))?;
+        decode(&result)
+    }
+
+    // Creation code sent by a contract deployment transaction.
+    pub fn get_creation_code(&self, hash: &str) -> Result<Vec<u8>, String> {
+        if !is_hex_of_len(hash, 32) {
+            return Err(format!("invalid transaction hash {}", hash));
+        }
+
+        let transaction = self.call("eth_getTransactionByHash", json!([hash]))?;
+        if transaction.is_null() {
+            return Err(format!("transaction {} not found", hash));
+        }
+        if !transaction["to"].is_null() {
+            return Err(format!("transaction {} is not a contract creation", hash));
+        }
+
+        decode(&transaction["input"])
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let body =
+            json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params}).to_string();
+
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            self.port,
+            body.len(),
+            body
+        );
+
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))
+            .map_err(|e| format!("failed to connect to {}:{}: {}", self.host, self.port, e))?;
+        stream.set_read_timeout(Some(TIMEOUT)).ok();
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| format!("failed to send request: {}", e))?;
+
+        let mut response = Vec::new();
+        stream
+            .read_to_end(&mut response)
+            .map_err(|e| format!("failed to read response: {}", e))?;
+
+        let body = http_body(&response)?;
+        let response: Value =
+            serde_json::from_slice(&body).map_err(|e| format!("invalid response: {}", e))?;
+
+        if let Some(error) = response.get("error") {
+            let message = error["message"].as_str().unwrap_or("unknown error");
+            return Err(format!("{} failed: {}", method, message));
+        }
+
+        Ok(response["result"].clone())
+    }
+}
+
+// Body of an HTTP/1.1 response, chunked transfer encoding is decoded.
+fn http_body(response: &[u8]) -> Result<Vec<u8>, String> {
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or("malformed HTTP response")?;
+    let head = String::from_utf8_lossy(&response[..split]);
+    let body = &response[split + 4..];
+
+    let status = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(format!("unexpected HTTP status: {}", status));
+    }
+
+    let chunked = head.lines().any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+    if !chunked {
+        return Ok(body.to_vec());
+    }
+
+    let mut decoded = Vec::new();
+    let mut rest = body;
+    loop {
+        let end = rest
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or("malformed chunked body")?;
+        let size = String::from_utf8_lossy(&rest[..end]);
+        let size = usize::from_str_radix(size.split(';').next().unwrap_or("").trim(), 16)
+            .map_err(|_| "malformed chunk size")?;
+        if size == 0 {
+            return Ok(decoded);
+        }
+
+        let stop = (end + 2).checked_add(size).ok_or("malformed chunk size")?;
+        let chunk = rest.get(end + 2..stop).ok_or("truncated chunked body")?;
+        decoded.extend_from_slice(chunk);
+        rest = rest.get(stop + 2..).unwrap_or_default();
+    }
+}
+
+// `latest`, `pending`, ... or a block number, decimal or hex.
+fn block_tag(block: &str) -> Result<String, String> {
+    if block.starts_with("0x") || block.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Ok(block.to_string());
+    }
+
+    block
+        .parse::<u64>()
+        .map(|number| format!("0x{:x}", number))
+        .map_err(|_| format!("invalid block {}", block))
+}
+
+fn is_hex_of_len(value: &str, bytes: usize) -> bool {
+    value
+        .strip_prefix("0x")
+        .is_some_and(|hex| hex.len() == bytes * 2 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn decode(value: &Value) -> Result<Vec<u8>, String> {
+    let hex = value.as_str().ok_or("unexpected result")?;
+    hex::decode(hex.strip_prefix("0x").unwrap_or(hex))
+        .map_err(|e| format!("failed to parse hex: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use super::*;
+
+    // Answer a single request with `response`, returning the request received.
+    fn serve(response: &'static str) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0u8; 4096];
+            let size = stream.read(&mut request).unwrap();
+            stream.write_all(response.as_bytes()).unwrap();
+            String::from_utf8_lossy(&request[..size]).to_string()
+        });
+
+        (url, handle)
+    }
+
+    #[test]
+    fn get_code_from_local_node() {
+        let (url, handle) = serve(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 44\r\n\r\n{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":\"0x6080ff\"}",
+        );
+
+        let client = Client::new(&url).unwrap();
+        let code = client
+            .get_code("0xbebebebebebebebebebebebebebebebebebebebe", "17")
+            .unwrap();
+        assert_eq!(code, vec![0x60, 0x80, 0xff]);
+
+        let request = handle.join().unwrap();
+        assert!(request.starts_with("POST / HTTP/1.1"));
+        assert!(
+            request.contains(r#""params":["0xbebebebebebebebebebebebebebebebebebebebe","0x11"]"#)
+        );
+    }
+
+    #[test]
+    fn chunked_error_response() {
+        let (url, handle) = serve(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n1b\r\n{\"jsonrpc\":\"2.0\",\"id\":1,\"er\r\n2b\r\nror\":{\"code\":-32000,\"message\":\"not found\"}}\r\n0\r\n\r\n",
+        );
+
+        let client = Client::new(&url).unwrap();
+        let hash = format!("0x{}", "ab".repeat(32));
+        assert_eq!(
+            client.get_creation_code(&hash).unwrap_err(),
+            "eth_getTransactionByHash failed: not found"
+        );
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn chunk_size_past_usize() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\n{}\r\n0\r\n\r\n";
+        assert_eq!(http_body(response).unwrap_err(), "malformed chunk size");
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    parser::{self, Instruction},
+    u256::U256,
+};
+
+// EIP-7702 delegation designator prefix, followed by a 20 byte address
+const DELEGATION_PREFIX: &[u8] = &[0xef, 0x01, 0x00];
+
+// Stubs are tiny, anything longer is treated as a regular contract.
+const MAX_STUB_SIZE: usize = 256;
+
+// Known precompile forwarders, `..` stands for the PUSH1/PUSH2 of the precompile address.
+const FORWARDERS: &[&str] = &[
+    // calldatacopy(0, 0, calldatasize()) pop(staticcall(gas(), p, 0, calldatasize(), 0, 0x20))
+    // return(0, 0x20)
+    "365f5f3760205f365f..5afa5060205ff3",
+    // same, returning the whole output
+    "365f5f375f5f365f..5afa503d5f5f3e3d5ff3",
+];
+
+const ENTRYPOINTS: &[(&str, &str)] = &[
+    ("v0.6", "5ff137d4b0fdcd49dca30c7cf57e578a026d2789"),
+    ("v0.7", "0000000071727de22e5e9d8baf0edac6f37da032"),
+    ("v0.8", "4337084d9e255ff0702461cf8895ce9e3b5ff108"),
+];
+
+// Code recognised as a whole. Empty code and delegations are described instead of
+// disassembled, stubs are disassembled with the shape as an annotation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Shape {
+    Empty,
+    // EIP-7702 account delegated to another contract
+    Delegation { address: String },
+    // forwards calldata to a precompile and returns its output
+    PrecompileForwarder { precompile: String },
+    // small ERC-4337 account stub hardcoding an EntryPoint
+    EntryPointStub { entrypoint: String, version: String },
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Shape::Empty => write!(f, "empty code"),
+            Shape::Delegation { address } => {
+                write!(
+                    f,
+                    "EIP-7702 delegation designator, delegates to {}",
+                    address
+                )
+            }
+            Shape::PrecompileForwarder { precompile } => {
+                write!(f, "precompile forwarder, calls precompile {}", precompile)
+            }
+            Shape::EntryPointStub {
+                entrypoint,
+                version,
+            } => write!(
+                f,
+                "ERC-4337 EntryPoint {} stub, entrypoint {}",
+                version, entrypoint
+            ),
+        }
+    }
+}
+
+pub fn detect_shape(bytes: &[u8]) -> Option<Shape> {
+    if bytes.is_empty() {
+        return Some(Shape::Empty);
+    }
+
+    if bytes.len() == DELEGATION_PREFIX.len() + 20 && bytes.starts_with(DELEGATION_PREFIX) {
+        return Some(Shape::Delegation {
+            address: format!("0x{}", hex::encode(&bytes[DELEGATION_PREFIX.len()..])),
+        });
+    }
+
+    if bytes.len() > MAX_STUB_SIZE {
+        return None;
+    }
+
+    detect_precompile_forwarder(bytes)
+        .or_else(|| detect_entrypoint_stub(&parser::parse_bytecode(bytes)))
+}
+
+// The whole code is one of the known forwarders
+fn detect_precompile_forwarder(bytes: &[u8]) -> Option<Shape> {
+    let code = hex::encode(bytes);
+
+    FORWARDERS.iter().find_map(|forwarder| {
+        let (before, after) = forwarder.split_once("..")?;
+        let push = code.strip_prefix(before)?.strip_suffix(after)?;
+        let address = match push.split_at_checked(2)? {
+            ("60", address) if address.len() == 2 => address,
+            ("61", address) if address.len() == 4 => address,
+            _ => return None,
+        };
+        let address = usize::from_str_radix(address, 16).ok()?;
+
+        // 0x01..0x11 are mainnet precompiles, 0x100 is the RIP-7212 P256 verifier
+        ((0x01..=0x11).contains(&address) || address == 0x100).then(|| Shape::PrecompileForwarder {
+            precompile: format!("0x{:02x}", address),
+        })
+    })
+}
+
+// Account letting only the EntryPoint in:
+// PUSH <entrypoint> CALLER EQ PUSH <dest> JUMPI, or CALLER first, at the start of the code
+fn detect_entrypoint_stub(instructions: &[Instruction]) -> Option<Shape> {
+    let [first, second, eq, dest, jumpi, ..] = instructions else {
+        return None;
+    };
+    let push = match (first.opcode, second.opcode) {
+        (0x33, _) => second,
+        (_, 0x33) => first,
+        _ => return None,
+    };
+    if !(0x60..=0x73).contains(&push.opcode)
+        || eq.opcode != 0x14
+        || !matches!(dest.opcode, 0x60 | 0x61)
+        || jumpi.opcode != 0x57
+    {
+        return None;
+    }
+
+    // PUSH20, or shorter with leading zero bytes dropped as the optimizer does
+    let value = U256::from_be_bytes(&push.data);
+    ENTRYPOINTS
+        .iter()
+        .find(|(_, address)| U256::from_be_bytes(&hex::decode(address).unwrap()) == value)
+        .map(|(version, address)| Shape::EntryPointStub {
+            entrypoint: format!("0x{}", address),
+            version: version.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delegation_designator() {
+        let code = hex::decode("ef0100bebebebebebebebebebebebebebebebebebebebe").unwrap();
+
+        assert_eq!(
+            detect_shape(&code),
+            Some(Shape::Delegation {
+                address: "0xbebebebebebebebebebebebebebebebebebebebe".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn precompile_forwarder() {
+        // CALLDATASIZE PUSH0 PUSH0 CALLDATACOPY
+        // PUSH1 0x20 PUSH0 CALLDATASIZE PUSH0 PUSH1 0x02 GAS STATICCALL
+        // POP PUSH1 0x20 PUSH0 RETURN
+        let code = hex::decode("365f5f3760205f365f60025afa5060205ff3").unwrap();
+
+        assert_eq!(
+            detect_shape(&code),
+            Some(Shape::PrecompileForwarder {
+                precompile: "0x02".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn entrypoint_stub() {
+        // PUSH20 <EntryPoint v0.7> CALLER EQ PUSH1 0x1d JUMPI PUSH0 PUSH0 REVERT
+        // JUMPDEST STOP
+        let code =
+            hex::decode("730000000071727de22e5e9d8baf0edac6f37da0323314601d575f5ffd5b00").unwrap();
+
+        assert_eq!(
+            detect_shape(&code),
+            Some(Shape::EntryPointStub {
+                entrypoint: "0x0000000071727de22e5e9d8baf0edac6f37da032".to_string(),
+                version: "v0.7".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn empty_code() {
+        assert_eq!(detect_shape(&[]), Some(Shape::Empty));
+    }
+
+    #[test]
+    fn ecrecover_caller_is_no_stub() {
+        // calldatacopy(0, 0, 0x80) pop(staticcall(gas(), 1, 0, 0x80, 0, 0x20))
+        // if iszero(eq(mload(0), caller())) { revert(0, 0) } stop()
+        let code = hex::decode("60805f5f3760205f60805f60015afa505f513314601a575f5ffd5b00").unwrap();
+
+        assert_eq!(detect_shape(&code), None);
+    }
+}
@@ -0,0 +1,143 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use crate::loader::SectionKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jump {
+    In,      // into a function
+    Out,     // return from a function
+    Regular, // loops, ifs, ...
+}
+
+impl Jump {
+    pub fn marker(&self) -> &'static str {
+        match self {
+            Jump::In => "[i]",
+            Jump::Out => "[o]",
+            Jump::Regular => "[-]",
+        }
+    }
+}
+
+// Source range an instruction was generated from, offsets are in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceRange {
+    pub start: usize,
+    pub length: usize,
+    pub file: Option<usize>, // none for compiler generated code
+    pub jump: Jump,
+}
+
+// solc source map, `s:l:f:j:m` entries separated by `;`, one per instruction.
+// Empty fields repeat the previous entry.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    pub entries: Vec<SourceRange>,
+}
+
+impl SourceMap {
+    pub fn parse(map: &str) -> Self {
+        let mut entries = Vec::new();
+        let mut current = SourceRange {
+            start: 0,
+            length: 0,
+            file: None,
+            jump: Jump::Regular,
+        };
+
+        for entry in map.split(';') {
+            let fields: Vec<&str> = entry.split(':').collect();
+            let field = |n: usize| fields.get(n).copied().filter(|f| !f.is_empty());
+
+            if let Some(start) = field(0).and_then(|s| s.parse().ok()) {
+                current.start = start;
+            }
+            if let Some(length) = field(1).and_then(|l| l.parse().ok()) {
+                current.length = length;
+            }
+            if let Some(file) = field(2).and_then(|f| f.parse::<i64>().ok()) {
+                current.file = usize::try_from(file).ok();
+            }
+            if let Some(jump) = field(3) {
+                current.jump = match jump {
+                    "i" => Jump::In,
+                    "o" => Jump::Out,
+                    _ => Jump::Regular,
+                };
+            }
+
+            entries.push(current);
+        }
+
+        SourceMap { entries }
+    }
+}
+
+pub struct SourceFile {
+    pub path: String,
+    pub content: Option<String>,
+}
+
+// Source maps per section and the files they refer to, by solc source id.
+#[derive(Default)]
+pub struct Sources {
+    pub maps: Vec<(SectionKind, SourceMap)>,
+    pub files: BTreeMap<usize, SourceFile>,
+}
+
+impl Sources {
+    pub fn map(&self, kind: SectionKind) -> Option<&SourceMap> {
+        self.maps
+            .iter()
+            .find(|(section, _)| *section == kind)
+            .map(|(_, map)| map)
+    }
+
+    // Read file contents, paths are tried relative to each root in order.
+    pub fn load_files(&mut self, roots: &[PathBuf]) {
+        for file in self.files.values_mut() {
+            if file.content.is_none() {
+                file.content = roots
+                    .iter()
+                    .find_map(|root| fs::read_to_string(root.join(&file.path)).ok());
+            }
+        }
+    }
+
+    // File path, line number and text of the first line of the range.
+    pub fn line(&self, range: &SourceRange) -> Option<(&str, usize, &str)> {
+        let file = self.files.get(&range.file?)?;
+        let content = file.content.as_ref()?;
+        if range.start > content.len() {
+            return None;
+        }
+
+        let before = &content.as_bytes()[..range.start];
+        let number = before.iter().filter(|&&b| b == b'\n').count() + 1;
+        let line_start = before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+
+        let text = content[line_start..].lines().next().unwrap_or_default();
+        Some((&file.path, number, text.trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressed_entries() {
+        let map = SourceMap::parse("0:10:0:-:0;;12:4::i;:::o;-1:-1:-1:-");
+
+        let starts: Vec<_> = map.entries.iter().map(|e| e.start).collect();
+        assert_eq!(starts, vec![0, 0, 12, 12, 12]);
+        assert_eq!(map.entries[2].length, 4);
+        assert_eq!(map.entries[2].file, Some(0));
+        assert_eq!(map.entries[2].jump, Jump::In);
+        assert_eq!(map.entries[3].jump, Jump::Out);
+        assert_eq!(map.entries[4].file, None);
+    }
+}
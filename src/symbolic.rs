@@ -0,0 +1,894 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use serde::{Serialize, Serializer};
+
+use crate::{
+    emulator::{describe_revert, evaluate, keccak},
+    loader::{Program, SectionKind},
+    parser::{self, Instruction, opcode_to_name},
+    u256::U256,
+};
+
+// Exploration bounds, paths cut short by them are counted as truncated.
+const MAX_PATHS: usize = 256;
+const MAX_STEPS: usize = 10_000; // per path
+const MAX_LOOP_VISITS: usize = 3; // times a path may go through the same JUMPI
+const MAX_MEMORY_READ: usize = 1 << 20; // bytes, larger reads and copies are unknown
+
+// Symbolic stack value. Calldata, callvalue, storage and other inputs are symbols, anything
+// computed from constants only is folded.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Const(U256),
+    CallData(Box<Expr>), // CALLDATALOAD at an offset
+    Storage(Box<Expr>),  // SLOAD of a slot not written before on the path
+    Keccak(Vec<Expr>),   // KECCAK256 of memory words
+    Env(u8),             // CALLVALUE, CALLER, TIMESTAMP, ...
+    Op(u8, Vec<Expr>),   // arithmetic on symbols, inputs top first
+    Unknown(u8, usize),  // output of an instruction that isn't modelled, by offset
+}
+
+impl Expr {
+    fn op(opcode: u8, inputs: Vec<Expr>) -> Expr {
+        let constants: Option<Vec<U256>> = inputs
+            .iter()
+            .map(|input| match input {
+                Expr::Const(value) => Some(*value),
+                _ => None,
+            })
+            .collect();
+
+        if let Some(value) = constants.and_then(|values| evaluate(opcode, &values)) {
+            return Expr::Const(value);
+        }
+
+        // identities the compilers leave around, mostly in ABI decoding
+        match (opcode, inputs.as_slice()) {
+            (0x14, [a, b]) if a == b => Expr::Const(U256::ONE),
+            (0x03 | 0x18, [a, b]) if a == b => Expr::Const(U256::ZERO),
+            (0x16, [a, Expr::Const(U256::MAX)]) | (0x16, [Expr::Const(U256::MAX), a]) => a.clone(),
+            // c + (x - c)
+            (0x01, [c, Expr::Op(0x03, sub)]) | (0x01, [Expr::Op(0x03, sub), c])
+                if matches!(c, Expr::Const(_)) && sub[1] == *c =>
+            {
+                sub[0].clone()
+            }
+            _ => Expr::Op(opcode, inputs),
+        }
+    }
+
+    // Condition that holds when this one doesn't.
+    pub fn negate(self) -> Expr {
+        match self {
+            Expr::Op(0x15, mut inputs) => inputs.remove(0),
+            other => Expr::op(0x15, vec![other]),
+        }
+    }
+
+    fn as_usize(&self) -> Option<usize> {
+        match self {
+            Expr::Const(value) => value.to_usize(),
+            _ => None,
+        }
+    }
+}
+
+// Names given to expressions when rendering, like state variables for storage slots.
+pub trait Names {
+    fn name(&self, expr: &Expr) -> Option<String>;
+}
+
+struct Plain;
+
+impl Names for Plain {
+    fn name(&self, _: &Expr) -> Option<String> {
+        None
+    }
+}
+
+impl Expr {
+    pub fn render(&self, names: &dyn Names) -> String {
+        if let Some(name) = names.name(self) {
+            return name;
+        }
+
+        let render = |expr: &Expr| expr.render(names);
+        match self {
+            Expr::Const(value) => value.to_string(),
+            Expr::CallData(offset) => format!("calldata[{}]", render(offset)),
+            Expr::Storage(slot) => format!("storage[{}]", render(slot)),
+            Expr::Keccak(words) => {
+                let words: Vec<String> = words.iter().map(render).collect();
+                format!("keccak({})", words.join(", "))
+            }
+            Expr::Env(opcode) => opcode_to_name(*opcode).to_lowercase(),
+            Expr::Op(0x15, inputs) => format!("!{}", render(&inputs[0])),
+            Expr::Op(0x19, inputs) => format!("~{}", render(&inputs[0])),
+            // shifts take the shift amount first
+            Expr::Op(0x1B, inputs) => format!("({} << {})", render(&inputs[1]), render(&inputs[0])),
+            Expr::Op(0x1C, inputs) => format!("({} >> {})", render(&inputs[1]), render(&inputs[0])),
+            Expr::Op(opcode, inputs) => {
+                let infix = match opcode {
+                    0x01 => "+",
+                    0x02 => "*",
+                    0x03 => "-",
+                    0x04 => "/",
+                    0x06 => "%",
+                    0x10 => "<",
+                    0x11 => ">",
+                    0x12 => "s<",
+                    0x13 => "s>",
+                    0x14 => "==",
+                    0x16 => "&",
+                    0x17 => "|",
+                    0x18 => "^",
+                    _ => {
+                        let inputs: Vec<String> = inputs.iter().map(render).collect();
+                        let name = opcode_to_name(*opcode).to_lowercase();
+                        return format!("{}({})", name, inputs.join(", "));
+                    }
+                };
+                format!("({} {} {})", render(&inputs[0]), infix, render(&inputs[1]))
+            }
+            Expr::Unknown(opcode, offset) => {
+                format!("{}@{:04x}", opcode_to_name(*opcode).to_lowercase(), offset)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(&Plain))
+    }
+}
+
+impl Serialize for Expr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Terminator {
+    Stop,
+    Return,
+    Revert,
+    SelfDestruct,
+    Invalid,
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Terminator::Stop => "stop",
+            Terminator::Return => "return",
+            Terminator::Revert => "revert",
+            Terminator::SelfDestruct => "selfdestruct",
+            Terminator::Invalid => "invalid",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A way through the code from the start to a terminator, with the conditions of every
+// JUMPI taken on the way. The first `dispatch` conditions lead to the function entry.
+#[derive(Serialize)]
+pub struct Path {
+    pub offset: usize,
+    pub terminator: Terminator,
+    pub data: Option<String>, // returned value or decoded revert reason
+    pub conditions: Vec<Expr>,
+    #[serde(skip)]
+    pub dispatch: usize,
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04x}: {}", self.offset, self.terminator)?;
+        if let Some(data) = &self.data {
+            write!(f, " {}", data)?;
+        }
+        for condition in &self.conditions[self.dispatch..] {
+            write!(f, "\n    if {}", condition)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+pub struct FunctionPaths {
+    pub selector: String,
+    pub signature: String,
+    pub paths: Vec<Path>,
+}
+
+#[derive(Serialize)]
+pub struct Exploration {
+    pub functions: Vec<FunctionPaths>,
+    pub shared: Vec<Path>, // ending before any function entry
+    pub truncated: usize,  // paths cut by the exploration bounds or an unresolved jump
+}
+
+impl fmt::Display for Exploration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let functions = self
+            .functions
+            .iter()
+            .map(|function| {
+                let name = format!("{} {}", function.selector, function.signature);
+                (name, &function.paths)
+            })
+            .chain([("dispatcher".to_string(), &self.shared)]);
+
+        for (name, paths) in functions {
+            writeln!(f, "{}", name)?;
+            for path in paths {
+                writeln!(f, "  {}", path)?;
+            }
+        }
+
+        if self.truncated > 0 {
+            writeln!(f, "; {} paths truncated", self.truncated)?;
+        }
+        Ok(())
+    }
+}
+
+// Bytes written to memory by one instruction.
+#[derive(Clone)]
+enum Bytes {
+    Word(Expr),
+    Raw(Vec<u8>),
+    Unknown,
+}
+
+#[derive(Clone)]
+struct Write {
+    offset: usize,
+    size: usize,
+    bytes: Bytes,
+}
+
+// Symbolic machine state of one path.
+#[derive(Clone)]
+pub struct State {
+    index: usize, // of the next instruction
+    stack: Vec<Expr>,
+    memory: Vec<Write>, // in order, later writes shadow earlier ones
+    storage: Vec<(Expr, Expr)>,
+    conditions: Vec<Expr>,
+    visits: HashMap<usize, usize>,
+    steps: usize,
+    function: Option<usize>,
+    dispatch: usize,
+    selector: Option<U256>, // assumed function selector
+}
+
+impl State {
+    fn pop(&mut self) -> Option<Expr> {
+        self.stack.pop()
+    }
+
+    fn write(&mut self, offset: &Expr, size: &Expr, bytes: Bytes) {
+        match (offset.as_usize(), size.as_usize()) {
+            (_, Some(0)) => {}
+            (Some(offset), Some(size)) => self.memory.push(Write {
+                offset,
+                size,
+                bytes,
+            }),
+            // anywhere in memory
+            _ => self.memory.push(Write {
+                offset: 0,
+                size: usize::MAX,
+                bytes: Bytes::Unknown,
+            }),
+        }
+    }
+
+    // Byte at `offset` if it's known, untouched memory is zero.
+    fn byte(&self, offset: usize) -> Option<u8> {
+        let write = self
+            .memory
+            .iter()
+            .rev()
+            .find(|w| w.offset <= offset && offset - w.offset < w.size);
+        let Some(write) = write else {
+            return Some(0);
+        };
+
+        let index = offset - write.offset;
+        match &write.bytes {
+            Bytes::Word(Expr::Const(value)) => Some(value.to_be_bytes()[index]),
+            Bytes::Raw(bytes) => Some(bytes.get(index).copied().unwrap_or(0)),
+            _ => None,
+        }
+    }
+
+    fn read_bytes(&self, offset: usize, size: usize) -> Option<Vec<u8>> {
+        (offset..offset.checked_add(size)?)
+            .map(|offset| self.byte(offset))
+            .collect()
+    }
+
+    // Word at `offset`, the stored value itself when the last write there was a full word.
+    fn read_word(&self, offset: usize, at: usize) -> Expr {
+        let Some(end) = offset.checked_add(32) else {
+            return Expr::Unknown(0x51, at);
+        };
+        let last = self
+            .memory
+            .iter()
+            .rev()
+            .find(|w| w.offset < end && offset - offset.min(w.offset) < w.size);
+        if let Some(Write {
+            offset: start,
+            bytes: Bytes::Word(value),
+            ..
+        }) = last
+            && *start == offset
+        {
+            return value.clone();
+        }
+
+        match self.read_bytes(offset, 32) {
+            Some(bytes) => Expr::Const(U256::from_be_bytes(&bytes)),
+            None => Expr::Unknown(0x51, at),
+        }
+    }
+
+    // Memory passed to a call or an event as words, calldata starts with a 4 byte selector.
+    fn read_words(&self, offset: &Expr, size: &Expr, at: usize) -> Vec<Expr> {
+        let Some((mut offset, size)) = memory_range(offset, size) else {
+            return vec![Expr::Unknown(0x51, at)];
+        };
+
+        let mut words = Vec::new();
+        if size % 32 == 4 {
+            words.push(match self.read_bytes(offset, 4) {
+                Some(bytes) => Expr::Const(U256::from_be_bytes(&bytes)),
+                None => Expr::Unknown(0x51, at),
+            });
+            offset += 4;
+        }
+        words.extend((0..size / 32).map(|i| self.read_word(offset + i * 32, at)));
+        words
+    }
+}
+
+// Where a path ends.
+#[derive(Clone, PartialEq)]
+pub struct Halt {
+    pub offset: usize,
+    pub terminator: Terminator,
+    pub values: Vec<Expr>, // words returned, or the SELFDESTRUCT beneficiary
+    pub reason: Option<String>, // decoded revert reason or constant data returned
+}
+
+// Instruction with an effect outside the machine state.
+#[derive(Clone, PartialEq)]
+pub enum Effect {
+    Store {
+        slot: Expr,
+        value: Expr,
+    },
+    Log {
+        topics: Vec<Expr>,
+        data: Vec<Expr>,
+    },
+    Call {
+        opcode: u8,
+        offset: usize,
+        target: Expr,
+        value: Option<Expr>,
+        data: Vec<Expr>,
+    },
+}
+
+pub enum Step {
+    Halt(Halt),
+    Fork {
+        offset: usize,
+        target: usize,
+        condition: Expr,
+    },
+    Effect(Effect),
+    Entry(usize), // first of the entries reached by the path
+}
+
+// Runs states of a code through the instructions between forks and effects.
+pub struct Executor<'a> {
+    instructions: &'a [Instruction],
+    code: &'a [u8],
+    entries: &'a [usize],
+    index_of: HashMap<usize, usize>,
+    jumpdests: HashSet<usize>,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new(instructions: &'a [Instruction], code: &'a [u8], entries: &'a [usize]) -> Self {
+        Executor {
+            instructions,
+            code,
+            entries,
+            index_of: instructions
+                .iter()
+                .enumerate()
+                .map(|(index, instruction)| (instruction.offset, index))
+                .collect(),
+            jumpdests: instructions
+                .iter()
+                .filter(|i| i.opcode == 0x5B)
+                .map(|i| i.offset)
+                .collect(),
+        }
+    }
+
+    // State at the first instruction, the dispatcher folds to constants when the
+    // selector is given.
+    pub fn start(&self, selector: Option<[u8; 4]>) -> State {
+        State {
+            index: 0,
+            stack: Vec::new(),
+            memory: Vec::new(),
+            storage: Vec::new(),
+            conditions: Vec::new(),
+            visits: HashMap::new(),
+            steps: 0,
+            function: None,
+            dispatch: 0,
+            selector: selector.map(|selector| U256::from_be_bytes(&selector)),
+        }
+    }
+
+    // Code offsets on the stack, the return addresses of the internal functions a state
+    // is in. A JUMPI reached again in the same context closes a loop.
+    pub fn return_addresses(&self, state: &State) -> Vec<usize> {
+        state
+            .stack
+            .iter()
+            .filter_map(|value| value.as_usize())
+            .filter(|offset| self.jumpdests.contains(offset))
+            .collect()
+    }
+
+    // Continue a forked state at the JUMPI target.
+    pub fn jump(&self, state: &mut State, target: usize) {
+        state.index = self.index_of[&target];
+    }
+
+    // Runs a state to the next step, None when the path can't be followed.
+    pub fn run(&self, state: &mut State) -> Option<Step> {
+        loop {
+            // running off the end of the code is a STOP
+            let Some(instruction) = self.instructions.get(state.index) else {
+                let offset = self
+                    .instructions
+                    .last()
+                    .map_or(0, |i| i.offset + 1 + i.data.len());
+                return Some(halt(offset, Terminator::Stop));
+            };
+            let offset = instruction.offset;
+            let opcode = instruction.opcode;
+
+            if state.function.is_none() && self.entries.contains(&offset) {
+                state.function = Some(offset);
+                state.dispatch = state.conditions.len();
+                return Some(Step::Entry(offset));
+            }
+            state.steps += 1;
+            if state.steps > MAX_STEPS {
+                return None;
+            }
+            state.index += 1;
+
+            match opcode {
+                0x80..=0x8F => {
+                    let depth = (opcode - 0x7F) as usize;
+                    let value = state
+                        .stack
+                        .get(state.stack.len().checked_sub(depth)?)?
+                        .clone();
+                    state.stack.push(value);
+                    continue;
+                }
+                0x90..=0x9F => {
+                    let depth = (opcode - 0x8F) as usize;
+                    let top = state.stack.len().checked_sub(1)?;
+                    state.stack.swap(top, top.checked_sub(depth)?);
+                    continue;
+                }
+                _ => {}
+            }
+
+            let (pops, pushes) = parser::stack_io(opcode);
+            if state.stack.len() < pops {
+                return None;
+            }
+            let inputs: Vec<Expr> = (0..pops).filter_map(|_| state.pop()).collect();
+
+            let output = match opcode {
+                0x00 => return Some(halt(offset, Terminator::Stop)),
+                // the selector read by the dispatcher, `calldata[0] >> 224`
+                0x1C if state.selector.is_some()
+                    && inputs[0] == Expr::Const(U256::from_u64(0xE0))
+                    && inputs[1] == Expr::CallData(Box::new(Expr::Const(U256::ZERO))) =>
+                {
+                    Expr::Const(state.selector.expect("checked"))
+                }
+                0x01..=0x0B | 0x10..=0x1D => Expr::op(opcode, inputs),
+                0x20 => hash(state, &inputs, offset),
+                0x30 | 0x32..=0x34 | 0x36 | 0x3A | 0x41..=0x48 | 0x4A => Expr::Env(opcode),
+                0x35 => Expr::CallData(Box::new(inputs[0].clone())),
+                0x38 => Expr::Const(U256::from_u64(self.code.len() as u64)),
+                0x39 => {
+                    let source = inputs[1].as_usize();
+                    let size = inputs[2].as_usize();
+                    let bytes = match (source, size) {
+                        (Some(source), Some(size)) if size <= MAX_MEMORY_READ => Bytes::Raw(
+                            (source..source.saturating_add(size))
+                                .map(|i| self.code.get(i).copied().unwrap_or(0))
+                                .collect(),
+                        ),
+                        _ => Bytes::Unknown,
+                    };
+                    state.write(&inputs[0], &inputs[2], bytes);
+                    continue;
+                }
+                0x37 | 0x3E | 0x5E => {
+                    state.write(&inputs[0], &inputs[2], Bytes::Unknown);
+                    continue;
+                }
+                0x3C => {
+                    state.write(&inputs[1], &inputs[3], Bytes::Unknown);
+                    continue;
+                }
+                0x51 => match inputs[0].as_usize() {
+                    Some(address) => state.read_word(address, offset),
+                    None => Expr::Unknown(opcode, offset),
+                },
+                0x52 => {
+                    let size = Expr::Const(U256::from_u64(32));
+                    state.write(&inputs[0], &size, Bytes::Word(inputs[1].clone()));
+                    continue;
+                }
+                0x53 => {
+                    let bytes = match &inputs[1] {
+                        Expr::Const(value) => Bytes::Raw(vec![value.to_be_bytes()[31]]),
+                        _ => Bytes::Unknown,
+                    };
+                    state.write(&inputs[0], &Expr::Const(U256::ONE), bytes);
+                    continue;
+                }
+                0x54 => state
+                    .storage
+                    .iter()
+                    .rev()
+                    .find(|(slot, _)| *slot == inputs[0])
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or_else(|| Expr::Storage(Box::new(inputs[0].clone()))),
+                0x55 => {
+                    state.storage.push((inputs[0].clone(), inputs[1].clone()));
+                    let [slot, value] = <[Expr; 2]>::try_from(inputs).ok()?;
+                    return Some(Step::Effect(Effect::Store { slot, value }));
+                }
+                0x56 => {
+                    let target = inputs[0]
+                        .as_usize()
+                        .filter(|t| self.jumpdests.contains(t))?;
+                    self.jump(state, target);
+                    continue;
+                }
+                0x57 => {
+                    let target = inputs[0]
+                        .as_usize()
+                        .filter(|t| self.jumpdests.contains(t))?;
+                    match &inputs[1] {
+                        Expr::Const(condition) => {
+                            if !condition.is_zero() {
+                                self.jump(state, target);
+                            }
+                            continue;
+                        }
+                        condition => {
+                            return Some(Step::Fork {
+                                offset,
+                                target,
+                                condition: condition.clone(),
+                            });
+                        }
+                    }
+                }
+                0x58 => Expr::Const(U256::from_u64(offset as u64)),
+                0x5F..=0x7F => Expr::Const(U256::from_be_bytes(&instruction.data)),
+                0xA0..=0xA4 => {
+                    let data = state.read_words(&inputs[0], &inputs[1], offset);
+                    let topics = inputs[2..].to_vec();
+                    return Some(Step::Effect(Effect::Log { topics, data }));
+                }
+                0xF3 | 0xFD => {
+                    let terminator = if opcode == 0xF3 {
+                        Terminator::Return
+                    } else {
+                        Terminator::Revert
+                    };
+                    return Some(Step::Halt(returned(
+                        state, &inputs[0], &inputs[1], terminator, offset,
+                    )));
+                }
+                0xFF => {
+                    return Some(Step::Halt(Halt {
+                        offset,
+                        terminator: Terminator::SelfDestruct,
+                        values: vec![inputs[0].clone()],
+                        reason: None,
+                    }));
+                }
+                0xF1 | 0xF2 | 0xF4 | 0xFA => {
+                    // CALL and CALLCODE send value, the others don't
+                    let (value, arguments) = if matches!(opcode, 0xF1 | 0xF2) {
+                        (Some(inputs[2].clone()), &inputs[3..])
+                    } else {
+                        (None, &inputs[2..])
+                    };
+                    let data = state.read_words(&arguments[0], &arguments[1], offset);
+                    // return data is copied into memory
+                    state.write(&arguments[2], &arguments[3], Bytes::Unknown);
+                    state.stack.push(Expr::Unknown(opcode, offset));
+                    return Some(Step::Effect(Effect::Call {
+                        opcode,
+                        offset,
+                        target: inputs[1].clone(),
+                        value,
+                        data,
+                    }));
+                }
+                _ if parser::is_terminator(opcode) => {
+                    return Some(halt(offset, Terminator::Invalid));
+                }
+                _ => Expr::Unknown(opcode, offset),
+            };
+
+            if pushes == 1 {
+                state.stack.push(output);
+            }
+        }
+    }
+}
+
+// Explore the runtime code of a program from its first instruction, once per function
+// with its selector in the calldata, so dispatchers jumping through tables reach it, and
+// once without for the paths going through no function.
+pub fn explore_program(program: &Program, selectors: &HashMap<u32, String>) -> Exploration {
+    let (instructions, code) = runtime_code(program);
+    let mut truncated = 0;
+
+    let functions: Vec<FunctionPaths> = program
+        .entrypoints
+        .iter()
+        .map(|entry| {
+            let (paths, skipped) =
+                explore(instructions, &code, &[entry.offset], Some(entry.selector));
+            truncated += skipped;
+            FunctionPaths {
+                selector: format!("0x{}", hex::encode(entry.selector)),
+                signature: entry.signature(selectors).0,
+                paths: paths
+                    .into_iter()
+                    .filter(|(function, _)| function.is_some())
+                    .map(|(_, path)| path)
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let entries: Vec<usize> = program.entrypoints.iter().map(|e| e.offset).collect();
+    let (paths, skipped) = explore(instructions, &code, &entries, None);
+    truncated += skipped;
+    let shared = paths
+        .into_iter()
+        .filter(|(function, _)| function.is_none())
+        .map(|(_, path)| path)
+        .collect();
+
+    Exploration {
+        functions,
+        shared,
+        truncated,
+    }
+}
+
+// Runtime instructions and the code as deployed, CODECOPY can read the data and metadata
+// sections too.
+pub fn runtime_code(program: &Program) -> (&[Instruction], Vec<u8>) {
+    let start = program
+        .sections
+        .iter()
+        .position(|section| matches!(section.kind, SectionKind::Runtime | SectionKind::Raw));
+    let code: Vec<u8> = start
+        .map(|start| {
+            program.sections[start..]
+                .iter()
+                .flat_map(|section| section.raw_bytes.iter().copied())
+                .collect()
+        })
+        .unwrap_or_default();
+    let instructions: &[Instruction] = start
+        .and_then(|start| program.sections[start].instructions.as_deref())
+        .unwrap_or_default();
+
+    (instructions, code)
+}
+
+// Paths from the first instruction to each reachable terminator, tagged with the first of
+// `entries` they go through, and the number of paths given up on. The calldata starts
+// with `selector` when given.
+pub fn explore(
+    instructions: &[Instruction],
+    code: &[u8],
+    entries: &[usize],
+    selector: Option<[u8; 4]>,
+) -> (Vec<(Option<usize>, Path)>, usize) {
+    let executor = Executor::new(instructions, code, entries);
+    let mut paths = Vec::new();
+    let mut truncated = 0;
+    let mut queue = vec![executor.start(selector)];
+
+    while let Some(mut state) = queue.pop() {
+        if paths.len() + truncated >= MAX_PATHS {
+            truncated += 1;
+            continue;
+        }
+
+        let step = loop {
+            match executor.run(&mut state) {
+                Some(Step::Effect(_) | Step::Entry(_)) => continue,
+                step => break step,
+            }
+        };
+
+        match step {
+            Some(Step::Halt(halt)) => {
+                let data = halt.reason.or_else(|| {
+                    let values: Vec<String> = halt.values.iter().map(|v| v.to_string()).collect();
+                    (!values.is_empty()).then(|| values.join(", "))
+                });
+                let path = Path {
+                    offset: halt.offset,
+                    terminator: halt.terminator,
+                    data,
+                    conditions: state.conditions,
+                    dispatch: state.dispatch,
+                };
+                paths.push((state.function, path));
+            }
+            Some(Step::Fork {
+                offset,
+                target,
+                condition,
+            }) => {
+                let visits = state.visits.entry(offset).or_insert(0);
+                *visits += 1;
+                if *visits > MAX_LOOP_VISITS {
+                    truncated += 1;
+                    continue;
+                }
+
+                let mut jumped = state.clone();
+                executor.jump(&mut jumped, target);
+                jumped.conditions.push(condition.clone());
+                state.conditions.push(condition.negate());
+                queue.push(state);
+                queue.push(jumped);
+            }
+            _ => truncated += 1,
+        }
+    }
+
+    (paths, truncated)
+}
+
+fn halt(offset: usize, terminator: Terminator) -> Step {
+    Step::Halt(Halt {
+        offset,
+        terminator,
+        values: Vec::new(),
+        reason: None,
+    })
+}
+
+// Known memory range small enough to be read, bigger ones are left unknown.
+fn memory_range(offset: &Expr, size: &Expr) -> Option<(usize, usize)> {
+    let (offset, size) = (offset.as_usize()?, size.as_usize()?);
+    offset.checked_add(size)?;
+    (size <= MAX_MEMORY_READ).then_some((offset, size))
+}
+
+// KECCAK256 of constant memory is folded, words holding symbols are kept as a hash of
+// symbols, like mapping slots.
+fn hash(state: &State, inputs: &[Expr], at: usize) -> Expr {
+    let Some((offset, size)) = memory_range(&inputs[0], &inputs[1]) else {
+        return Expr::Unknown(0x20, at);
+    };
+
+    if let Some(bytes) = state.read_bytes(offset, size) {
+        return Expr::Const(U256::from_be_bytes(&keccak(&bytes)));
+    }
+    if size % 32 != 0 {
+        return Expr::Unknown(0x20, at);
+    }
+
+    Expr::Keccak(
+        (offset..offset + size)
+            .step_by(32)
+            .map(|offset| state.read_word(offset, at))
+            .collect(),
+    )
+}
+
+// RETURN or REVERT with the memory returned, decoded when it's constant.
+fn returned(state: &State, offset: &Expr, size: &Expr, terminator: Terminator, at: usize) -> Halt {
+    let mut halt = Halt {
+        offset: at,
+        terminator,
+        values: Vec::new(),
+        reason: None,
+    };
+    let Some((offset, size)) = memory_range(offset, size) else {
+        return halt;
+    };
+    if size == 0 {
+        return halt;
+    }
+
+    if let Some(bytes) = state.read_bytes(offset, size) {
+        let reason = (terminator == Terminator::Revert)
+            .then(|| describe_revert(&bytes))
+            .flatten();
+        halt.reason = Some(reason.unwrap_or_else(|| format!("0x{}", hex::encode(bytes))));
+    } else if size % 32 == 0 {
+        halt.values = (offset..offset + size)
+            .step_by(32)
+            .map(|offset| state.read_word(offset, at))
+            .collect();
+    }
+    halt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_bytecode;
+
+    #[test]
+    fn revert_unless_caller_is_owner() {
+        // if (caller != sload(0)) revert; return calldataload(4) + 1
+        let code = hex::decode("5f543314600a575f5ffd5b60016004350160405260206040f3").unwrap();
+        let (paths, truncated) = explore(&parse_bytecode(&code), &code, &[], None);
+        assert_eq!(truncated, 0);
+
+        let found: Vec<String> = paths.iter().map(|(_, path)| path.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "0018: return (calldata[0x4] + 0x1)\n    if (caller == storage[0x0])",
+                "0009: revert\n    if !(caller == storage[0x0])",
+            ]
+        );
+    }
+
+    #[test]
+    fn huge_memory_accesses() {
+        // MSTORE(0, 1) MLOAD(2^64 - 1) STOP, then RETURN(0, 2^40)
+        for code in ["600160005267ffffffffffffffff5100", "64ffffffffff5ff3"] {
+            let code = hex::decode(code).unwrap();
+            let (paths, _) = explore(&parse_bytecode(&code), &code, &[], None);
+            assert_eq!(paths.len(), 1);
+        }
+    }
+}
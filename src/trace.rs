@@ -0,0 +1,174 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use serde_json::Value;
+
+use crate::{emulator::Execution, parser::Instruction, u256::U256};
+
+pub struct TraceStep {
+    pub pc: usize,
+    pub depth: usize,     // call depth, 1 for the traced contract
+    pub stack: Vec<U256>, // top first
+}
+
+// Outcomes of a JUMPI over a trace.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Branch {
+    pub jumped: usize,
+    pub fell_through: usize,
+}
+
+// Executed instructions of a transaction, from `debug_traceTransaction` or `scent run`.
+pub struct Trace {
+    pub steps: Vec<TraceStep>,
+}
+
+impl Trace {
+    // structLogs from `debug_traceTransaction`, as the RPC response, its result or the bare
+    // list, or the JSON printed by `scent run --trace --json`.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let json: Value =
+            serde_json::from_str(text).map_err(|e| format!("invalid trace JSON: {}", e))?;
+        let json = json.get("result").unwrap_or(&json);
+
+        if let Some(steps) = json.get("steps").and_then(Value::as_array) {
+            return steps
+                .iter()
+                .map(|step| parse_step(step, false))
+                .collect::<Result<_, _>>()
+                .map(|steps| Trace { steps });
+        }
+
+        let logs = json
+            .get("structLogs")
+            .unwrap_or(json)
+            .as_array()
+            .ok_or("trace has no structLogs")?;
+
+        // geth lists the stack bottom first
+        logs.iter()
+            .map(|step| parse_step(step, true))
+            .collect::<Result<_, _>>()
+            .map(|steps| Trace { steps })
+    }
+
+    pub fn from_execution(execution: &Execution) -> Self {
+        Trace {
+            steps: execution
+                .steps
+                .iter()
+                .map(|step| TraceStep {
+                    pc: step.pc,
+                    depth: 1,
+                    stack: step.stack.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    // Steps of the traced contract, nested calls run other code.
+    pub fn top_level(&self) -> impl Iterator<Item = &TraceStep> {
+        let depth = self.steps.iter().map(|step| step.depth).min().unwrap_or(1);
+        self.steps.iter().filter(move |step| step.depth == depth)
+    }
+
+    // Times each instruction was executed, by offset.
+    pub fn hits(&self) -> HashMap<usize, usize> {
+        let mut hits = HashMap::new();
+        for step in self.top_level() {
+            *hits.entry(step.pc).or_insert(0) += 1;
+        }
+        hits
+    }
+
+    // JUMPI outcomes by offset, the condition is right below the target on the stack.
+    pub fn branches(&self, instructions: &[Instruction]) -> HashMap<usize, Branch> {
+        let jumpis: HashSet<usize> = instructions
+            .iter()
+            .filter(|i| i.opcode == 0x57)
+            .map(|i| i.offset)
+            .collect();
+
+        let mut branches: HashMap<usize, Branch> = HashMap::new();
+        for step in self.top_level().filter(|step| jumpis.contains(&step.pc)) {
+            let branch = branches.entry(step.pc).or_default();
+            match step.stack.get(1) {
+                Some(condition) if !condition.is_zero() => branch.jumped += 1,
+                Some(_) => branch.fell_through += 1,
+                None => {}
+            }
+        }
+        branches
+    }
+
+    // Traces of several transactions counted as one.
+    pub fn merge(traces: Vec<Trace>) -> Self {
+        Trace {
+            steps: traces.into_iter().flat_map(|trace| trace.steps).collect(),
+        }
+    }
+}
+
+pub fn read_trace(path: &Path) -> Result<Trace, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    Trace::from_json(&text)
+}
+
+fn parse_step(step: &Value, bottom_first: bool) -> Result<TraceStep, String> {
+    let pc = step["pc"].as_u64().ok_or("trace step without pc")? as usize;
+    let depth = step["depth"].as_u64().unwrap_or(1) as usize;
+
+    let mut stack = step["stack"]
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .map(|item| {
+                    let item = item.as_str().ok_or("invalid stack item")?;
+                    // older geth versions print words without the 0x prefix
+                    let item = match item.strip_prefix("0x") {
+                        Some(_) => item.to_string(),
+                        None => format!("0x{}", item),
+                    };
+                    item.parse::<U256>()
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+    if bottom_first {
+        stack.reverse();
+    }
+
+    Ok(TraceStep { pc, depth, stack })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn struct_logs_of_nested_call() {
+        let trace = Trace::from_json(
+            r#"{"jsonrpc":"2.0","id":1,"result":{"gas":21000,"failed":true,"returnValue":"",
+            "structLogs":[
+                {"pc":0,"op":"PUSH1","gas":100,"gasCost":3,"depth":1,"stack":[]},
+                {"pc":2,"op":"PUSH1","gas":97,"gasCost":3,"depth":1,"stack":["0x80"]},
+                {"pc":0,"op":"STOP","gas":90,"gasCost":0,"depth":2,"stack":[]},
+                {"pc":2,"op":"PUSH1","gas":94,"gasCost":3,"depth":1,"stack":["0x80","0x0000000000000000000000000000000000000000000000000000000000000040"]}
+            ]}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(trace.steps.len(), 4);
+        assert_eq!(trace.hits(), HashMap::from([(0, 1), (2, 2)]));
+        assert_eq!(
+            trace.steps[3].stack,
+            vec![U256::from_u64(0x40), U256::from_u64(0x80)]
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    ops::{BitAnd, BitOr, BitXor, Not},
+    str::FromStr,
+};
+
+use serde::Serialize;
+
+// 256-bit EVM word, little-endian 64-bit limbs. Arithmetic wraps like the EVM's.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct U256([u64; 4]);
+
+impl U256 {
+    pub const ZERO: U256 = U256([0; 4]);
+    pub const ONE: U256 = U256([1, 0, 0, 0]);
+    pub const MAX: U256 = U256([u64::MAX; 4]);
+
+    pub fn from_u64(value: u64) -> Self {
+        U256([value, 0, 0, 0])
+    }
+
+    // Big-endian bytes, shorter inputs are left padded, longer ones keep the low 32 bytes.
+    pub fn from_be_bytes(bytes: &[u8]) -> Self {
+        let mut padded = [0u8; 32];
+        let bytes = &bytes[bytes.len().saturating_sub(32)..];
+        padded[32 - bytes.len()..].copy_from_slice(bytes);
+
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let start = 32 - (i + 1) * 8;
+            *limb = u64::from_be_bytes(padded[start..start + 8].try_into().expect("8 bytes"));
+        }
+        U256(limbs)
+    }
+
+    pub fn to_be_bytes(self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in self.0.iter().enumerate() {
+            let start = 32 - (i + 1) * 8;
+            bytes[start..start + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    // Value as usize when it fits, for offsets and sizes.
+    pub fn to_usize(self) -> Option<usize> {
+        if self.0[1..].iter().any(|&limb| limb != 0) {
+            return None;
+        }
+        usize::try_from(self.0[0]).ok()
+    }
+
+    pub fn is_zero(self) -> bool {
+        self == U256::ZERO
+    }
+
+    fn bit(self, index: usize) -> bool {
+        self.0[index / 64] >> (index % 64) & 1 == 1
+    }
+
+    fn bits(self) -> usize {
+        (0..4)
+            .rev()
+            .find(|&i| self.0[i] != 0)
+            .map_or(0, |i| i * 64 + 64 - self.0[i].leading_zeros() as usize)
+    }
+
+    pub fn overflowing_add(self, other: U256) -> (U256, bool) {
+        let mut limbs = [0u64; 4];
+        let mut carry = false;
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let (sum, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = c1 || c2;
+        }
+        (U256(limbs), carry)
+    }
+
+    pub fn wrapping_add(self, other: U256) -> U256 {
+        self.overflowing_add(other).0
+    }
+
+    pub fn wrapping_sub(self, other: U256) -> U256 {
+        self.wrapping_add(other.wrapping_neg())
+    }
+
+    pub fn wrapping_neg(self) -> U256 {
+        (!self).wrapping_add(U256::ONE)
+    }
+
+    pub fn wrapping_mul(self, other: U256) -> U256 {
+        let mut limbs = [0u64; 4];
+        for i in 0..4 {
+            let mut carry = 0u128;
+            for j in 0..4 - i {
+                let product = self.0[i] as u128 * other.0[j] as u128 + limbs[i + j] as u128 + carry;
+                limbs[i + j] = product as u64;
+                carry = product >> 64;
+            }
+        }
+        U256(limbs)
+    }
+
+    // Quotient and remainder, none when dividing by zero.
+    pub fn div_rem(self, divisor: U256) -> Option<(U256, U256)> {
+        if divisor.is_zero() {
+            return None;
+        }
+
+        let mut quotient = U256::ZERO;
+        let mut remainder = U256::ZERO;
+        for i in (0..self.bits()).rev() {
+            remainder = remainder.shift_left(1);
+            remainder.0[0] |= self.bit(i) as u64;
+            if remainder >= divisor {
+                remainder = remainder.wrapping_sub(divisor);
+                quotient.0[i / 64] |= 1 << (i % 64);
+            }
+        }
+        Some((quotient, remainder))
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.bit(255)
+    }
+
+    fn abs(self) -> U256 {
+        if self.is_negative() {
+            self.wrapping_neg()
+        } else {
+            self
+        }
+    }
+
+    // Signed division and remainder, the remainder takes the sign of the dividend.
+    pub fn signed_div_rem(self, divisor: U256) -> Option<(U256, U256)> {
+        let (quotient, remainder) = self.abs().div_rem(divisor.abs())?;
+        let quotient = if self.is_negative() != divisor.is_negative() {
+            quotient.wrapping_neg()
+        } else {
+            quotient
+        };
+        let remainder = if self.is_negative() {
+            remainder.wrapping_neg()
+        } else {
+            remainder
+        };
+        Some((quotient, remainder))
+    }
+
+    // (self + other) % modulus without losing the carry, zero for a zero modulus.
+    pub fn add_mod(self, other: U256, modulus: U256) -> U256 {
+        let Some((_, a)) = self.div_rem(modulus) else {
+            return U256::ZERO;
+        };
+        let (_, b) = other.div_rem(modulus).expect("non zero modulus");
+
+        let (sum, carry) = a.overflowing_add(b);
+        if carry || sum >= modulus {
+            sum.wrapping_sub(modulus)
+        } else {
+            sum
+        }
+    }
+
+    pub fn mul_mod(self, other: U256, modulus: U256) -> U256 {
+        let Some((_, a)) = self.div_rem(modulus) else {
+            return U256::ZERO;
+        };
+
+        let mut result = U256::ZERO;
+        for i in (0..other.bits()).rev() {
+            result = result.add_mod(result, modulus);
+            if other.bit(i) {
+                result = result.add_mod(a, modulus);
+            }
+        }
+        result
+    }
+
+    pub fn wrapping_pow(self, exponent: U256) -> U256 {
+        let mut result = U256::ONE;
+        for i in (0..exponent.bits()).rev() {
+            result = result.wrapping_mul(result);
+            if exponent.bit(i) {
+                result = result.wrapping_mul(self);
+            }
+        }
+        result
+    }
+
+    // SIGNEXTEND, extends the sign bit of byte `byte` counting from the least significant.
+    pub fn sign_extend(self, byte: U256) -> U256 {
+        let Some(byte) = byte.to_usize().filter(|&byte| byte < 31) else {
+            return self;
+        };
+        let sign_bit = byte * 8 + 7;
+        let mask = U256::ONE.shift_left(sign_bit + 1).wrapping_sub(U256::ONE);
+        if self.bit(sign_bit) {
+            self | !mask
+        } else {
+            self & mask
+        }
+    }
+
+    pub fn signed_cmp(self, other: U256) -> Ordering {
+        match (self.is_negative(), other.is_negative()) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => self.cmp(&other),
+        }
+    }
+
+    // BYTE, `index` counts from the most significant byte.
+    pub fn byte(self, index: U256) -> U256 {
+        match index.to_usize().filter(|&index| index < 32) {
+            Some(index) => U256::from_u64(self.to_be_bytes()[index] as u64),
+            None => U256::ZERO,
+        }
+    }
+
+    pub fn shift_left(self, shift: usize) -> U256 {
+        if shift >= 256 {
+            return U256::ZERO;
+        }
+        let (limbs, bits) = (shift / 64, shift % 64);
+        U256(std::array::from_fn(|i| {
+            let Some(source) = i.checked_sub(limbs) else {
+                return 0;
+            };
+            let carried = match source.checked_sub(1) {
+                Some(lower) if bits > 0 => self.0[lower] >> (64 - bits),
+                _ => 0,
+            };
+            self.0[source] << bits | carried
+        }))
+    }
+
+    pub fn shift_right(self, shift: usize) -> U256 {
+        if shift >= 256 {
+            return U256::ZERO;
+        }
+        let (limbs, bits) = (shift / 64, shift % 64);
+        U256(std::array::from_fn(|i| {
+            let source = i + limbs;
+            if source >= 4 {
+                return 0;
+            }
+            let carried = match self.0.get(source + 1) {
+                Some(upper) if bits > 0 => upper << (64 - bits),
+                _ => 0,
+            };
+            self.0[source] >> bits | carried
+        }))
+    }
+
+    // SAR, shifts in the sign bit.
+    pub fn arithmetic_shift_right(self, shift: usize) -> U256 {
+        if !self.is_negative() {
+            return self.shift_right(shift);
+        }
+        if shift >= 256 {
+            return U256::MAX;
+        }
+        self.shift_right(shift) | !(U256::MAX.shift_right(shift))
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl BitAnd for U256 {
+    type Output = U256;
+
+    fn bitand(self, other: U256) -> U256 {
+        U256(std::array::from_fn(|i| self.0[i] & other.0[i]))
+    }
+}
+
+impl BitOr for U256 {
+    type Output = U256;
+
+    fn bitor(self, other: U256) -> U256 {
+        U256(std::array::from_fn(|i| self.0[i] | other.0[i]))
+    }
+}
+
+impl BitXor for U256 {
+    type Output = U256;
+
+    fn bitxor(self, other: U256) -> U256 {
+        U256(std::array::from_fn(|i| self.0[i] ^ other.0[i]))
+    }
+}
+
+impl Not for U256 {
+    type Output = U256;
+
+    fn not(self) -> U256 {
+        U256(std::array::from_fn(|i| !self.0[i]))
+    }
+}
+
+// Hex without leading zeroes.
+impl fmt::Display for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = hex::encode(self.to_be_bytes());
+        let trimmed = hex.trim_start_matches('0');
+        write!(f, "0x{}", if trimmed.is_empty() { "0" } else { trimmed })
+    }
+}
+
+// Decimal, or hex with a `0x` prefix.
+impl FromStr for U256 {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid number {}", value);
+
+        if let Some(hex) = value.strip_prefix("0x") {
+            if hex.is_empty() || hex.len() > 64 {
+                return Err(invalid());
+            }
+            let padded = format!("{:0>64}", hex);
+            let bytes = hex::decode(padded).map_err(|_| invalid())?;
+            return Ok(U256::from_be_bytes(&bytes));
+        }
+
+        if value.is_empty() {
+            return Err(invalid());
+        }
+        value.chars().try_fold(U256::ZERO, |acc, c| {
+            let digit = c.to_digit(10).ok_or_else(invalid)?;
+            let ten = acc.wrapping_mul(U256::from_u64(10));
+            let (sum, carry) = ten.overflowing_add(U256::from_u64(digit as u64));
+            if carry || ten.div_rem(U256::from_u64(10)) != Some((acc, U256::ZERO)) {
+                return Err(invalid());
+            }
+            Ok(sum)
+        })
+    }
+}
+
+impl Serialize for U256 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(value: &str) -> U256 {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(U256::MAX.wrapping_add(U256::ONE), U256::ZERO);
+        assert_eq!(U256::ZERO.wrapping_sub(U256::ONE), U256::MAX);
+        assert_eq!(
+            n("0x100000000000000000").wrapping_mul(n("0x100000000000000000")),
+            n("0x10000000000000000000000000000000000")
+        );
+        assert_eq!(
+            n("1000000000000000000000").div_rem(n("7")),
+            Some((n("142857142857142857142"), n("6")))
+        );
+        assert_eq!(
+            U256::MAX.signed_div_rem(n("2")),
+            Some((U256::ZERO, U256::MAX))
+        );
+        assert_eq!(U256::MAX.mul_mod(U256::MAX, n("12")), n("9"));
+        assert_eq!(n("2").wrapping_pow(n("255")).shift_right(255), U256::ONE);
+        assert_eq!(n("0xff").sign_extend(U256::ZERO), U256::MAX);
+        assert_eq!(U256::MAX.arithmetic_shift_right(8), U256::MAX);
+        assert_eq!(n("0x1234").byte(n("30")), n("0x12"));
+        assert_eq!(n("0x1234").shift_left(70), n("0x48d000000000000000000"));
+        assert_eq!(n("0x1234").shift_left(70).shift_right(70), n("0x1234"));
+        assert_eq!(n("0x1234").to_string(), "0x1234");
+    }
+}
@@ -1,6 +1,19 @@
 use std::{collections::HashMap, fmt};
 
-use crate::{loader::Program, loader::SectionKind, parser::Instruction};
+use crate::{
+    cfg::{Cfg, Slot},
+    input::StorageVariable,
+    interfaces::detect_interfaces,
+    loader::Program,
+    loader::SectionKind,
+    parser::Instruction,
+    source::Sources,
+    trace::Trace,
+    u256::U256,
+};
+
+// stack values shown for each executed instruction
+const TRACE_STACK_DEPTH: usize = 4;
 
 pub struct View {
     pub lines: Vec<Line>,
@@ -10,12 +23,14 @@ pub struct Line {
     pub offset: usize,
     pub kind: LineKind,
     pub comment: Option<String>,
+    pub executed: bool, // on the path of an overlaid trace
 }
 
 pub enum LineKind {
     Label(String), // for ".runtime", "label_0011:", etc.
     Instruction(Instruction),
     HexDump(Vec<u8>), // for metadata or unknown blobs
+    Comment(String),  // standalone "; ..." line, e.g. program summary
     Blank,            // spacing
 }
 
@@ -24,84 +39,253 @@ impl View {
         program: &Program,
         decorated: bool,
         selectors: HashMap<u32, String>,
+        storage: &[StorageVariable],
+        sources: &Sources,
+        trace: Option<&Trace>,
     ) -> Self {
         let mut lines = Vec::new();
+        push_program(
+            &mut lines, program, "", decorated, &selectors, storage, sources, trace,
+        );
+        Self { lines }
+    }
+}
+
+// Appends the listing of a program, children embedded by factories are listed after
+// the section they are copied from, with labels prefixed by their parent section. A trace
+// is overlaid on the executed code, the runtime section or the whole input.
+#[allow(clippy::too_many_arguments)]
+fn push_program(
+    lines: &mut Vec<Line>,
+    program: &Program,
+    prefix: &str,
+    decorated: bool,
+    selectors: &HashMap<u32, String>,
+    storage: &[StorageVariable],
+    sources: &Sources,
+    trace: Option<&Trace>,
+) {
+    let comment = |text: String| Line {
+        offset: 0,
+        kind: LineKind::Comment(text),
+        comment: None,
+        executed: false,
+    };
+
+    if let Some(shape) = &program.shape {
+        lines.push(comment(shape.to_string()));
+    }
+
+    if let Some(compiler) = &program.compiler {
+        lines.push(comment(format!("compiler: {}", compiler)));
+    }
+
+    if let Some(proxy) = &program.proxy {
+        lines.push(comment(format!("proxy: {}", proxy)));
+    }
+
+    let interfaces: Vec<String> = detect_interfaces(program)
+        .iter()
+        .map(|m| m.to_string())
+        .collect();
+    if !interfaces.is_empty() {
+        lines.push(comment(format!("interfaces: {}", interfaces.join(", "))));
+    }
 
-        for section in &program.sections {
-            match section.kind {
-                SectionKind::Init => {
+    for section in &program.sections {
+        if !matches!(section.kind, SectionKind::Raw) {
+            lines.push(Line {
+                offset: section.start_pc,
+                kind: LineKind::Label(format!("{}.{}", prefix, section.kind.name())),
+                comment: None,
+                executed: false,
+            });
+        }
+
+        if let Some(instructions) = &section.instructions {
+            let slots = if decorated && !storage.is_empty() {
+                storage_slots(instructions)
+            } else {
+                HashMap::new()
+            };
+
+            let map = sources.map(section.kind);
+            let trace =
+                trace.filter(|_| matches!(section.kind, SectionKind::Runtime | SectionKind::Raw));
+            let hits = trace.map(Trace::hits).unwrap_or_default();
+            let last_stack: HashMap<usize, &[U256]> = trace
+                .map(|trace| {
+                    trace
+                        .top_level()
+                        .map(|step| (step.pc, step.stack.as_slice()))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let branches = trace
+                .map(|trace| trace.branches(instructions))
+                .unwrap_or_default();
+            let mut last_line = None;
+
+            for (index, instruction) in instructions.iter().enumerate() {
+                let mut comment = None;
+                let range = map.and_then(|map| map.entries.get(index));
+
+                // originating source line above each run of instructions
+                if let Some(range) = range
+                    && let Some((path, number, text)) = sources.line(range)
+                    && last_line != Some((range.file, number))
+                {
+                    last_line = Some((range.file, number));
                     lines.push(Line {
-                        offset: section.start_pc,
-                        kind: LineKind::Label(".init".to_string()),
+                        offset: section.start_pc + instruction.offset,
+                        kind: LineKind::Comment(format!("{}:{}: {}", path, number, text)),
                         comment: None,
+                        executed: false,
                     });
                 }
-                SectionKind::Runtime => {
-                    lines.push(Line {
-                        offset: section.start_pc,
-                        kind: LineKind::Label(".runtime".to_string()),
-                        comment: None,
-                    });
+
+                if decorated && !instruction.data.is_empty() {
+                    comment = decorate_push_data(&instruction.data, selectors)
+                }
+
+                // SLOAD, SSTORE of a known state variable
+                if let Some(slot) = slots.get(&instruction.offset) {
+                    let variables: Vec<String> = storage
+                        .iter()
+                        .filter(|variable| variable.slot == *slot)
+                        .map(|variable| format!("{} ({})", variable.label, variable.type_name))
+                        .collect();
+                    if !variables.is_empty() {
+                        comment = Some(variables.join(", "));
+                    }
+                }
+
+                // PUSH32 placeholder patched by the constructor
+                if matches!(section.kind, SectionKind::Runtime)
+                    && let Some(immutable) = program
+                        .immutables
+                        .iter()
+                        .find(|i| i.offsets.contains(&instruction.offset))
+                {
+                    comment = Some(immutable.to_string());
                 }
-                SectionKind::Metadata => {
+
+                // function entry, a JUMPDEST unless the body follows the dispatcher check
+                if let Some(entry) = program
+                    .entrypoints
+                    .iter()
+                    .find(|f| instruction.offset == f.offset)
+                {
+                    let comment = if decorated {
+                        let (signature, _) = entry.signature(selectors);
+                        format!("{} [{}]", signature, entry.mutability)
+                    } else {
+                        format!("[{}]", entry.mutability)
+                    };
+
                     lines.push(Line {
-                        offset: section.start_pc,
-                        kind: LineKind::Label(".metadata".to_string()),
-                        comment: None,
+                        offset: section.start_pc + instruction.offset,
+                        kind: LineKind::Label(format!("0x{}", hex::encode(entry.selector))),
+                        comment: Some(comment),
+                        executed: false,
                     });
                 }
-                SectionKind::Raw => {} // fallthrough
-            }
 
-            if let Some(instructions) = &section.instructions {
-                for instruction in instructions {
-                    let mut comment = None;
+                // JUMP or JUMPI into or out of a function
+                if matches!(instruction.opcode, 0x56 | 0x57)
+                    && let Some(range) = range
+                {
+                    let marker = range.jump.marker();
+                    comment = Some(match comment {
+                        Some(comment) => format!("{} {}", marker, comment),
+                        None => marker.to_string(),
+                    });
+                }
 
-                    if decorated && !instruction.data.is_empty() {
-                        comment = decorate_push_data(&instruction.data, &selectors)
+                // times executed and the stack on the last visit, top first
+                let executed = hits.get(&instruction.offset);
+                if let Some(count) = executed {
+                    let stack = last_stack.get(&instruction.offset).copied().unwrap_or(&[]);
+                    let mut values: Vec<String> = stack
+                        .iter()
+                        .take(TRACE_STACK_DEPTH)
+                        .map(U256::to_string)
+                        .collect();
+                    if stack.len() > TRACE_STACK_DEPTH {
+                        values.push("...".to_string());
                     }
+                    let mut overlay = format!("x{} [{}]", count, values.join(", "));
 
-                    // JUMPDEST
-                    if instruction.opcode == 0x5b {
-                        if let Some(entry) = program
-                            .entrypoints
-                            .iter()
-                            .find(|f| return instruction.offset == f.offset)
-                        {
-                            let data = entry.selector;
-                            let val = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
-
-                            lines.push(Line {
-                                offset: section.start_pc + instruction.offset,
-                                kind: LineKind::Label(format!("0x{}", hex::encode(entry.selector))),
-                                comment: if decorated {
-                                    selectors.get(&val).cloned()
-                                } else {
-                                    None
-                                },
-                            });
+                    // one way of a conditional jump was never followed
+                    match branches.get(&instruction.offset) {
+                        Some(branch) if branch.jumped == 0 => overlay.push_str(" never jumps"),
+                        Some(branch) if branch.fell_through == 0 => {
+                            overlay.push_str(" never falls through")
                         }
+                        _ => {}
                     }
-
-                    lines.push(Line {
-                        offset: section.start_pc + instruction.offset,
-                        kind: LineKind::Instruction(instruction.clone()),
-                        comment,
+                    comment = Some(match comment {
+                        Some(comment) => format!("{} {}", overlay, comment),
+                        None => overlay,
                     });
                 }
-            } else {
+
                 lines.push(Line {
-                    offset: 0,
-                    kind: LineKind::HexDump(section.raw_bytes.to_vec()),
-                    comment: None,
+                    offset: section.start_pc + instruction.offset,
+                    kind: LineKind::Instruction(instruction.clone()),
+                    comment,
+                    executed: executed.is_some(),
                 });
             }
+        } else {
+            lines.push(Line {
+                offset: 0,
+                kind: LineKind::HexDump(section.raw_bytes.to_vec()),
+                comment: None,
+                executed: false,
+            });
         }
 
-        Self { lines }
+        for (n, child) in program
+            .children
+            .iter()
+            .filter(|child| child.parent == section.kind)
+            .enumerate()
+        {
+            let prefix = format!("{}.{}.child_{}", prefix, section.kind.name(), n);
+            lines.push(comment(format!(
+                "{}: creation code embedded at 0x{:04x}",
+                prefix, child.offset
+            )));
+            push_program(
+                lines,
+                &child.program,
+                &prefix,
+                decorated,
+                selectors,
+                &[],
+                &Sources::default(),
+                None,
+            );
+        }
     }
 }
 
+// Constant storage slots accessed by SLOAD and SSTORE, by instruction offset.
+fn storage_slots(instructions: &[Instruction]) -> HashMap<usize, usize> {
+    let mut slots = HashMap::new();
+
+    Cfg::from_instructions(instructions).walk_with::<Slot>(0, |instruction, inputs| {
+        if matches!(instruction.opcode, 0x54 | 0x55)
+            && let Some(Slot::Const(slot)) = inputs.first()
+        {
+            slots.entry(instruction.offset).or_insert(*slot);
+        }
+    });
+
+    slots
+}
+
 fn decorate_push_data(data: &[u8], selectors: &HashMap<u32, String>) -> Option<String> {
     if data.is_empty() {
         return None;
@@ -127,17 +311,17 @@ fn decorate_push_data(data: &[u8], selectors: &HashMap<u32, String>) -> Option<S
         let val = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
 
         if let Some(selector) = selectors.get(&val) {
-            return Some(format!("{}", selector));
+            return Some(selector.to_string());
         }
     }
 
     // if data is longer than two bytes and is purely printable characters we assume it's string
-    if data.len() > 2 && data.iter().all(|&b| b >= 0x20 && b <= 0x7e) {
+    if data.len() > 2 && data.iter().all(|&b| (0x20..=0x7e).contains(&b)) {
         let text = str::from_utf8(data).unwrap_or("");
         return Some(format!("{:?}", text));
     }
 
-    return None;
+    None
 }
 
 impl fmt::Display for Line {
@@ -152,13 +336,19 @@ impl fmt::Display for Line {
             return Ok(());
         }
 
-        write!(f, "  {:04x}: ", self.offset)?; // padded with two spaces
+        if let LineKind::Comment(text) = &self.kind {
+            return write!(f, "; {}", text);
+        }
+
+        // padded with two spaces, executed instructions are marked
+        let marker = if self.executed { "> " } else { "  " };
+        write!(f, "{}{:04x}: ", marker, self.offset)?;
 
         let content = match &self.kind {
             LineKind::Instruction(instruction) => format!("{}", instruction),
             LineKind::HexDump(bytes) => hex::encode(bytes),
             LineKind::Blank => String::new(),
-            LineKind::Label(_) => unreachable!(),
+            LineKind::Label(_) | LineKind::Comment(_) => unreachable!(),
         };
 
         write!(f, "{:<20}", content)?;
@@ -0,0 +1,174 @@
+use crate::{
+    abi,
+    analysis::{FunctionEntrypoint, infer_mutability},
+    cfg::Cfg,
+    parser::Instruction,
+};
+
+// bucket magic (2 bytes), bucket location (2 bytes), bucket size (1 byte)
+const DENSE_BUCKET_SIZE: usize = 5;
+// selector (4 bytes), function label (2 bytes), function info (1 byte)
+const DENSE_FUNCTION_SIZE: usize = 7;
+
+// Start of the data section holding the selector jump table. Since 0.3.10 the dispatcher
+// reads its bucket with `CODECOPY(32 - header size, table + bucket * header size, ...)`.
+pub fn data_section_start(instructions: &[Instruction]) -> Option<usize> {
+    let code_size = instructions
+        .last()
+        .map_or(0, |i| i.offset + 1 + i.data.len());
+
+    instructions
+        .windows(4)
+        .filter_map(|w| {
+            // PUSH table, ADD, PUSH1 30 (sparse) or 27 (dense), CODECOPY
+            let [table, add, dst, codecopy] = w else {
+                return None;
+            };
+            if !(0x60..=0x62).contains(&table.opcode)
+                || add.opcode != 0x01
+                || dst.opcode != 0x60
+                || !matches!(dst.data[..], [0x1E] | [0x1B])
+                || codecopy.opcode != 0x39
+            {
+                return None;
+            }
+
+            let table = bytes_to_usize(&table.data);
+            (table > codecopy.offset && table < code_size).then_some(table)
+        })
+        .min()
+}
+
+// Vyper checks each selector with `PUSH4 selector DUPn XOR PUSH next JUMPI` and the
+// function body follows the JUMPI, whether the checks are in a linear chain (before
+// 0.3.10) or grouped in the buckets of a sparse jump table. Dense tables (`-O codesize`)
+// list `selector | label | info` entries in the data section instead, `info` packs the
+// minimum calldata size with a nonpayable bit the dispatcher checks before jumping.
+pub fn analyze_entrypoints(instructions: &[Instruction], data: &[u8]) -> Vec<FunctionEntrypoint> {
+    let cfg = Cfg::from_instructions(instructions);
+    let entry = |selector: [u8; 4], offset: usize, dispatcher_guarded: bool| FunctionEntrypoint {
+        selector,
+        offset,
+        mutability: infer_mutability(&cfg, offset, dispatcher_guarded),
+        parameters: abi::infer_parameters(&cfg, offset),
+    };
+
+    let mut entrypoints: Vec<FunctionEntrypoint> = instructions
+        .windows(5)
+        .filter_map(|w| {
+            let opcodes: Vec<u8> = w.iter().map(|i| i.opcode).collect();
+            let push4 = match opcodes.as_slice() {
+                [0x63, 0x80..=0x8F, 0x18, 0x60..=0x62, 0x57] => &w[0],
+                [0x80..=0x8F, 0x63, 0x18, 0x60..=0x62, 0x57] => &w[1],
+                _ => return None,
+            };
+
+            let selector: [u8; 4] = push4.data[..].try_into().ok()?;
+            Some(entry(selector, w[4].offset + 1, false))
+        })
+        .collect();
+
+    if entrypoints.is_empty() {
+        let jumpdests: Vec<usize> = instructions
+            .iter()
+            .filter(|i| i.opcode == 0x5B)
+            .map(|i| i.offset)
+            .collect();
+
+        entrypoints = dense_table(data, instructions, &jumpdests)
+            .into_iter()
+            .map(|(selector, label, info)| entry(selector, label, info & 1 == 1))
+            .collect();
+    }
+
+    entrypoints
+}
+
+// Entries of a dense selector table, `data` is the data section as laid out right after
+// the runtime code. Bucket locations are absolute code offsets.
+fn dense_table(
+    data: &[u8],
+    instructions: &[Instruction],
+    jumpdests: &[usize],
+) -> Vec<([u8; 4], usize, u8)> {
+    let code_size = instructions
+        .last()
+        .map_or(0, |i| i.offset + 1 + i.data.len());
+    let mut entries = Vec::new();
+    let mut headers_end = data.len();
+
+    let mut bucket = 0;
+    while (bucket + 1) * DENSE_BUCKET_SIZE <= headers_end {
+        let header = &data[bucket * DENSE_BUCKET_SIZE..(bucket + 1) * DENSE_BUCKET_SIZE];
+        let location = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let size = header[4] as usize;
+
+        let Some(start) = location.checked_sub(code_size) else {
+            return Vec::new();
+        };
+        let end = start + size * DENSE_FUNCTION_SIZE;
+        if end > data.len() {
+            return Vec::new();
+        }
+        headers_end = headers_end.min(start);
+
+        for function in data[start..end].chunks(DENSE_FUNCTION_SIZE) {
+            let label = u16::from_be_bytes([function[4], function[5]]) as usize;
+            if !jumpdests.contains(&label) {
+                return Vec::new();
+            }
+            let selector = function[..4].try_into().expect("4 bytes");
+            entries.push((selector, label, function[6]));
+        }
+
+        bucket += 1;
+    }
+
+    entries
+}
+
+// Labels of the buckets of a sparse selector table, the dispatcher jumps to the one it
+// reads from the data section. Empty when the data isn't such a table.
+pub fn sparse_buckets(data: &[u8], jumpdests: &[usize]) -> Vec<usize> {
+    if !data.len().is_multiple_of(2) {
+        return Vec::new();
+    }
+    let labels: Vec<usize> = data.chunks(2).map(bytes_to_usize).collect();
+    if !labels.iter().all(|label| jumpdests.contains(label)) {
+        return Vec::new();
+    }
+    labels
+}
+
+fn bytes_to_usize(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |acc, &b| (acc << 8) | b as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::Mutability, parser::parse_bytecode};
+
+    #[test]
+    fn dense_selector_table() {
+        // JUMPDEST STOP, JUMPDEST PUSH0 SLOAD STOP
+        let code = parse_bytecode(&hex::decode("5b005b5f5400").unwrap());
+
+        // one bucket at 0x0b holding both functions, the second one nonpayable
+        let mut data = vec![0x00, 0x01, 0x00, 0x0b, 0x02];
+        data.extend([0x18, 0x16, 0x0d, 0xdd, 0x00, 0x00, 0x08]);
+        data.extend([0x70, 0xa0, 0x82, 0x31, 0x00, 0x02, 0x49]);
+
+        let found: Vec<_> = analyze_entrypoints(&code, &data)
+            .iter()
+            .map(|e| (hex::encode(e.selector), e.offset, e.mutability))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("18160ddd".to_string(), 0, Mutability::Payable),
+                ("70a08231".to_string(), 2, Mutability::View),
+            ]
+        );
+    }
+}
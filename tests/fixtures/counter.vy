@@ -0,0 +1,14 @@
+# pragma version ^0.3.7
+
+number: public(uint256)
+
+@external
+def setNumber(new_number: uint256):
+    self.number = new_number
+
+@external
+def increment():
+    self.number += 1
+
+# counter_vyper_0.3.7.bin: linear dispatch, metadata appended to the runtime code
+# counter_vyper_0.4.0.bin: sparse selector table in the data section, metadata in the initcode
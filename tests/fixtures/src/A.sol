@@ -0,0 +1,10 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+contract A {
+    function f() internal {}
+
+    fallback() external {
+        if (msg.value == 0) f();
+    }
+}
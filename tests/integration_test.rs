@@ -1,6 +1,36 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use scent::abi::reconstruct_abi;
 use scent::analysis::FunctionSelector;
+use scent::analysis::Mutability;
 use scent::analysis::analyze_function_selectors;
+use scent::coverage::analyze_coverage;
+use scent::deadcode::analyze_dead_code;
+use scent::decompile::decompile_program;
+use scent::detectors::{Severity, default_detectors, run_detectors};
+use scent::diff::{Status, diff_programs};
+use scent::emulator::{Environment, Outcome, execute};
+use scent::fingerprint::{compare, fingerprint_program};
+use scent::input::{Input, read_input};
+use scent::interfaces::detect_interfaces;
+use scent::ir::Ir;
+use scent::loader::{Program, SectionKind};
+use scent::loops::analyze_loops;
 use scent::parser::parse_bytecode;
+use scent::source::Sources;
+use scent::symbolic::{Terminator, explore_program};
+use scent::trace::Trace;
+use scent::u256::U256;
+use scent::view::View;
+
+const COUNTER: &str = include_str!("../assets/counter.bin");
+
+fn counter() -> (Input, Program) {
+    let input = read_input(Path::new("assets/counter.json"), false, None).unwrap();
+    let program = Program::load(&input.bytecode, false, input.runtime);
+    (input, program)
+}
 
 #[test]
 fn counter_contract_function_selectors() {
@@ -35,6 +65,147 @@ fn counter_contract_function_selectors() {
         }
     );
 }
+
+#[test]
+fn counter_contract_mutability() {
+    let input = hex::decode(COUNTER.trim().trim_start_matches("0x")).expect("invalid hex");
+    let program = Program::load(&input, false, false);
+
+    let mutability: Vec<_> = program.entrypoints.iter().map(|e| e.mutability).collect();
+    assert_eq!(
+        mutability,
+        vec![
+            Mutability::NonPayable, // setNumber(uint256)
+            Mutability::View,       // number()
+            Mutability::NonPayable, // increment()
+        ]
+    );
+}
+
+#[test]
+fn counter_contract_guessed_signatures() {
+    let input = hex::decode(COUNTER.trim().trim_start_matches("0x")).expect("invalid hex");
+    let program = Program::load(&input, false, false);
+
+    let signatures: Vec<_> = program
+        .entrypoints
+        .iter()
+        .map(|e| e.signature(&HashMap::new()).0)
+        .collect();
+    assert_eq!(
+        signatures,
+        vec![
+            "func_3fb5c1cb(uint256)",
+            "func_8381f58a()",
+            "func_d09de08a()"
+        ]
+    );
+}
+
+#[test]
+fn counter_contract_abi() {
+    let input = hex::decode(COUNTER.trim().trim_start_matches("0x")).expect("invalid hex");
+    let program = Program::load(&input, false, false);
+    let selectors = HashMap::from([(0x3fb5c1cb, "setNumber(uint256)".to_string())]);
+
+    let abi = reconstruct_abi(&program, &selectors);
+    let names: Vec<_> = abi.iter().map(|item| item.name.as_str()).collect();
+
+    assert_eq!(names, vec!["setNumber", "func_8381f58a", "func_d09de08a"]);
+    assert_eq!(abi[0].inputs[0].kind, "uint256");
+    assert!(abi[0].confidence > abi[1].confidence);
+}
+
+#[test]
+fn return_constant_contract_is_pure() {
+    let input: Vec<u8> = hex::decode("6080604052348015600e575f5ffd5b50608680601a5f395ff3fe6080604052348015600e575f5ffd5b50600436106026575f3560e01c80632096525514602a575b5f5ffd5b60306044565b604051603b91906062565b60405180910390f35b5f602a905090565b5f819050919050565b605c81604c565b82525050565b5f60208201905060735f8301846055565b9291505056fea164736f6c634300081e000a").expect("invalid hex"); // return_const.sol
+    let program = Program::load(&input, false, false);
+
+    assert_eq!(program.entrypoints.len(), 1);
+    assert_eq!(program.entrypoints[0].mutability, Mutability::Pure);
+}
+
+// Runtime code with a plain dispatcher jumping to a single `JUMPDEST STOP` for every selector.
+fn dispatcher(selectors: &[u32]) -> Vec<u8> {
+    let mut code = hex::decode("5f3560e01c").unwrap(); // PUSH0 CALLDATALOAD PUSH1 0xe0 SHR
+    let target = (code.len() + selectors.len() * 11 + 3) as u16;
+
+    for selector in selectors {
+        code.extend([0x80, 0x63]); // DUP1 PUSH4
+        code.extend(selector.to_be_bytes());
+        code.extend([0x14, 0x61]); // EQ PUSH2
+        code.extend(target.to_be_bytes());
+        code.push(0x57); // JUMPI
+    }
+    code.extend([0x5f, 0x5f, 0xfd, 0x5b, 0x00]); // PUSH0 PUSH0 REVERT JUMPDEST STOP
+
+    code
+}
+
+#[test]
+fn erc20_with_permit_interfaces() {
+    let code = dispatcher(&[
+        0x18160ddd, 0x70a08231, 0xa9059cbb, 0x23b872dd, 0x095ea7b3, 0xdd62ed3e, // ERC-20
+        0x06fdde03, 0x95d89b41, // name, symbol
+        0xd505accf, 0x7ecebe00, 0x3644e515, // ERC-2612
+    ]);
+    let program = Program::load(&code, false, true);
+
+    let summary: Vec<_> = detect_interfaces(&program)
+        .iter()
+        .map(|m| m.to_string())
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            "ERC-20 (complete)",
+            "ERC-20 metadata (partial, missing decimals())",
+            "ERC-2612 permit (complete)",
+        ]
+    );
+}
+
+#[test]
+fn erc721_is_not_a_partial_erc20() {
+    let mut code = dispatcher(&[
+        0x01ffc9a7, // supportsInterface
+        0x70a08231, 0x6352211e, 0x42842e0e, 0xb88d4fde, 0x23b872dd, 0x095ea7b3, 0xa22cb465,
+        0x081812fc, 0xe985e9c5, // ERC-721
+    ]);
+    // ERC-721 id as a left-aligned PUSH32, the metadata id shifted with PUSH1 0xe0 SHL
+    code.extend(hex::decode("7f80ac58cd").unwrap());
+    code.extend([0; 28]);
+    code.extend(hex::decode("635b5e139f60e01b").unwrap());
+    let program = Program::load(&code, false, true);
+
+    let summary: Vec<_> = detect_interfaces(&program)
+        .iter()
+        .map(|m| m.to_string())
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            "ERC-165 (complete)",
+            "ERC-721 (complete, advertised)",
+            "ERC-721 metadata (partial, missing name(), symbol(), tokenURI(uint256), advertised)",
+        ]
+    );
+}
+
+#[test]
+fn counter_foundry_artifact() {
+    let (input, program) = counter();
+    assert!(!input.runtime);
+    assert_eq!(input.selectors[&0x3fb5c1cb], "setNumber(uint256)");
+    assert_eq!(input.storage.len(), 1);
+    assert_eq!(input.storage[0].label, "number");
+    assert_eq!(program.entrypoints.len(), 3);
+
+    let runtime = read_input(Path::new("assets/counter.json"), true, None).unwrap();
+    assert!(runtime.runtime);
+    assert!(runtime.bytecode.starts_with(&program.sections[1].raw_bytes));
+}
+
 // must be run via cargo test -- --nocapture
 // #[test]
 // fn print_empty_contract_bytecode() {
@@ -59,3 +230,505 @@ fn counter_contract_function_selectors() {
 //     let view = View::from_instructions(&instructions, true);
 //     view.print_entries();
 // }
+
+#[test]
+fn factory_embedding_counter_creation_code() {
+    let child = hex::decode(COUNTER.trim().trim_start_matches("0x")).unwrap();
+    let metadata_start = child.len() - 0x35;
+
+    // PUSH2 <size> DUP1 PUSH2 0x000d PUSH0 CODECOPY PUSH0 CREATE STOP INVALID
+    let mut runtime = vec![0x61];
+    runtime.extend((child.len() as u16).to_be_bytes());
+    runtime.extend([0x80, 0x61, 0x00, 0x0d, 0x5f, 0x39, 0x5f, 0xf0, 0x00, 0xfe]);
+    runtime.extend(&child);
+    runtime.extend(&child[metadata_start..]); // factory metadata
+
+    // PUSH2 <size> DUP1 PUSH1 0x0b PUSH0 CODECOPY PUSH0 RETURN INVALID
+    let mut code = vec![0x61];
+    code.extend((runtime.len() as u16).to_be_bytes());
+    code.extend([0x80, 0x60, 0x0b, 0x5f, 0x39, 0x5f, 0xf3, 0xfe]);
+    code.extend(&runtime);
+
+    let program = Program::load(&code, false, false);
+
+    let kinds: Vec<_> = program.sections.iter().map(|s| s.kind.name()).collect();
+    assert_eq!(kinds, vec!["init", "runtime", "metadata"]);
+    assert_eq!(program.sections[1].raw_bytes.len(), 0x0d);
+
+    assert_eq!(program.children.len(), 1);
+    let embedded = &program.children[0];
+    assert!(embedded.parent == SectionKind::Runtime);
+    assert_eq!(embedded.offset, 0x0d);
+    assert_eq!(embedded.program.sections.len(), 3);
+    assert_eq!(embedded.program.entrypoints.len(), 3);
+}
+
+#[test]
+fn factory_copy_size_past_code() {
+    let child = hex::decode(COUNTER.trim().trim_start_matches("0x")).unwrap();
+    let metadata_start = child.len() - 0x35;
+
+    // PUSH8 0xffffffffffffffff PUSH1 0x12 PUSH0 CODECOPY PUSH0 PUSH0 PUSH0 CREATE STOP
+    let mut code = hex::decode("67ffffffffffffffff60125f395f5f5ff000").unwrap();
+    code.extend(&child);
+    code.extend(&child[metadata_start..]);
+
+    let program = Program::load(&code, false, true);
+    assert_eq!(program.sections[0].raw_bytes.len(), 0x12);
+    assert_eq!(program.children.len(), 1);
+    assert_eq!(program.children[0].offset, 0x12);
+    assert_eq!(program.children[0].program.entrypoints.len(), 3);
+}
+
+#[test]
+fn source_lines_interleaved_with_jumps() {
+    let input = read_input(Path::new("tests/fixtures/jumps.json"), false, None).unwrap();
+    assert!(input.runtime);
+
+    let map = input.sources.map(SectionKind::Runtime).unwrap();
+    assert_eq!(
+        input.sources.line(&map.entries[2]),
+        Some(("src/A.sol", 8, "if (msg.value == 0) f();"))
+    );
+
+    let program = Program::load(&input.bytecode, false, input.runtime);
+    let view = View::from_program(&program, false, HashMap::new(), &[], &input.sources, None);
+    let listing = view.to_string();
+
+    assert!(listing.contains("; src/A.sol:8: if (msg.value == 0) f();\n  0000: 34 CALLVALUE"));
+    assert!(listing.contains("  0003: 57 JUMPI            ; [-]\n"));
+    assert!(listing.contains("  0008: 56 JUMP             ; [i]\n"));
+    assert!(listing.contains("; src/A.sol:5: function f() internal {}\n  000b: 5b JUMPDEST"));
+    assert!(listing.contains("  000c: 56 JUMP             ; [o]\n"));
+}
+
+#[test]
+fn diff_ignores_shifted_code() {
+    let old = Program::load(&dispatcher(&[0x18160ddd, 0x70a08231]), false, true);
+    let new = Program::load(
+        &dispatcher(&[0x70a08231, 0x18160ddd, 0xa9059cbb]),
+        false,
+        true,
+    );
+
+    let diff = diff_programs(&old, &new, &HashMap::new());
+    let statuses: Vec<_> = diff
+        .functions
+        .iter()
+        .map(|f| (f.selector.as_str(), f.status))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            ("0x18160ddd", Status::Unchanged),
+            ("0x70a08231", Status::Unchanged),
+            ("0xa9059cbb", Status::Added),
+        ]
+    );
+    assert!(!diff.shared.is_empty());
+    assert!(!diff.is_identical());
+
+    assert!(diff_programs(&old, &old, &HashMap::new()).is_identical());
+}
+
+#[test]
+fn counter_redeployed_with_other_metadata_is_similar() {
+    let counter = hex::decode(COUNTER.trim().trim_start_matches("0x")).unwrap();
+    // same code built by another solc version
+    let mut copy = counter.clone();
+    let version = copy.len() - 3;
+    copy[version] = 0x1d;
+
+    let target = fingerprint_program(&Program::load(&counter, false, false));
+    let clone = compare(
+        &target,
+        &fingerprint_program(&Program::load(&copy, false, false)),
+    );
+    assert_eq!(clone.score, 1.0);
+    assert_eq!((clone.functions_matched, clone.functions_total), (3, 3));
+
+    let other = Program::load(&dispatcher(&[0x18160ddd, 0x70a08231]), false, true);
+    let other = compare(&target, &fingerprint_program(&other));
+    assert!(other.score < 0.2);
+    assert_eq!(other.functions_matched, 0);
+}
+
+#[test]
+fn vyper_linear_dispatch() {
+    let input = include_str!("fixtures/counter_vyper_0.3.7.bin");
+    let program = Program::load(&hex::decode(input.trim()).unwrap(), false, false);
+
+    let compiler = program.compiler.as_ref().unwrap();
+    assert_eq!(compiler.to_string(), "vyper 0.3.7");
+
+    let kinds: Vec<_> = program.sections.iter().map(|s| s.kind.name()).collect();
+    assert_eq!(kinds, vec!["init", "runtime", "metadata"]);
+
+    let entrypoints: Vec<_> = program
+        .entrypoints
+        .iter()
+        .map(|e| (hex::encode(e.selector), e.mutability))
+        .collect();
+    assert_eq!(
+        entrypoints,
+        vec![
+            ("3fb5c1cb".to_string(), Mutability::NonPayable),
+            ("8381f58a".to_string(), Mutability::View),
+            ("d09de08a".to_string(), Mutability::NonPayable),
+        ]
+    );
+}
+
+#[test]
+fn vyper_sparse_jump_table() {
+    let input = include_str!("fixtures/counter_vyper_0.4.0.bin");
+    let bytes = hex::decode(input.trim()).unwrap();
+    let program = Program::load(&bytes, false, false);
+
+    assert_eq!(
+        program.compiler.as_ref().unwrap().to_string(),
+        "vyper 0.4.0"
+    );
+    // the runtime size from the metadata ends the deploy code
+    assert_eq!(program.sections[0].raw_bytes.len(), 0x0b);
+
+    // selector buckets are data, not code
+    let data = program
+        .sections
+        .iter()
+        .find(|s| s.kind == SectionKind::Data)
+        .unwrap();
+    assert_eq!(data.raw_bytes.len(), 4);
+
+    let mut selectors: Vec<_> = program
+        .entrypoints
+        .iter()
+        .map(|e| hex::encode(e.selector))
+        .collect();
+    selectors.sort();
+    assert_eq!(selectors, vec!["3fb5c1cb", "8381f58a", "d09de08a"]);
+
+    // the same runtime code on its own, found through the dispatcher
+    let runtime = program
+        .sections
+        .iter()
+        .find(|s| s.kind == SectionKind::Runtime)
+        .unwrap();
+    let deployed = [runtime.raw_bytes.clone(), data.raw_bytes.clone()].concat();
+    let deployed = Program::load(&deployed, false, true);
+    assert_eq!(deployed.entrypoints.len(), 3);
+    assert!(
+        deployed
+            .sections
+            .iter()
+            .any(|s| s.kind == SectionKind::Data)
+    );
+}
+
+#[test]
+fn counter_deployed_and_called() {
+    let creation = hex::decode(COUNTER.trim().trim_start_matches("0x")).unwrap();
+    let Outcome::Return(runtime) = execute(&creation, &Environment::default(), false).outcome
+    else {
+        panic!("deployment failed");
+    };
+
+    // setNumber(41), increment(), number()
+    let mut env = Environment {
+        calldata: hex::decode(format!("3fb5c1cb{:064x}", 41)).unwrap(),
+        ..Default::default()
+    };
+    for calldata in ["d09de08a", "8381f58a"] {
+        env.storage = execute(&runtime, &env, false).storage;
+        env.calldata = hex::decode(calldata).unwrap();
+    }
+
+    let execution = execute(&runtime, &env, true);
+    assert_eq!(
+        execution.outcome,
+        Outcome::Return(U256::from_u64(42).to_be_bytes().to_vec())
+    );
+    assert_eq!(execution.steps[0].opcode, "PUSH1");
+
+    // nonpayable
+    env.callvalue = U256::ONE;
+    assert_eq!(
+        execute(&runtime, &env, false).outcome,
+        Outcome::Revert(Vec::new())
+    );
+}
+
+#[test]
+fn trace_overlaid_on_counter() {
+    let creation = hex::decode(COUNTER.trim().trim_start_matches("0x")).unwrap();
+    let program = Program::load(&creation, false, false);
+    let Outcome::Return(runtime) = execute(&creation, &Environment::default(), false).outcome
+    else {
+        panic!("deployment failed");
+    };
+
+    // number() with the counter at 7
+    let env = Environment {
+        calldata: hex::decode("8381f58a").unwrap(),
+        storage: [(U256::ZERO, U256::from_u64(7))].into(),
+        ..Default::default()
+    };
+    let trace = Trace::from_execution(&execute(&runtime, &env, true));
+
+    let view = View::from_program(
+        &program,
+        false,
+        HashMap::new(),
+        &[],
+        &Sources::default(),
+        Some(&trace),
+    );
+    let listing = view.to_string();
+
+    // only the runtime section is overlaid, setNumber() is never reached
+    assert!(listing.contains("  0000: 60 PUSH1 0x80       \n"));
+    assert!(listing.contains("> 0000: 60 PUSH1 0x80       ; x1 []"));
+    assert!(listing.contains("> 0066: 56 JUMP             ; x1 [0x90, 0x67, 0x8381f58a]"));
+    assert!(listing.contains("  0043: 5b JUMPDEST"));
+}
+
+#[test]
+fn counter_coverage_from_traces() {
+    let creation = hex::decode(COUNTER.trim().trim_start_matches("0x")).unwrap();
+    let Outcome::Return(runtime) = execute(&creation, &Environment::default(), false).outcome
+    else {
+        panic!("deployment failed");
+    };
+    let program = Program::load(&runtime, false, true);
+
+    // number(), then increment() overflowing
+    let mut env = Environment {
+        calldata: hex::decode("8381f58a").unwrap(),
+        ..Default::default()
+    };
+    let number = Trace::from_execution(&execute(&runtime, &env, true));
+    env.calldata = hex::decode("d09de08a").unwrap();
+    env.storage = [(U256::ZERO, U256::MAX)].into();
+    let increment = Trace::from_execution(&execute(&runtime, &env, true));
+
+    let coverage = analyze_coverage(&program, &[number, increment], &HashMap::new());
+    let calls: Vec<(&str, usize)> = coverage
+        .functions
+        .iter()
+        .map(|f| (f.selector.as_str(), f.calls))
+        .collect();
+    assert_eq!(
+        calls,
+        vec![("0x3fb5c1cb", 0), ("0x8381f58a", 1), ("0xd09de08a", 1)]
+    );
+    // setNumber() shares its return block with the other functions
+    assert!(coverage.functions[0].blocks.hit < coverage.functions[0].blocks.total);
+    assert_eq!(
+        coverage.functions[1].instructions.hit,
+        coverage.functions[1].instructions.total
+    );
+
+    // the overflow check always reverts
+    let increment = &coverage.functions[2];
+    assert!(
+        increment
+            .branches
+            .iter()
+            .any(|b| b.jumped == 0 && b.fell_through == 1)
+    );
+}
+
+#[test]
+fn counter_revert_conditions() {
+    let (input, program) = counter();
+    let exploration = explore_program(&program, &input.selectors);
+    assert_eq!(exploration.truncated, 0);
+
+    let increment = exploration
+        .functions
+        .iter()
+        .find(|f| f.signature == "increment()")
+        .unwrap();
+    let reverts: Vec<String> = increment
+        .paths
+        .iter()
+        .filter(|path| path.terminator == Terminator::Revert)
+        .map(|path| path.to_string())
+        .collect();
+    assert_eq!(
+        reverts,
+        vec![format!(
+            "0163: revert Panic(0x11)\n    if !(storage[0x0] - {})",
+            U256::MAX
+        )]
+    );
+
+    // nonpayable check before dispatching
+    assert!(exploration.shared.iter().any(|path| {
+        path.terminator == Terminator::Revert
+            && path
+                .conditions
+                .iter()
+                .map(|c| c.to_string())
+                .eq(["callvalue"])
+    }));
+
+    // vyper 0.4 dispatches through a jump table, only reached with the selector set
+    let input = include_str!("fixtures/counter_vyper_0.4.0.bin");
+    let program = Program::load(&hex::decode(input.trim()).unwrap(), false, false);
+    let exploration = explore_program(&program, &HashMap::new());
+
+    let set_number = exploration
+        .functions
+        .iter()
+        .find(|f| f.selector == "0x3fb5c1cb")
+        .unwrap();
+    let reverts: Vec<String> = set_number
+        .paths
+        .iter()
+        .filter(|path| path.terminator == Terminator::Revert)
+        .map(|path| path.to_string())
+        .collect();
+    assert_eq!(
+        reverts,
+        vec![
+            "0080: revert\n    if callvalue",
+            "0080: revert\n    if !callvalue\n    if (calldatasize < 0x24)"
+        ]
+    );
+    assert!(exploration.functions.iter().all(|f| !f.paths.is_empty()));
+}
+
+#[test]
+fn counter_decompiled() {
+    let (input, program) = counter();
+    let decompiled = decompile_program(&program, &input.selectors, &input.storage).to_string();
+
+    assert!(decompiled.contains(
+        "function setNumber(uint256 arg0) external { // 0x3fb5c1cb\n    \
+         require(!((msg.data.length - 0x4) s< 0x20));\n    number = arg0;\n}\n"
+    ));
+    assert!(
+        decompiled
+            .contains("function number() external view { // 0x8381f58a\n    return number;\n}\n")
+    );
+    assert!(decompiled.contains("    number = (number + 0x1);\n}\n"));
+}
+
+#[test]
+fn counter_ir() {
+    let program = Program::load(
+        &hex::decode(COUNTER.trim().trim_start_matches("0x")).unwrap(),
+        false,
+        false,
+    );
+    let runtime = program
+        .sections
+        .iter()
+        .find(|section| section.kind == SectionKind::Runtime)
+        .unwrap();
+    let ir = Ir::from_instructions(runtime.instructions.as_ref().unwrap());
+
+    let statements: Vec<String> = ir.blocks[&0x19]
+        .statements
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(
+        statements,
+        vec![
+            "v7 = CALLDATALOAD 0x0",
+            "v8 = SHR 0xe0, v7",
+            "v9 = EQ 0x3fb5c1cb, v8",
+            "JUMPI 0x43, v9"
+        ]
+    );
+
+    // no stack juggling is left
+    assert!(
+        ir.statements()
+            .all(|s| !matches!(s.opcode, 0x50 | 0x5F..=0x9F))
+    );
+    assert!(ir.blocks.values().any(|block| !block.phis.is_empty()));
+}
+
+#[test]
+fn loop_in_dispatched_function() {
+    // counter has internal functions called from several places but no loop
+    let (input, program) = counter();
+    assert!(analyze_loops(&program, &input.selectors).loops.is_empty());
+
+    // totalSupply() { for (i = 0; i < sload(0); i++) {} }
+    let bytecode = hex::decode(
+        "60003560e01c806318160ddd14601157005b60005b600054811015602457600101601456\
+         5b00",
+    )
+    .unwrap();
+    let program = Program::load(&bytecode, false, true);
+    let selectors = HashMap::from([(0x18160ddd, "totalSupply()".to_string())]);
+    let loops = analyze_loops(&program, &selectors).loops;
+
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].functions, vec!["0x18160ddd totalSupply()"]);
+    assert!(loops[0].unbounded);
+}
+
+#[test]
+fn counter_dead_code() {
+    let (_, program) = counter();
+    let dead = analyze_dead_code(&program);
+
+    // continuations of calls to reverting helpers and the INVALID before the metadata
+    let ranges: Vec<(usize, usize)> = dead.unreachable.iter().map(|r| (r.start, r.end)).collect();
+    assert_eq!(ranges, vec![(0xf8, 0xf9), (0x19f, 0x1a0), (0x1ab, 0x1ac)]);
+    assert!(dead.untargeted.is_empty());
+    assert!(dead.unresolved.is_empty());
+
+    // vyper 0.4 jumps to its selector buckets through a table read from the data section
+    let input = include_str!("fixtures/counter_vyper_0.4.0.bin");
+    let program = Program::load(&hex::decode(input.trim()).unwrap(), false, false);
+    let dead = analyze_dead_code(&program);
+    assert!(dead.unreachable.is_empty());
+    assert!(dead.untargeted.is_empty());
+    assert_eq!(dead.unresolved, vec![0x20]);
+}
+
+#[test]
+fn wallet_security_smells() {
+    let (input, program) = counter();
+    let findings = run_detectors(&program, &input.selectors, &default_detectors());
+    assert!(findings.findings.is_empty());
+
+    // kill() { require(tx.origin == sload(0)); selfdestruct(msg.sender); }
+    // execute(address) { target.delegatecall(""); }
+    // forward() { msg.sender.callcode(""); }
+    let bytecode = hex::decode(
+        "60003560e01c806341c0e1b514602857806312345678146038578063aabbccdd14604557600080fd5b\
+         6000543214603557600080fd5b33ff5b60008080806004355af450005b600080808080335af25000",
+    )
+    .unwrap();
+    let program = Program::load(&bytecode, false, true);
+    let selectors = HashMap::from([
+        (0x41c0e1b5, "kill()".to_string()),
+        (0x12345678, "execute(address)".to_string()),
+        (0xaabbccdd, "forward()".to_string()),
+    ]);
+    let findings = run_detectors(&program, &selectors, &default_detectors()).findings;
+
+    let found: Vec<(&str, usize, Option<&str>)> = findings
+        .iter()
+        .map(|f| (f.detector, f.offset, f.signature.as_deref()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("selfdestruct", 0x37, Some("kill()")),
+            ("delegatecall-calldata", 0x42, Some("execute(address)")),
+            ("tx-origin", 0x2d, Some("kill()")),
+            ("unchecked-call", 0x42, Some("execute(address)")),
+            ("unchecked-call", 0x4e, Some("forward()")),
+            ("callcode", 0x4e, Some("forward()")),
+        ]
+    );
+    assert_eq!(findings[0].severity, Severity::High);
+}